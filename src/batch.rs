@@ -0,0 +1,280 @@
+//! Column-wise (batched) evaluation.
+//!
+//! Instead of running a compiled tree once per `DataRow`, every node computes a whole column at once: a `Vec<f64>`
+//! holding its value for every row of the dataset. Only scalar types take part in this mode. Integers are carried as
+//! f64 inside the columns and converted back when a rule has to fall back to its per-value `func`. The output of an
+//! integer rule's column implementation is truncated and wrapped to i32, as when compiled trees run it on one row.
+//!
+//! Rules can opt in to a native column implementation through `NonTerminalRule::with_batch`. Rules without one
+//! still work, but their `func` runs once per row and allocates the box it returns every time, so they are about
+//! as slow as row-wise evaluation.
+
+use crate::{
+    compiled::{CompiledTree, Instruction, StackValue},
+    nonterminal::NonTerminalGrammar,
+    types::{DataType, Dataset, Shape, TypeInfo, VariableDefinitions},
+    utils::{l1_loss_to_reciprocal_fitness, scalar_as_f64, weighted_mean_loss, wrapping_i32},
+};
+use std::{any::Any, collections::HashMap};
+
+/// Column implementation of a rule: left column, right column, output column. All three have the same length.
+pub type BatchFn = fn(&[f64], &[f64], &mut [f64]);
+
+/// Dataset laid out by column, built once from a row based `Dataset`.
+#[derive(Debug, Clone)]
+pub struct ColumnarDataset {
+    columns: HashMap<String, Vec<f64>>,
+    targets: Vec<f64>,
+    weights: Option<Vec<f64>>,
+    len: usize,
+}
+
+impl ColumnarDataset {
+    /// Extracts one column per scalar variable, plus the target column.
+    /// Non-scalar variables are skipped; a tree referencing them fails to evaluate in batched mode.
+    pub fn from_dataset(dataset: &Dataset, variable_defs: &VariableDefinitions) -> Result<Self, String> {
+        let len = dataset.features.len();
+        let mut columns = HashMap::new();
+
+        for var in &variable_defs.variables {
+            if var._type.shape != Shape::Scalar {
+                continue;
+            }
+
+            let mut column = Vec::with_capacity(len);
+            for (row_idx, row) in dataset.features.iter().enumerate() {
+                let value = row
+                    .values
+                    .get(&var.name)
+                    .ok_or_else(|| format!("Row {}: missing variable '{}'", row_idx, var.name))?;
+                let value = scalar_as_f64(value.as_ref(), var._type.data_type).ok_or_else(|| {
+                    format!("Row {}: variable '{}' is not a {:?} scalar", row_idx, var.name, var._type.data_type)
+                })?;
+                column.push(value);
+            }
+            columns.insert(var.name.clone(), column);
+        }
+
+        let mut targets = Vec::with_capacity(len);
+        for (row_idx, target) in dataset.targets.iter().enumerate() {
+            let target = any_scalar_as_f64(target.as_ref())
+                .ok_or_else(|| format!("Row {}: unsupported target type", row_idx))?;
+            targets.push(target);
+        }
+
+        Ok(ColumnarDataset {
+            columns,
+            targets,
+            weights: dataset.weights.clone(),
+            len,
+        })
+    }
+
+    /// Column layout restricted to the given rows, for subsampled evaluation.
+    pub fn select(&self, rows: &[usize]) -> ColumnarDataset {
+        let pick = |column: &Vec<f64>| rows.iter().map(|&idx| column[idx]).collect::<Vec<f64>>();
+
+        ColumnarDataset {
+            columns: self.columns.iter().map(|(name, column)| (name.clone(), pick(column))).collect(),
+            targets: pick(&self.targets),
+            weights: self.weights.as_ref().map(pick),
+            len: rows.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn column(&self, name: &str) -> Option<&[f64]> {
+        self.columns.get(name).map(|column| column.as_slice())
+    }
+
+    pub fn targets(&self) -> &[f64] {
+        &self.targets
+    }
+
+    pub fn weights(&self) -> Option<&[f64]> {
+        self.weights.as_deref()
+    }
+}
+
+/// Checks that trees of `output_type` built from the grammar can be evaluated column-wise: the output and every
+/// input and output of every rule have to be scalars.
+pub fn check_batchable(grammar: &NonTerminalGrammar, output_type: TypeInfo) -> Result<(), String> {
+    if output_type.shape != Shape::Scalar {
+        return Err(format!("Batched evaluation only supports scalar outputs, got {}", output_type));
+    }
+    for rule in &grammar.rules {
+        let inputs = [rule.input_one_type, rule.input_two_type];
+        if inputs[..rule.arity()].iter().chain([&rule.output]).any(|type_info| type_info.shape != Shape::Scalar) {
+            return Err(format!("Rule {} is not scalar and cannot be evaluated column-wise", rule.signature()));
+        }
+    }
+    Ok(())
+}
+
+impl Dataset {
+    pub fn to_columns(&self, variable_defs: &VariableDefinitions) -> Result<ColumnarDataset, String> {
+        ColumnarDataset::from_dataset(self, variable_defs)
+    }
+}
+
+/// A column on the batched evaluation stack.
+#[derive(Debug)]
+enum Column<'r> {
+    Borrowed(&'r [f64]),
+    Owned(Vec<f64>),
+}
+
+impl Column<'_> {
+    fn as_slice(&self) -> &[f64] {
+        match self {
+            Column::Borrowed(column) => column,
+            Column::Owned(column) => column,
+        }
+    }
+}
+
+/// Reusable buffers for batched evaluation. Keeping one around across trees avoids reallocating the columns.
+#[derive(Debug, Default)]
+pub struct BatchScratch<'r> {
+    stack: Vec<Column<'r>>,
+    pool: Vec<Vec<f64>>,
+}
+
+impl<'r> BatchScratch<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn take_buffer(&mut self, len: usize) -> Vec<f64> {
+        let mut buffer = self.pool.pop().unwrap_or_default();
+        buffer.clear();
+        buffer.resize(len, 0.0);
+        buffer
+    }
+
+    fn recycle(&mut self, column: Column<'r>) {
+        if let Column::Owned(buffer) = column {
+            self.pool.push(buffer);
+        }
+    }
+}
+
+impl<'a> CompiledTree<'a> {
+    /// Runs the program over every row at once and returns the output column.
+    pub fn evaluate_columns<'r>(
+        &self,
+        data: &'r ColumnarDataset,
+        scratch: &mut BatchScratch<'r>,
+    ) -> Result<Vec<f64>, String> {
+        if self.output_type().shape != Shape::Scalar {
+            return Err(format!("Batched evaluation only supports scalar outputs, got {:?}", self.output_type()));
+        }
+
+        let len = data.len();
+        scratch.stack.clear();
+
+        for instruction in self.instructions() {
+            match *instruction {
+                Instruction::Constant(value) => {
+                    let value = any_scalar_as_f64(value)
+                        .ok_or("Batched evaluation only supports scalar constants")?;
+                    let mut buffer = scratch.take_buffer(len);
+                    buffer.fill(value);
+                    scratch.stack.push(Column::Owned(buffer));
+                }
+                Instruction::Variable { name, .. } => {
+                    let column = data
+                        .column(name)
+                        .ok_or_else(|| format!("Variable '{}' has no column in the batched dataset", name))?;
+                    scratch.stack.push(Column::Borrowed(column));
+                }
+                Instruction::Apply(rule) => {
+                    let right = match rule.arity() {
+                        1 => None,
+                        _ => Some(scratch.stack.pop().ok_or("Stack underflow in compiled tree")?),
+                    };
+                    let left = scratch.stack.pop().ok_or("Stack underflow in compiled tree")?;
+                    let mut out = scratch.take_buffer(len);
+
+                    match rule.batch_func {
+                        // Unary rules get their operand in both columns.
+                        Some(batch) => {
+                            let right = right.as_ref().unwrap_or(&left);
+                            batch(left.as_slice(), right.as_slice(), &mut out);
+                            if rule.output.data_type == DataType::Integer {
+                                out.iter_mut().for_each(|value| *value = wrapping_i32(*value) as f64);
+                            }
+                        }
+                        None => {
+                            if rule.input_one_type.shape != Shape::Scalar
+                                || rule.input_two_type.shape != Shape::Scalar
+                                || rule.output.shape != Shape::Scalar
+                            {
+                                return Err(format!(
+                                    "Rule {:?} is not scalar and cannot be evaluated column-wise",
+                                    rule.operation
+                                ));
+                            }
+
+                            // Fall back to the per-value function, one row at a time. The inputs live on the stack,
+                            // only the result `func` returns is allocated.
+                            for (i, slot) in out.iter_mut().enumerate() {
+                                let a = StackValue::scalar(left.as_slice()[i], rule.input_one_type.data_type);
+                                let b = right
+                                    .as_ref()
+                                    .map(|right| StackValue::scalar(right.as_slice()[i], rule.input_two_type.data_type));
+                                let result = rule.execute_children(a.as_any(), b.as_ref().map(|b| b.as_any()));
+                                *slot = scalar_as_f64(result.as_ref(), rule.output.data_type).ok_or_else(|| {
+                                    format!("Rule {:?} returned a value that is not {:?}", rule.operation, rule.output)
+                                })?;
+                            }
+                        }
+                    }
+
+                    scratch.recycle(left);
+                    if let Some(right) = right {
+                        scratch.recycle(right);
+                    }
+                    scratch.stack.push(Column::Owned(out));
+                }
+            }
+        }
+
+        match scratch.stack.pop() {
+            Some(Column::Owned(column)) => Ok(column),
+            Some(Column::Borrowed(column)) => Ok(column.to_vec()),
+            None => Err("Compiled tree produced no value".to_string()),
+        }
+    }
+
+    /// Batched equivalent of `CompiledTree::evaluate_fitness`.
+    pub fn evaluate_fitness_columns<'r>(
+        &self,
+        data: &'r ColumnarDataset,
+        scratch: &mut BatchScratch<'r>,
+    ) -> Result<f64, String> {
+        let predictions = self.evaluate_columns(data, scratch)?;
+        let losses = predictions
+            .iter()
+            .zip(data.targets())
+            .map(|(prediction, target)| (prediction - target).abs());
+        let (agg_loss, total_weight): (f64, f64) = match data.weights() {
+            Some(weights) => (losses.zip(weights).map(|(loss, weight)| loss * weight).sum(), weights.iter().sum()),
+            None => (losses.sum(), predictions.len() as f64),
+        };
+        scratch.pool.push(predictions);
+
+        Ok(l1_loss_to_reciprocal_fitness(weighted_mean_loss(agg_loss, total_weight)))
+    }
+}
+
+fn any_scalar_as_f64(value: &dyn Any) -> Option<f64> {
+    scalar_as_f64(value, DataType::Float).or_else(|| scalar_as_f64(value, DataType::Integer))
+}
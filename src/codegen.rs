@@ -0,0 +1,693 @@
+//! Export of trees as standalone source code: Rust, Python/NumPy and C99.
+//!
+//! The generated function takes every variable of the `VariableDefinitions`, in order, and binds one value per
+//! nonterminal and constant, so the output reads like the tree it came from. Variables are used directly.
+//! How a rule is computed comes from the source template it carries for the `Target`
+//! (see `NonTerminalRule::with_template`, unary rules only have `{0}`), since only the user knows what its `func`
+//! does. Without a template,
+//! element-wise `Add` and `Subtract`, and scalar `Multiply`, fall back to the operator of their `Operation`.
+//! Division never does: protected division conventions differ too much to guess. Integer arithmetic wraps on
+//! overflow as in evaluation, through `wrapping_*` in Rust and unsigned arithmetic in C.
+//!
+//! Variable names are turned into identifiers of the target: characters other than letters, digits and `_` become
+//! `_`, and names that are keywords or clash with the generated code get a trailing `_`, e.g. `x-1` is `x_1` and
+//! `class` is `class_` in Python.
+//!
+//! - Rust: scalars are `i32`/`f64`, vectors fixed size arrays and matrices arrays of rows.
+//! - Python: the function is vectorized with NumPy. Each argument holds every row, scalars have shape `(n,)`,
+//!   vectors `(n, size)` and matrices `(n, rows, cols)`.
+//! - C99: scalars are `int32_t`/`double` and arrays are passed as `const` arrays. A non-scalar result is written
+//!   to a trailing `out` parameter. C templates for non-scalar outputs are statements assigning `{out}`.
+//!
+//! The verification helpers generate a program embedding a `Dataset` and the outputs of `ParseTree` evaluation
+//! on it, run the exported function over every row and report the rows that disagree.
+
+use crate::{
+    node::NodeType,
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    tree_builder::ParseTree,
+    types::{DataType, Dataset, EvalInput, Shape, TypeInfo, VariableDefinitions},
+    utils::flatten_as_f64,
+};
+use std::{
+    collections::HashMap,
+    io::Write,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Language a rule template is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    Rust,
+    Python,
+    C,
+}
+
+/// Relative and absolute tolerance of the verification programs.
+const TOLERANCE: f64 = 1e-9;
+
+static VERIFY_RUN: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct CodeGenerator<'a> {
+    grammar: &'a NonTerminalGrammar,
+    variable_definitions: &'a VariableDefinitions,
+    function_name: String,
+}
+
+impl<'a> CodeGenerator<'a> {
+    pub fn new(grammar: &'a NonTerminalGrammar, variable_definitions: &'a VariableDefinitions) -> Self {
+        CodeGenerator {
+            grammar,
+            variable_definitions,
+            function_name: "model".to_string(),
+        }
+    }
+
+    pub fn with_function_name(mut self, function_name: &str) -> Self {
+        self.function_name = function_name.to_string();
+        self
+    }
+
+    /// Emits `pub fn model(x: i32, ...) -> T`. Fails if a rule used by the tree has no Rust template.
+    pub fn rust(&self, tree: &ParseTree) -> Result<String, String> {
+        let identifiers = self.identifiers(Target::Rust);
+        let (body, result) = self.emit_body(tree, Target::Rust, &identifiers)?;
+
+        let params: Vec<String> = self
+            .variable_definitions
+            .variables
+            .iter()
+            .map(|var| format!("{}: {}", identifiers[&var.name], rust_type(var._type)))
+            .collect();
+
+        let mut source = format!("// Generated by stsr from {}\n", tree);
+        if self.has_unused_variables(tree) {
+            source.push_str("#[allow(unused_variables)]\n");
+        }
+        source.push_str(&format!(
+            "pub fn {}({}) -> {} {{\n",
+            self.function_name,
+            params.join(", "),
+            rust_type(tree.tree[0]._type.output_type())
+        ));
+        for line in body {
+            source.push_str(&format!("    {}\n", line));
+        }
+        source.push_str(&format!("    {}\n}}\n", result));
+        Ok(source)
+    }
+
+    /// Emits a NumPy function `def model(x, ...)` evaluating every row at once.
+    pub fn python(&self, tree: &ParseTree) -> Result<String, String> {
+        let identifiers = self.identifiers(Target::Python);
+        let (body, result) = self.emit_body(tree, Target::Python, &identifiers)?;
+        let params = self.parameters(&identifiers);
+
+        let mut source = format!("# Generated by stsr from {}\nimport numpy as np\n\n\n", tree);
+        source.push_str(&format!("def {}({}):\n", self.function_name, params.join(", ")));
+        source.push_str(
+            "    \"\"\"Vectorized over rows: scalars have shape (n,), vectors (n, size) and matrices (n, rows, cols).\"\"\"\n",
+        );
+        for line in body {
+            source.push_str(&format!("    {}\n", line));
+        }
+        source.push_str(&format!("    return {}\n", result));
+        Ok(source)
+    }
+
+    /// Emits a C99 function. Scalar results are returned, others written to the trailing `out` parameter.
+    pub fn c(&self, tree: &ParseTree) -> Result<String, String> {
+        let identifiers = self.identifiers(Target::C);
+        let (body, result) = self.emit_body(tree, Target::C, &identifiers)?;
+        let output_type = tree.tree[0]._type.output_type();
+
+        let mut params: Vec<String> = self
+            .variable_definitions
+            .variables
+            .iter()
+            .map(|var| c_declaration(var._type, &identifiers[&var.name], var._type.shape != Shape::Scalar))
+            .collect();
+        if output_type.shape != Shape::Scalar {
+            params.push(c_declaration(output_type, "out", false));
+        }
+        if params.is_empty() {
+            params.push("void".to_string());
+        }
+
+        let mut source = format!(
+            "/* Generated by stsr from {} */\n#include <math.h>\n#include <stdint.h>\n#include <string.h>\n\n",
+            tree
+        );
+        let return_type = match output_type.shape {
+            Shape::Scalar => c_scalar_type(output_type.data_type),
+            _ => "void",
+        };
+        source.push_str(&format!("{} {}({}) {{\n", return_type, self.function_name, params.join(", ")));
+        for line in body {
+            source.push_str(&format!("    {}\n", line));
+        }
+        match output_type.shape {
+            Shape::Scalar => source.push_str(&format!("    return {};\n}}\n", result)),
+            _ => source.push_str(&format!(
+                "    memcpy(out, {}, sizeof({}) * {});\n}}\n",
+                result,
+                c_scalar_type(output_type.data_type),
+                element_count(output_type)
+            )),
+        }
+        Ok(source)
+    }
+
+    /// Python script that checks `python(tree)` against `ParseTree` evaluation on every row of the dataset.
+    /// It prints `ok` and exits with status 0 when all outputs match.
+    pub fn python_verification_script(&self, tree: &ParseTree, dataset: &Dataset) -> Result<String, String> {
+        let expected = self.expected_outputs(tree, dataset)?;
+        let output_type = tree.tree[0]._type.output_type();
+        let rows = dataset.len();
+
+        let identifiers = self.identifiers(Target::Python);
+        let mut script = self.python(tree)?;
+        script.push_str("\n\nimport sys\n\n");
+        script.push_str(&format!("_rows = {}\n", rows));
+        for var in &self.variable_definitions.variables {
+            let values = self.variable_column(dataset, &var.name, var._type)?;
+            script.push_str(&format!(
+                "{} = np.array([{}], dtype={}).reshape((_rows,) + {})\n",
+                identifiers[&var.name],
+                values.iter().map(|v| python_number(*v, var._type.data_type)).collect::<Vec<String>>().join(", "),
+                python_dtype(var._type.data_type),
+                python_shape(var._type.shape)
+            ));
+        }
+        script.push_str(&format!(
+            "_expected = np.array([{}], dtype=np.float64).reshape((_rows, {}))\n",
+            expected.iter().map(|v| python_number(*v, DataType::Float)).collect::<Vec<String>>().join(", "),
+            element_count(output_type)
+        ));
+        script.push_str(&format!(
+            "_result = np.broadcast_to(np.asarray({}({}), dtype=np.float64), (_rows,) + {}).reshape((_rows, {}))\n",
+            self.function_name,
+            self.parameters(&identifiers).join(", "),
+            python_shape(output_type.shape),
+            element_count(output_type)
+        ));
+        script.push_str(&format!(
+            "_mismatches = ~np.isclose(_result, _expected, rtol={:e}, atol={:e}, equal_nan=True)\n",
+            TOLERANCE, TOLERANCE
+        ));
+        script.push_str(
+            "if _mismatches.any():\n    _row = int(np.argwhere(_mismatches)[0][0])\n    \
+             print(f\"row {_row}: expected {_expected[_row]}, got {_result[_row]}\")\n    sys.exit(1)\nprint(\"ok\")\n",
+        );
+        Ok(script)
+    }
+
+    /// C99 program that checks `c(tree)` against `ParseTree` evaluation on every row of the dataset.
+    /// It prints `ok` and exits with status 0 when all outputs match. Link it with `-lm`.
+    pub fn c_verification_program(&self, tree: &ParseTree, dataset: &Dataset) -> Result<String, String> {
+        let expected = self.expected_outputs(tree, dataset)?;
+        let output_type = tree.tree[0]._type.output_type();
+        let outputs = element_count(output_type);
+        let rows = dataset.len();
+
+        let identifiers = self.identifiers(Target::C);
+        let mut program = self.c(tree)?;
+        program.push_str("\n#include <stdio.h>\n\n");
+        for var in &self.variable_definitions.variables {
+            let values = self.variable_column(dataset, &var.name, var._type)?;
+            program.push_str(&format!(
+                "static const {} {}_data[{}]{} = {{{}}};\n",
+                c_scalar_type(var._type.data_type),
+                identifiers[&var.name],
+                rows,
+                c_dimensions(var._type.shape),
+                values.iter().map(|v| c_number(*v, var._type.data_type)).collect::<Vec<String>>().join(", ")
+            ));
+        }
+        program.push_str(&format!(
+            "static const double expected[{}][{}] = {{{}}};\n\n",
+            rows,
+            outputs,
+            expected.iter().map(|v| c_number(*v, DataType::Float)).collect::<Vec<String>>().join(", ")
+        ));
+
+        let mut args: Vec<String> = self
+            .parameters(&identifiers)
+            .iter()
+            .map(|identifier| format!("{}_data[row]", identifier))
+            .collect();
+        program.push_str("int main(void) {\n    int failures = 0;\n");
+        program.push_str(&format!("    for (int row = 0; row < {}; row++) {{\n", rows));
+        match output_type.shape {
+            Shape::Scalar => {
+                program.push_str(&format!(
+                    "        {} result[1];\n        result[0] = {}({});\n",
+                    c_scalar_type(output_type.data_type),
+                    self.function_name,
+                    args.join(", ")
+                ));
+            }
+            _ => {
+                args.push("result".to_string());
+                program.push_str(&format!(
+                    "        {};\n        {}({});\n",
+                    c_declaration(output_type, "result", false),
+                    self.function_name,
+                    args.join(", ")
+                ));
+            }
+        }
+        program.push_str(&format!(
+            "        const {} *values = (const {} *) result;\n",
+            c_scalar_type(output_type.data_type),
+            c_scalar_type(output_type.data_type)
+        ));
+        program.push_str(&format!("        for (int i = 0; i < {}; i++) {{\n", outputs));
+        program.push_str(&format!(
+            "            double got = (double) values[i], want = expected[row][i];\n            \
+             int same = (isnan(got) && isnan(want)) || got == want || fabs(got - want) <= {:e} + {:e} * fabs(want);\n",
+            TOLERANCE, TOLERANCE
+        ));
+        program.push_str(
+            "            if (!same) {\n                \
+             printf(\"row %d, element %d: expected %.17g, got %.17g\\n\", row, i, want, got);\n                \
+             failures++;\n            }\n        }\n    }\n    \
+             if (failures > 0) {\n        return 1;\n    }\n    printf(\"ok\\n\");\n    return 0;\n}\n",
+        );
+        Ok(program)
+    }
+
+    /// Runs `python_verification_script` with the given interpreter, e.g. `"python3"`.
+    pub fn verify_python(&self, tree: &ParseTree, dataset: &Dataset, python: &str) -> Result<(), String> {
+        let script = self.python_verification_script(tree, dataset)?;
+
+        let mut child = Command::new(python)
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Cannot run '{}': {}", python, err))?;
+        child
+            .stdin
+            .take()
+            .ok_or("Cannot write to the Python interpreter")?
+            .write_all(script.as_bytes())
+            .map_err(|err| format!("Cannot write to the Python interpreter: {}", err))?;
+        let output = child.wait_with_output().map_err(|err| format!("Cannot run '{}': {}", python, err))?;
+
+        check_output("Python", &output)
+    }
+
+    /// Compiles `c_verification_program` with the given compiler, e.g. `"cc"`, in a temporary directory and runs it.
+    pub fn verify_c(&self, tree: &ParseTree, dataset: &Dataset, compiler: &str) -> Result<(), String> {
+        let program = self.c_verification_program(tree, dataset)?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "stsr-verify-{}-{}",
+            std::process::id(),
+            VERIFY_RUN.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).map_err(|err| format!("Cannot create '{}': {}", dir.display(), err))?;
+        let source = dir.join("verify.c");
+        let binary = dir.join("verify");
+
+        let result = (|| {
+            std::fs::write(&source, program).map_err(|err| format!("Cannot write '{}': {}", source.display(), err))?;
+            let compiled = Command::new(compiler)
+                .arg("-std=c99")
+                .arg("-o")
+                .arg(&binary)
+                .arg(&source)
+                .arg("-lm")
+                .output()
+                .map_err(|err| format!("Cannot run '{}': {}", compiler, err))?;
+            check_output("C compiler", &compiled)?;
+
+            let output = Command::new(&binary)
+                .output()
+                .map_err(|err| format!("Cannot run '{}': {}", binary.display(), err))?;
+            check_output("C program", &output)
+        })();
+
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    fn has_unused_variables(&self, tree: &ParseTree) -> bool {
+        self.variable_definitions
+            .variables
+            .iter()
+            .any(|var| !tree.tree.iter().any(|node| node.variable_id.as_ref() == Some(&var.name)))
+    }
+
+    /// Identifier of each variable in the target language, see the module documentation.
+    fn identifiers(&self, target: Target) -> HashMap<String, String> {
+        let mut taken: Vec<String> = Vec::new();
+        let mut identifiers = HashMap::new();
+
+        for var in &self.variable_definitions.variables {
+            let mut identifier: String =
+                var.name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+            if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                identifier.insert(0, '_');
+            }
+            while self.is_reserved(&identifier, target) || taken.contains(&identifier) {
+                identifier.push('_');
+            }
+            taken.push(identifier.clone());
+            identifiers.insert(var.name.clone(), identifier);
+        }
+        identifiers
+    }
+
+    /// Keywords of the target, and names the generated code and verification programs use themselves.
+    fn is_reserved(&self, identifier: &str, target: Target) -> bool {
+        let keywords: &[&str] = match target {
+            Target::Rust => &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+                "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+                "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+                "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try",
+                "typeof", "unsized", "virtual", "yield", "core", "_", "i", "r", "c",
+            ],
+            Target::Python => &[
+                "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+                "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+                "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield", "np",
+                "sys", "_rows", "_expected", "_result", "_mismatches", "_row",
+            ],
+            Target::C => &[
+                "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+                "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return",
+                "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+                "volatile", "while", "int32_t", "uint32_t", "memcpy", "fabs", "isnan", "printf", "main", "expected",
+                "failures", "row", "result", "values", "i", "r", "c", "got", "want", "same",
+            ],
+        };
+        // Bindings are `n<index>`, the non-scalar result is `out`.
+        let binding = identifier.strip_prefix('n').is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()));
+        keywords.contains(&identifier)
+            || binding
+            || identifier == "out"
+            || identifier == self.function_name
+            || (target == Target::C && identifier.ends_with("_data"))
+    }
+
+    /// Identifiers of the variables, in parameter order.
+    fn parameters<'i>(&self, identifiers: &'i HashMap<String, String>) -> Vec<&'i str> {
+        self.variable_definitions.variables.iter().map(|var| identifiers[&var.name].as_str()).collect()
+    }
+
+    /// Statements computing the tree, and the expression holding the root's value.
+    fn emit_body(
+        &self,
+        tree: &ParseTree,
+        target: Target,
+        identifiers: &HashMap<String, String>,
+    ) -> Result<(Vec<String>, String), String> {
+        if tree.tree.is_empty() {
+            return Err("Cannot export an empty tree".to_string());
+        }
+
+        let mut body = Vec::new();
+        let result = self.emit_node(tree, 0, target, identifiers, &mut body)?;
+        Ok((body, result))
+    }
+
+    /// Appends the statements computing the subtree at idx to `body` and returns the expression holding its value.
+    fn emit_node(
+        &self,
+        tree: &ParseTree,
+        idx: usize,
+        target: Target,
+        identifiers: &HashMap<String, String>,
+        body: &mut Vec<String>,
+    ) -> Result<String, String> {
+        let node = &tree.tree[idx];
+        let output_type = node._type.output_type();
+        let binding = format!("n{}", idx);
+
+        match (node._type, node.left_index) {
+            (NodeType::NonTerminal(_, _, _, _), Some(left_idx)) => {
+                let left = self.emit_node(tree, left_idx, target, identifiers, body)?;
+                let right = match node.right_index {
+                    Some(right_idx) => self.emit_node(tree, right_idx, target, identifiers, body)?,
+                    None => String::new(),
+                };
+
+                let rule = tree.find_matching_rule_for_node(idx, self.grammar)?;
+                let template = match rule.template(target) {
+                    Some(template) => template.to_string(),
+                    None => default_template(rule, target)
+                        .ok_or_else(|| format!("Rule {} has no {:?} template", rule.signature(), target))?,
+                };
+                let code = template.replace("{0}", &left).replace("{1}", &right).replace("{out}", &binding);
+
+                match target {
+                    Target::Rust => body.push(format!("let {}: {} = {};", binding, rust_type(output_type), code)),
+                    Target::Python => body.push(format!("{} = {}", binding, code)),
+                    Target::C if output_type.shape == Shape::Scalar => {
+                        body.push(format!("{} = {};", c_declaration(output_type, &binding, false), code))
+                    }
+                    Target::C => {
+                        if !template.contains("{out}") {
+                            return Err(format!(
+                                "C template of rule {} -> {} must assign its result to {{out}}",
+                                rule.operation, rule.output
+                            ));
+                        }
+                        body.push(format!("{};", c_declaration(output_type, &binding, false)));
+                        body.push(code);
+                    }
+                }
+                Ok(binding)
+            }
+            (NodeType::Terminal(_), None) => match &node.variable_id {
+                Some(name) => identifiers
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("Node {}: variable '{}' is not defined", idx, name)),
+                None => {
+                    let elements = flatten_as_f64(node.value.as_ref(), output_type)
+                        .ok_or_else(|| format!("Node {}: constant is not stored as {}", idx, output_type))?;
+                    body.push(match target {
+                        Target::Rust => format!(
+                            "let {}: {} = {};",
+                            binding,
+                            rust_type(output_type),
+                            nested_literal(&elements, output_type, ("[", "]"), |v| rust_number(v, output_type.data_type))
+                        ),
+                        Target::Python => {
+                            let literal =
+                                nested_literal(&elements, output_type, ("[", "]"), |v| python_number(v, output_type.data_type));
+                            match output_type.shape {
+                                Shape::Scalar => format!("{} = {}", binding, literal),
+                                _ => format!("{} = np.array({}, dtype={})", binding, literal, python_dtype(output_type.data_type)),
+                            }
+                        }
+                        Target::C => format!(
+                            "{} = {};",
+                            c_declaration(output_type, &binding, true),
+                            nested_literal(&elements, output_type, ("{", "}"), |v| c_number(v, output_type.data_type))
+                        ),
+                    });
+                    Ok(binding)
+                }
+            },
+            _ => Err(format!("Node {}: invalid node configuration", idx)),
+        }
+    }
+
+    /// Output of `ParseTree` evaluation for every row, flattened row-major.
+    fn expected_outputs(&self, tree: &ParseTree, dataset: &Dataset) -> Result<Vec<f64>, String> {
+        if dataset.is_empty() {
+            return Err("Cannot verify against an empty dataset".to_string());
+        }
+
+        let program = tree.compile(self.grammar, self.variable_definitions)?;
+        let mut stack = program.new_stack();
+        let mut expected = Vec::new();
+        for (row_idx, EvalInput::Data(row, _, _)) in dataset.iter().enumerate() {
+            let value = program.evaluate_row(row, &mut stack)?;
+            let elements = flatten_as_f64(value.as_any(), program.output_type())
+                .ok_or_else(|| format!("Row {}: output is not stored as {}", row_idx, program.output_type()))?;
+            expected.extend(elements);
+        }
+        Ok(expected)
+    }
+
+    fn variable_column(&self, dataset: &Dataset, name: &str, type_info: TypeInfo) -> Result<Vec<f64>, String> {
+        let mut column = Vec::new();
+        for (row_idx, row) in dataset.features.iter().enumerate() {
+            let value = row
+                .values
+                .get(name)
+                .ok_or_else(|| format!("Row {}: missing variable '{}'", row_idx, name))?;
+            column.extend(
+                flatten_as_f64(value.as_ref(), type_info)
+                    .ok_or_else(|| format!("Row {}: variable '{}' is not stored as {}", row_idx, name, type_info))?,
+            );
+        }
+        Ok(column)
+    }
+}
+
+/// Template derived from the rule's `Operation`, for rules where the target's own operator has the same meaning.
+fn default_template(rule: &NonTerminalRule, target: Target) -> Option<String> {
+    if rule.input_one_type != rule.output || rule.input_two_type != rule.output {
+        return None;
+    }
+    let (symbol, wrapping) = match (rule.operation, rule.output.shape) {
+        (Operation::Add, _) => ("+", "wrapping_add"),
+        (Operation::Subtract, _) => ("-", "wrapping_sub"),
+        // Multiplying two matrices may just as well mean a matrix product.
+        (Operation::Multiply, Shape::Scalar) => ("*", "wrapping_mul"),
+        _ => return None,
+    };
+    // NumPy's int32 arrays already wrap, Rust would panic in debug builds and C has undefined behaviour instead.
+    let apply = |left: &str, right: &str| match (target, rule.output.data_type) {
+        (Target::Rust, DataType::Integer) => format!("{}.{}({})", left, wrapping, right),
+        (Target::C, DataType::Integer) => format!("(int32_t) ((uint32_t) {} {} (uint32_t) {})", left, symbol, right),
+        _ => format!("{} {} {}", left, symbol, right),
+    };
+
+    Some(match (target, rule.output.shape) {
+        (_, Shape::Scalar) | (Target::Python, _) => apply("{0}", "{1}"),
+        (Target::Rust, Shape::Vector(_)) => format!("core::array::from_fn(|i| {})", apply("{0}[i]", "{1}[i]")),
+        (Target::Rust, Shape::Matrix(_, _)) => format!(
+            "core::array::from_fn(|r| core::array::from_fn(|c| {}))",
+            apply("{0}[r][c]", "{1}[r][c]")
+        ),
+        (Target::C, Shape::Vector(size)) => {
+            format!("for (int i = 0; i < {}; i++) {{out}}[i] = {};", size, apply("{0}[i]", "{1}[i]"))
+        }
+        (Target::C, Shape::Matrix(rows, cols)) => format!(
+            "for (int r = 0; r < {}; r++) for (int c = 0; c < {}; c++) {{out}}[r][c] = {};",
+            rows,
+            cols,
+            apply("{0}[r][c]", "{1}[r][c]")
+        ),
+    })
+}
+
+fn element_count(type_info: TypeInfo) -> usize {
+    match type_info.shape {
+        Shape::Scalar => 1,
+        Shape::Vector(size) => size,
+        Shape::Matrix(rows, cols) => rows * cols,
+    }
+}
+
+/// Literal of a constant from its row-major elements, with the given array delimiters.
+fn nested_literal(elements: &[f64], type_info: TypeInfo, delimiters: (&str, &str), number: impl Fn(f64) -> String) -> String {
+    let (open, close) = delimiters;
+    let row = |values: &[f64]| format!("{}{}{}", open, values.iter().map(|v| number(*v)).collect::<Vec<String>>().join(", "), close);
+
+    match type_info.shape {
+        Shape::Scalar => number(elements[0]),
+        Shape::Vector(_) => row(elements),
+        Shape::Matrix(_, cols) => format!(
+            "{}{}{}",
+            open,
+            elements.chunks(cols).map(row).collect::<Vec<String>>().join(", "),
+            close
+        ),
+    }
+}
+
+fn rust_type(type_info: TypeInfo) -> String {
+    let scalar = match type_info.data_type {
+        DataType::Integer => "i32",
+        DataType::Float => "f64",
+    };
+    match type_info.shape {
+        Shape::Scalar => scalar.to_string(),
+        Shape::Vector(size) => format!("[{}; {}]", scalar, size),
+        Shape::Matrix(rows, cols) => format!("[[{}; {}]; {}]", scalar, cols, rows),
+    }
+}
+
+fn rust_number(value: f64, data_type: DataType) -> String {
+    match data_type {
+        DataType::Integer => (value as i32).to_string(),
+        DataType::Float if value.is_nan() => "f64::NAN".to_string(),
+        DataType::Float if value.is_infinite() => {
+            if value > 0.0 { "f64::INFINITY" } else { "f64::NEG_INFINITY" }.to_string()
+        }
+        // Debug formatting keeps the decimal point and round trips.
+        DataType::Float => format!("{:?}", value),
+    }
+}
+
+fn python_dtype(data_type: DataType) -> &'static str {
+    match data_type {
+        DataType::Integer => "np.int32",
+        DataType::Float => "np.float64",
+    }
+}
+
+/// Shape of one row as a Python tuple.
+fn python_shape(shape: Shape) -> String {
+    match shape {
+        Shape::Scalar => "()".to_string(),
+        Shape::Vector(size) => format!("({},)", size),
+        Shape::Matrix(rows, cols) => format!("({}, {})", rows, cols),
+    }
+}
+
+fn python_number(value: f64, data_type: DataType) -> String {
+    match data_type {
+        DataType::Integer => (value as i32).to_string(),
+        DataType::Float if value.is_nan() => "np.nan".to_string(),
+        DataType::Float if value.is_infinite() => if value > 0.0 { "np.inf" } else { "-np.inf" }.to_string(),
+        DataType::Float => format!("{:?}", value),
+    }
+}
+
+fn c_scalar_type(data_type: DataType) -> &'static str {
+    match data_type {
+        DataType::Integer => "int32_t",
+        DataType::Float => "double",
+    }
+}
+
+fn c_dimensions(shape: Shape) -> String {
+    match shape {
+        Shape::Scalar => String::new(),
+        Shape::Vector(size) => format!("[{}]", size),
+        Shape::Matrix(rows, cols) => format!("[{}][{}]", rows, cols),
+    }
+}
+
+fn c_declaration(type_info: TypeInfo, name: &str, constant: bool) -> String {
+    let qualifier = if constant { "const " } else { "" };
+    format!("{}{} {}{}", qualifier, c_scalar_type(type_info.data_type), name, c_dimensions(type_info.shape))
+}
+
+fn c_number(value: f64, data_type: DataType) -> String {
+    match data_type {
+        // -2147483648 is not an int literal in C, only the negation of one that does not fit.
+        DataType::Integer if value as i32 == i32::MIN => "INT32_MIN".to_string(),
+        DataType::Integer => (value as i32).to_string(),
+        DataType::Float if value.is_nan() => "NAN".to_string(),
+        DataType::Float if value.is_infinite() => if value > 0.0 { "INFINITY" } else { "-INFINITY" }.to_string(),
+        DataType::Float => format!("{:?}", value),
+    }
+}
+
+fn check_output(what: &str, output: &std::process::Output) -> Result<(), String> {
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(format!(
+        "{} failed ({}): {}{}",
+        what,
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
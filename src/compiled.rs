@@ -0,0 +1,238 @@
+//! Compiled (postfix) form of a ParseTree.
+//!
+//! Walking `ParseTree.tree` on every row means a linear scan over the grammar for each nonterminal and a fresh
+//! `Box<dyn Any>` per node. Compiling resolves every rule pointer and every variable's slot in the row once and
+//! flattens the tree into a postfix program for a small stack machine.
+//!
+//! Scalars live unboxed on the stack, and scalar rules with a column implementation (`NonTerminalRule::with_batch`)
+//! are run on them in place, so evaluating such trees over many rows with a reused stack allocates nothing. Other
+//! rules go through their per-value `func`, which returns a box, and vectors and matrices are borrowed from the tree
+//! or the row when they are terminals.
+
+use crate::{
+    nonterminal::NonTerminalRule,
+    types::{AnyValue, DataRow, DataType, Dataset, EvalInput, Shape, TypeInfo},
+    utils::{l1_loss, l1_loss_to_reciprocal_fitness, weighted_mean_loss, wrapping_i32},
+};
+use std::any::Any;
+
+/// A single stack machine instruction.
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction<'a> {
+    /// Push a constant terminal value.
+    Constant(&'a AnyValue),
+    /// Push the value of a variable from the current data row, `slot` is its position in `DataRow::slots`.
+    Variable { name: &'a str, slot: usize },
+    /// Pop the right (for binary rules) then left operand, push the result of the rule.
+    Apply(&'a NonTerminalRule),
+}
+
+/// A value on the evaluation stack. Scalars are held by value, other terminals are borrowed and other rule outputs
+/// are owned.
+#[derive(Debug)]
+pub enum StackValue<'a> {
+    Float(f64),
+    Integer(i32),
+    Borrowed(&'a dyn Any),
+    Owned(Box<AnyValue>),
+}
+
+impl<'a> StackValue<'a> {
+    fn from_any(value: &'a dyn Any) -> Self {
+        if let Some(value) = value.downcast_ref::<f64>() {
+            StackValue::Float(*value)
+        } else if let Some(value) = value.downcast_ref::<i32>() {
+            StackValue::Integer(*value)
+        } else {
+            StackValue::Borrowed(value)
+        }
+    }
+
+    fn from_box(value: Box<AnyValue>) -> Self {
+        match StackValue::from_any(value.as_ref()) {
+            StackValue::Float(value) => StackValue::Float(value),
+            StackValue::Integer(value) => StackValue::Integer(value),
+            _ => StackValue::Owned(value),
+        }
+    }
+
+    /// A scalar computed as f64, integers being carried as f64 like in batched evaluation.
+    pub(crate) fn scalar(value: f64, data_type: DataType) -> Self {
+        match data_type {
+            DataType::Float => StackValue::Float(value),
+            DataType::Integer => StackValue::Integer(wrapping_i32(value)),
+        }
+    }
+
+    pub fn as_any(&self) -> &dyn Any {
+        match self {
+            StackValue::Float(value) => value,
+            StackValue::Integer(value) => value,
+            StackValue::Borrowed(value) => *value,
+            StackValue::Owned(value) => value.as_ref(),
+        }
+    }
+
+    /// The value as f64 if it is a scalar of either data type.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            StackValue::Float(value) => Some(*value),
+            StackValue::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CompiledTree<'a> {
+    instructions: Vec<Instruction<'a>>,
+    output_type: TypeInfo,
+    max_stack: usize,
+}
+
+impl<'a> CompiledTree<'a> {
+    pub(crate) fn new(instructions: Vec<Instruction<'a>>, output_type: TypeInfo) -> Self {
+        // Track the stack height the program reaches so evaluation can reserve it up front.
+        let mut height: usize = 0;
+        let mut max_stack = 0;
+        for instruction in &instructions {
+            match instruction {
+                Instruction::Constant(_) | Instruction::Variable { .. } => height += 1,
+                Instruction::Apply(rule) => height -= rule.arity() - 1,
+            }
+            max_stack = max_stack.max(height);
+        }
+
+        CompiledTree {
+            instructions,
+            output_type,
+            max_stack,
+        }
+    }
+
+    pub fn instructions(&self) -> &[Instruction<'a>] {
+        &self.instructions
+    }
+
+    /// Type of the value the program leaves on the stack.
+    pub fn output_type(&self) -> TypeInfo {
+        self.output_type
+    }
+
+    /// Creates an empty stack with enough capacity to run this program without growing.
+    pub fn new_stack<'r>(&self) -> Vec<StackValue<'r>> {
+        Vec::with_capacity(self.max_stack)
+    }
+
+    /// Runs the program against a single row. `stack` is scratch space that can be reused across rows. The row has
+    /// to be laid out by the `VariableDefinitions` the tree was compiled with.
+    pub fn evaluate_row<'r>(
+        &self,
+        row: &'r DataRow,
+        stack: &mut Vec<StackValue<'r>>,
+    ) -> Result<StackValue<'r>, String>
+    where
+        'a: 'r,
+    {
+        stack.clear();
+
+        for instruction in &self.instructions {
+            match *instruction {
+                Instruction::Constant(value) => stack.push(StackValue::from_any(value)),
+                Instruction::Variable { name, slot } => {
+                    let value = row
+                        .slots
+                        .get(slot)
+                        .ok_or_else(|| format!("Variable '{}' not found in data row", name))?;
+                    stack.push(StackValue::from_any(value.as_ref()));
+                }
+                Instruction::Apply(rule) => {
+                    let right = match rule.arity() {
+                        1 => None,
+                        _ => Some(stack.pop().ok_or("Stack underflow in compiled tree")?),
+                    };
+                    let left = stack.pop().ok_or("Stack underflow in compiled tree")?;
+                    stack.push(apply(rule, &left, right.as_ref()));
+                }
+            }
+        }
+
+        stack.pop().ok_or_else(|| "Compiled tree produced no value".to_string())
+    }
+
+    /// Same fitness as `ParseTree::evaluate_fitness`: reciprocal of the mean L1 loss over the dataset, each row's
+    /// loss weighted by its weight.
+    pub fn evaluate_fitness(&self, dataset: &Dataset) -> Result<f64, String> {
+        self.fitness_over(dataset.iter())
+    }
+
+    /// Fitness over a subset of the dataset's rows, for subsampled evaluation.
+    pub fn evaluate_fitness_rows(&self, dataset: &Dataset, rows: &[usize]) -> Result<f64, String> {
+        self.fitness_over(rows.iter().map(|&idx| dataset.sample_row(idx)))
+    }
+
+    fn fitness_over<'r>(&self, rows: impl Iterator<Item = EvalInput<'r>>) -> Result<f64, String>
+    where
+        'a: 'r,
+    {
+        let mut stack = self.new_stack();
+        let mut agg_loss: f64 = 0.0;
+        let mut total_weight: f64 = 0.0;
+        let mut rows = rows.peekable();
+        if let Some(EvalInput::Data(row, _, _)) = rows.peek() {
+            self.check_layout(row)?;
+        }
+
+        for eval_input in rows {
+            let EvalInput::Data(row, target, weight) = eval_input;
+            let output = self.evaluate_row(row, &mut stack)?;
+            let prediction = match output {
+                StackValue::Float(value) if self.output_type.data_type == DataType::Float => value,
+                StackValue::Integer(value) if self.output_type.data_type == DataType::Integer => value as f64,
+                _ => {
+                    return Err(format!("Cannot evaluate fitness: tree output has invalid type {:?}", self.output_type))
+                }
+            };
+
+            let loss = l1_loss(prediction, target.as_ref())
+                .ok_or("Cannot evaluate fitness: targets must be Integer or Float scalars")?;
+            agg_loss += weight * loss;
+            total_weight += weight;
+        }
+
+        Ok(l1_loss_to_reciprocal_fitness(weighted_mean_loss(agg_loss, total_weight)))
+    }
+
+    /// Checks once per dataset that its rows have the layout the variables were resolved against.
+    fn check_layout(&self, row: &DataRow) -> Result<(), String> {
+        for instruction in &self.instructions {
+            if let Instruction::Variable { name, slot } = *instruction {
+                let same = match (row.values.get(name), row.slots.get(slot)) {
+                    (Some(value), Some(slotted)) => std::sync::Arc::ptr_eq(value, slotted),
+                    _ => false,
+                };
+                if !same {
+                    return Err(format!(
+                        "Variable '{}' is not in slot {} of the data row, it was built with other variable definitions",
+                        name, slot
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a rule on its operands. Scalar rules with a column implementation run it on one-element columns, which
+/// needs no allocation, everything else calls the rule's `func`.
+fn apply<'r>(rule: &NonTerminalRule, left: &StackValue<'r>, right: Option<&StackValue<'r>>) -> StackValue<'r> {
+    if let (Some(kernel), Shape::Scalar) = (rule.batch_func, rule.output.shape) {
+        // Unary rules get their operand in both columns, as in batched evaluation.
+        if let (Some(a), Some(b)) = (left.as_f64(), right.unwrap_or(left).as_f64()) {
+            let mut out = [0.0];
+            kernel(&[a], &[b], &mut out);
+            return StackValue::scalar(out[0], rule.output.data_type);
+        }
+    }
+    StackValue::from_box(rule.execute_children(left.as_any(), right.map(|right| right.as_any())))
+}
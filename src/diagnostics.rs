@@ -0,0 +1,145 @@
+//! Static checks of a grammar against its variables, target type and maximum depth.
+//!
+//! Generation never fails on a broken grammar: the possibility table leaves out whatever cannot be completed within
+//! the depth left, and types no variable can end are filled with random constants. `NonTerminalGrammar::diagnose`
+//! lists these problems up front instead, one `GrammarIssue` each, from the same table generation uses.
+
+use crate::{
+    nonterminal::NonTerminalGrammar,
+    possibilities_tables::PossibilityTable,
+    types::{TypeInfo, VariableDefinitions},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrammarIssue {
+    /// No variable can end the type, directly or through rules, so every node of that type is a random constant.
+    NoProducer(TypeInfo),
+    /// The rule, by signature, can never be part of a tree of the target type within the maximum depth.
+    UnusedRule(String),
+    /// The type appears in a rule or variable, but no tree of the target type can contain it within the maximum
+    /// depth. `min_depth` is the shallowest depth it could appear at regardless of the maximum depth, None when it
+    /// is not reachable at all.
+    UnreachableType { type_info: TypeInfo, min_depth: Option<usize> },
+    /// The rule, by signature, is declared more than once.
+    DuplicateRule(String),
+}
+
+impl fmt::Display for GrammarIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarIssue::NoProducer(type_info) => write!(
+                f,
+                "No variable can end {}, directly or through rules, so its nodes are always random constants",
+                type_info
+            ),
+            GrammarIssue::UnusedRule(signature) => {
+                write!(f, "Rule {} can never appear in a tree of the target type", signature)
+            }
+            GrammarIssue::UnreachableType { type_info, min_depth: Some(depth) } => write!(
+                f,
+                "{} is reachable from the target type at depth {} at the earliest, too deep to complete within the maximum depth",
+                type_info, depth
+            ),
+            GrammarIssue::UnreachableType { type_info, min_depth: None } => {
+                write!(f, "{} is never reachable from the target type", type_info)
+            }
+            GrammarIssue::DuplicateRule(signature) => write!(f, "Rule {} is declared more than once", signature),
+        }
+    }
+}
+
+impl NonTerminalGrammar {
+    /// Problems of this grammar for trees producing `target_type` with at most `max_depth` levels, as used by
+    /// `TreeOrchestrator`. An empty list means every rule and variable can take part in generation.
+    pub fn diagnose(
+        &self,
+        variables: &VariableDefinitions,
+        target_type: TypeInfo,
+        max_depth: usize,
+    ) -> Vec<GrammarIssue> {
+        let mut issues = Vec::new();
+
+        let mut seen = HashSet::new();
+        for rule in &self.rules {
+            if !seen.insert(rule.id()) && !issues.contains(&GrammarIssue::DuplicateRule(rule.signature())) {
+                issues.push(GrammarIssue::DuplicateRule(rule.signature()));
+            }
+        }
+
+        let table = PossibilityTable::new(self, variables, target_type, max_depth);
+        let rows: Vec<_> = (0..max_depth).filter_map(|depth| table.get_possible_types_at_depth(depth)).collect();
+        let first_depth = |type_info: &TypeInfo| rows.iter().position(|row| row.contains(type_info));
+
+        let variable_types: HashSet<TypeInfo> = variables.variables.iter().map(|var| var._type).collect();
+        let mut constants: Vec<TypeInfo> = rows
+            .iter()
+            .flat_map(|row| row.iter().copied())
+            .filter(|type_info| table.is_terminal(*type_info) && !variable_types.contains(type_info))
+            .collect::<HashSet<TypeInfo>>()
+            .into_iter()
+            .collect();
+        constants.sort_by_key(|type_info| (first_depth(type_info), type_info.to_string()));
+        issues.extend(constants.into_iter().map(GrammarIssue::NoProducer));
+
+        // A rule is used if its output appears at some depth with room to complete its inputs below it.
+        for rule in &self.rules {
+            let used = rows.iter().enumerate().any(|(depth, row)| {
+                row.contains(&rule.output)
+                    && table
+                        .expansions(self, depth, rule.output)
+                        .contains(&(rule.input_one_type, rule.input_two_type, rule.operation))
+            });
+            if !used && !issues.contains(&GrammarIssue::UnusedRule(rule.signature())) {
+                issues.push(GrammarIssue::UnusedRule(rule.signature()));
+            }
+        }
+
+        let depths = self.min_depths(target_type);
+        let mut mentioned = Vec::new();
+        for type_info in self
+            .rules
+            .iter()
+            .flat_map(|rule| [rule.output, rule.input_one_type, rule.input_two_type])
+            .chain(variables.variables.iter().map(|var| var._type))
+        {
+            if first_depth(&type_info).is_none() && !mentioned.contains(&type_info) {
+                mentioned.push(type_info);
+                issues.push(GrammarIssue::UnreachableType {
+                    type_info,
+                    min_depth: depths.get(&type_info).copied(),
+                });
+            }
+        }
+
+        issues
+    }
+
+    /// Shallowest depth each type can appear at in a tree whose root produces `target_type`, ignoring max depth.
+    fn min_depths(&self, target_type: TypeInfo) -> HashMap<TypeInfo, usize> {
+        let mut depths = HashMap::from([(target_type, 0)]);
+        let mut frontier = vec![target_type];
+
+        for depth in 1.. {
+            let mut next = Vec::new();
+            for rule in self.rules.iter().filter(|rule| frontier.contains(&rule.output)) {
+                let inputs = [rule.input_one_type, rule.input_two_type];
+                for input in &inputs[..rule.arity()] {
+                    if !depths.contains_key(input) {
+                        depths.insert(*input, depth);
+                        next.push(*input);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        depths
+    }
+}
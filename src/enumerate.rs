@@ -0,0 +1,226 @@
+//! Counting and exhaustive enumeration of the type-legal trees of a grammar, up to a maximum depth.
+//!
+//! The leaves are the variables of each type. Types that only constants can end (see `PossibilityTable`) get a
+//! single constant leaf holding a zero placeholder, so every enumerated tree can be evaluated as is and its
+//! constants set or fitted afterwards.
+//!
+//! Trees are produced by unranking: the i-th tree is built directly from the subtree counts, so `TreeEnumerator`
+//! is lazy, needs no more memory than the counts, and `nth` skips ahead without building the trees in between.
+//! Counts saturate at `u128::MAX`.
+
+use crate::{
+    node::{Node, NodeType},
+    nonterminal::NonTerminalGrammar,
+    ops::Operation,
+    possibilities_tables::PossibilityTable,
+    tree_builder::ParseTree,
+    types::{TypeInfo, VariableDefinitions},
+};
+use std::collections::HashMap;
+
+pub struct TreeEnumerator<'a> {
+    grammar: &'a NonTerminalGrammar,
+    /// Leaves of each type, a variable name or None for a constant.
+    leaves: HashMap<TypeInfo, Vec<Option<String>>>,
+    target_type: TypeInfo,
+    max_depth: usize,
+    /// Number of trees of a type within a number of levels.
+    counts: HashMap<(TypeInfo, usize), u128>,
+    next: u128,
+    total: u128,
+}
+
+impl<'a> TreeEnumerator<'a> {
+    pub fn new(
+        grammar: &'a NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        table: &PossibilityTable,
+        target_type: TypeInfo,
+    ) -> Self {
+        let mut leaves: HashMap<TypeInfo, Vec<Option<String>>> = HashMap::new();
+        for var in &variables.variables {
+            leaves.entry(var._type).or_default().push(Some(var.name.clone()));
+        }
+        let types = grammar
+            .rules
+            .iter()
+            .flat_map(|rule| [rule.output, rule.input_one_type, rule.input_two_type])
+            .chain([target_type]);
+        for type_info in types {
+            if table.is_terminal(type_info) {
+                leaves.entry(type_info).or_insert_with(|| vec![None]);
+            }
+        }
+
+        let mut enumerator = TreeEnumerator {
+            grammar,
+            leaves,
+            target_type,
+            max_depth: table.get_max_depth(),
+            counts: HashMap::new(),
+            next: 0,
+            total: 0,
+        };
+        enumerator.total = enumerator.count_of(target_type, enumerator.max_depth);
+        enumerator
+    }
+
+    /// Number of distinct trees, whether enumerated yet or not.
+    pub fn total(&self) -> u128 {
+        self.total
+    }
+
+    /// The `index`-th tree of the enumeration, without advancing it.
+    pub fn tree(&mut self, index: u128) -> Option<ParseTree> {
+        if index >= self.total {
+            return None;
+        }
+        let mut tree = ParseTree::empty(usize::try_from(index).unwrap_or(usize::MAX));
+        self.unrank(&mut tree, self.target_type, self.max_depth, index, 0, 0);
+        Some(tree)
+    }
+
+    fn count_of(&mut self, type_info: TypeInfo, levels: usize) -> u128 {
+        if levels == 0 {
+            return 0;
+        }
+        if let Some(count) = self.counts.get(&(type_info, levels)) {
+            return *count;
+        }
+
+        let mut count = self.leaves.get(&type_info).map_or(0, |leaves| leaves.len() as u128);
+        for (left_type, right_type, operation) in distinct_expansions(self.grammar, type_info) {
+            let mut product = self.count_of(left_type, levels - 1);
+            if operation.arity() == 2 {
+                product = product.saturating_mul(self.count_of(right_type, levels - 1));
+            }
+            count = count.saturating_add(product);
+        }
+
+        self.counts.insert((type_info, levels), count);
+        count
+    }
+
+    /// Appends the `index`-th tree of `type_info` within `levels` in pre-order: leaves first, then each rule in
+    /// grammar order with the left subtree varying slowest.
+    fn unrank(&mut self, tree: &mut ParseTree, type_info: TypeInfo, levels: usize, mut index: u128, depth: usize, parent_idx: usize) {
+        let idx = tree.tree.len();
+        let leaves = self.leaves.get(&type_info).map_or(0, |leaves| leaves.len() as u128);
+
+        if index < leaves {
+            tree.tree.push(Node {
+                idx,
+                _type: NodeType::Terminal(type_info),
+                value: ParseTree::create_placeholder_value(type_info),
+                variable_id: self.leaves[&type_info][index as usize].clone(),
+                left_index: None,
+                right_index: None,
+                parent_index: parent_idx,
+                depth,
+            });
+            return;
+        }
+        index -= leaves;
+
+        for (left_type, right_type, operation) in distinct_expansions(self.grammar, type_info) {
+            let rights = match operation.arity() {
+                1 => 1,
+                _ => self.count_of(right_type, levels - 1),
+            };
+            let product = self.count_of(left_type, levels - 1).saturating_mul(rights);
+            if index >= product {
+                index -= product;
+                continue;
+            }
+
+            tree.tree.push(Node {
+                idx,
+                _type: NodeType::NonTerminal(left_type, right_type, operation, type_info),
+                value: ParseTree::create_placeholder_value(type_info),
+                variable_id: None,
+                left_index: Some(idx + 1),
+                right_index: None,
+                parent_index: parent_idx,
+                depth,
+            });
+            self.unrank(tree, left_type, levels - 1, index / rights, depth + 1, idx);
+            if operation.arity() == 2 {
+                tree.tree[idx].right_index = Some(tree.tree.len());
+                self.unrank(tree, right_type, levels - 1, index % rights, depth + 1, idx);
+            }
+            return;
+        }
+        unreachable!("index within the count of its type");
+    }
+}
+
+/// Distinct rule inputs producing `type_info`, a grammar listing a rule twice still yields its trees once.
+pub(crate) fn distinct_expansions(grammar: &NonTerminalGrammar, type_info: TypeInfo) -> Vec<(TypeInfo, TypeInfo, Operation)> {
+    let mut expansions = grammar.get_all_possible_input_types_with_operations(type_info);
+    let mut seen = Vec::new();
+    expansions.retain(|expansion| {
+        let new = !seen.contains(expansion);
+        seen.push(*expansion);
+        new
+    });
+    expansions
+}
+
+impl Iterator for TreeEnumerator<'_> {
+    type Item = ParseTree;
+
+    fn next(&mut self) -> Option<ParseTree> {
+        let tree = self.tree(self.next)?;
+        self.next += 1;
+        Some(tree)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<ParseTree> {
+        self.next = self.next.saturating_add(n as u128);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.total - self.next.min(self.total);
+        (usize::try_from(remaining).unwrap_or(usize::MAX), usize::try_from(remaining).ok())
+    }
+}
+
+impl PossibilityTable {
+    /// Number of distinct trees of `target_type` within this table's max depth, see the `enumerate` module.
+    pub fn count_trees(
+        &self,
+        grammar: &NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        target_type: TypeInfo,
+    ) -> u128 {
+        TreeEnumerator::new(grammar, variables, self, target_type).total()
+    }
+
+    /// Every distinct tree of `target_type` within this table's max depth, lazily, leaves before rules.
+    pub fn enumerate_trees<'a>(
+        &self,
+        grammar: &'a NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        target_type: TypeInfo,
+    ) -> TreeEnumerator<'a> {
+        TreeEnumerator::new(grammar, variables, self, target_type)
+    }
+}
+
+impl NonTerminalGrammar {
+    /// Number of distinct trees of `target_type` with at most `max_depth` levels.
+    pub fn count_trees(&self, variables: &VariableDefinitions, target_type: TypeInfo, max_depth: usize) -> u128 {
+        PossibilityTable::new(self, variables, target_type, max_depth).count_trees(self, variables, target_type)
+    }
+
+    /// Every distinct tree of `target_type` with at most `max_depth` levels, lazily.
+    pub fn enumerate_trees(
+        &self,
+        variables: &VariableDefinitions,
+        target_type: TypeInfo,
+        max_depth: usize,
+    ) -> TreeEnumerator<'_> {
+        PossibilityTable::new(self, variables, target_type, max_depth).enumerate_trees(self, variables, target_type)
+    }
+}
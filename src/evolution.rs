@@ -0,0 +1,157 @@
+//! Settings and results of an evolutionary run driven by `TreeOrchestrator`.
+
+use crate::{
+    tree_builder::ParseTree,
+    types::{GenerationMethod, TypeInfo, VariableDefinitions},
+};
+
+/// Parameters of the generational loop.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvolutionConfig {
+    /// Number of trees competing in each tournament selection.
+    pub tournament_size: usize,
+    /// Probability that two selected parents are recombined with subtree crossover.
+    pub crossover_rate: f64,
+    /// Probability that an offspring has a random subtree replaced.
+    pub mutation_rate: f64,
+    /// Probability that an offspring has a single node changed in place, see `ParseTree::point_mutate`.
+    /// Off by default, missing from checkpoints written before it existed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub point_mutation_rate: f64,
+    /// Probability that an offspring has its constants perturbed, see `ParseTree::perturb_constants`. Off by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub constant_mutation_rate: f64,
+    /// Size of the perturbation: the standard deviation for floats, the largest step for integers.
+    #[cfg_attr(feature = "serde", serde(default = "default_constant_mutation_scale"))]
+    pub constant_mutation_scale: f64,
+    /// Number of best trees copied unchanged into the next generation.
+    pub elitism: usize,
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        EvolutionConfig {
+            tournament_size: 3,
+            crossover_rate: 0.9,
+            mutation_rate: 0.1,
+            point_mutation_rate: 0.0,
+            constant_mutation_rate: 0.0,
+            constant_mutation_scale: default_constant_mutation_scale(),
+            elitism: 1,
+        }
+    }
+}
+
+fn default_constant_mutation_scale() -> f64 {
+    1.0
+}
+
+/// Which rows of the dataset each generation is scored on.
+/// Subsampled modes trade noisy fitness for speed on large datasets; the reported best tree is always
+/// re-evaluated on the full dataset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FitnessMode {
+    /// Every row, every generation.
+    #[default]
+    Full,
+    /// A fresh random sample of `size` rows each generation.
+    MiniBatch { size: usize },
+    /// Every `stride`-th row, starting at an offset that cycles with the generation, so the whole dataset
+    /// is covered every `stride` generations.
+    Interleaved { stride: usize },
+}
+
+impl FitnessMode {
+    /// Checks that the mode picks at least one row of a dataset of `rows` rows every generation.
+    pub fn validate(&self, rows: usize) -> Result<(), String> {
+        match *self {
+            FitnessMode::Full => Ok(()),
+            FitnessMode::MiniBatch { size: 0 } => Err("Mini-batch size must be at least 1".to_string()),
+            FitnessMode::MiniBatch { .. } => Ok(()),
+            FitnessMode::Interleaved { stride } if stride == 0 || stride > rows => Err(format!(
+                "Interleaved stride must be between 1 and the number of rows ({}), got {}",
+                rows, stride
+            )),
+            FitnessMode::Interleaved { .. } => Ok(()),
+        }
+    }
+}
+
+/// Summary of the population after a generation has been evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenerationStats {
+    pub generation: usize,
+    /// Best and mean fitness on the rows this generation was scored on, see `FitnessMode`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float"))]
+    pub best_fitness: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float"))]
+    pub mean_fitness: f64,
+    /// Fitness of the generation's best tree on the held-out validation set, if the orchestrator has one.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float_option"))]
+    pub validation_fitness: Option<f64>,
+}
+
+/// Outcome of `TreeOrchestrator::run`. The seed is enough to replay the run with the same inputs.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunResult {
+    pub seed: u64,
+    pub generations: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float"))]
+    pub best_fitness: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float_option"))]
+    pub best_validation_fitness: Option<f64>,
+    pub best_tree: ParseTree,
+    pub history: Vec<GenerationStats>,
+}
+
+/// The population of a `TreeOrchestrator` with the scores of its last evaluation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PopulationSnapshot {
+    pub generation: usize,
+    pub trees: Vec<ParseTree>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float_vec"))]
+    pub scores: Vec<f64>,
+}
+
+/// Settings of a `TreeOrchestrator`, everything but the grammar and the data.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunConfig {
+    pub seed: u64,
+    pub max_trees: usize,
+    pub max_depth: usize,
+    pub required_output_type: TypeInfo,
+    pub generation_method: GenerationMethod,
+    pub variable_definitions: VariableDefinitions,
+    pub evolution_config: EvolutionConfig,
+    pub fitness_mode: FitnessMode,
+}
+
+/// Position of the orchestrator's ChaCha generator, enough to continue its exact sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RngState {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+/// Everything a `TreeOrchestrator` needs to continue a run on the same trajectory, except the grammar and the
+/// data. Rules are referred to by `NonTerminalRule::signature` and re-bound to the grammar given on resume.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    pub config: RunConfig,
+    pub population: PopulationSnapshot,
+    pub history: Vec<GenerationStats>,
+    pub rng: RngState,
+    /// Signatures of the grammar's rules, in order.
+    pub rules: Vec<String>,
+    /// Number of training rows, as a sanity check that the run resumes on the same data.
+    pub dataset_rows: usize,
+}
@@ -0,0 +1,288 @@
+//! Text format for grammars, so search spaces can live in version control next to the data instead of in code.
+//!
+//! One declaration per line, `#` starts a comment:
+//!
+//! ```text
+//! # named types, usable anywhere a type is expected
+//! type real = Float/Scalar
+//! type point = Float/Vector(3)
+//!
+//! # terminals: the variables of the dataset
+//! variable x : real
+//! variable p : point
+//!
+//! # type of the tree's root, optional
+//! output real
+//!
+//! # types only random constants end, when no variable has them
+//! constant Integer/Scalar
+//!
+//! # custom operations: name and number of inputs
+//! operation Scale : 2
+//!
+//! # rules: operation(left, right) -> output = function
+//! rule Add(real, real) -> real = add_f64
+//! rule Multiply(Float/Scalar, real) -> real = mul_f64
+//! rule Norm(point) -> real = norm
+//! rule Scale(real, real) -> real = mul_f64
+//! rule Element(point, Integer/Scalar) -> real = element
+//! ```
+//!
+//! Operations other than the built-in ones are custom operations (`ops::CustomOperation`) and have to be declared
+//! with `operation` before a rule uses them, so a misspelt built-in is an error rather than a new operation.
+//!
+//! Types are either a name declared with `type` or written out as in `TypeInfo`'s `Display` form. Functions are
+//! looked up by name in a `FunctionRegistry`, which also checks that they can serve the rule's types.
+//!
+//! When the output type is declared, the grammar is checked with `NonTerminalGrammar::diagnose` without a depth
+//! limit, and any issue fails loading: every rule and type has to be usable in some tree of the output type, and
+//! every type has to be ended by a variable, or be declared `constant` to be filled with random constants.
+
+use crate::{
+    diagnostics::GrammarIssue,
+    nonterminal::NonTerminalGrammar,
+    ops::{CustomOperation, Operation},
+    registry::FunctionRegistry,
+    types::{TypeInfo, Variable, VariableDefinitions},
+};
+use std::{collections::HashMap, path::Path};
+
+#[derive(Debug)]
+pub struct GrammarFile {
+    /// Named types, by name.
+    pub types: HashMap<String, TypeInfo>,
+    /// Declared custom operations, by name.
+    pub operations: HashMap<String, CustomOperation>,
+    /// Types declared `constant`.
+    pub constants: Vec<TypeInfo>,
+    pub variable_definitions: VariableDefinitions,
+    pub output_type: Option<TypeInfo>,
+    pub grammar: NonTerminalGrammar,
+}
+
+impl GrammarFile {
+    pub fn load(path: impl AsRef<Path>, registry: &FunctionRegistry) -> Result<GrammarFile, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| format!("Cannot read '{}': {}", path.display(), err))?;
+        Self::parse(&text, registry)
+    }
+
+    /// Reads a grammar file. Errors name the offending line.
+    pub fn parse(text: &str, registry: &FunctionRegistry) -> Result<GrammarFile, String> {
+        let mut file = GrammarFile {
+            types: HashMap::new(),
+            operations: HashMap::new(),
+            constants: Vec::new(),
+            variable_definitions: VariableDefinitions::new(Vec::new()),
+            output_type: None,
+            grammar: NonTerminalGrammar::new(),
+        };
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            file.parse_line(line, registry).map_err(|err| format!("line {}: {}", idx + 1, err))?;
+        }
+
+        file.validate()?;
+        Ok(file)
+    }
+
+    fn parse_line(&mut self, line: &str, registry: &FunctionRegistry) -> Result<(), String> {
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+
+        match keyword {
+            "type" => {
+                let (name, type_text) = rest.split_once('=').ok_or("Expected 'type <name> = <type>'")?;
+                let name = identifier(name)?;
+                if self.types.contains_key(name) {
+                    return Err(format!("Type '{}' is already declared", name));
+                }
+                let type_info = type_text.trim().parse::<TypeInfo>()?;
+                self.types.insert(name.to_string(), type_info);
+            }
+            "variable" => {
+                let (name, type_text) = rest.split_once(':').ok_or("Expected 'variable <name> : <type>'")?;
+                let name = identifier(name)?;
+                if self.variable_definitions.variables.iter().any(|var| var.name == name) {
+                    return Err(format!("Variable '{}' is already declared", name));
+                }
+                let type_info = self.resolve_type(type_text)?;
+                self.variable_definitions.variables.push(Variable {
+                    name: name.to_string(),
+                    _type: type_info,
+                });
+            }
+            "output" => {
+                if self.output_type.is_some() {
+                    return Err("The output type is already declared".to_string());
+                }
+                self.output_type = Some(self.resolve_type(rest)?);
+            }
+            "constant" => {
+                let type_info = self.resolve_type(rest)?;
+                if self.constants.contains(&type_info) {
+                    return Err(format!("{} is already declared constant", type_info));
+                }
+                self.constants.push(type_info);
+            }
+            "operation" => {
+                let (name, arity) = rest.split_once(':').ok_or("Expected 'operation <name> : <arity>'")?;
+                let name = identifier(name)?;
+                if name.parse::<Operation>().is_ok() {
+                    return Err(format!("'{}' is a built-in operation", name));
+                }
+                if self.operations.contains_key(name) {
+                    return Err(format!("Operation '{}' is already declared", name));
+                }
+                let arity = arity.trim().parse::<usize>().map_err(|_| format!("'{}' is not a number of inputs", arity.trim()))?;
+                self.operations.insert(name.to_string(), CustomOperation::try_new(name, arity)?);
+            }
+            "rule" => self.parse_rule(rest, registry)?,
+            other => {
+                return Err(format!(
+                    "Unknown declaration '{}', expected type, variable, output, constant, operation or rule",
+                    other
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// `Add(real, real) -> real = add_f64`
+    fn parse_rule(&mut self, text: &str, registry: &FunctionRegistry) -> Result<(), String> {
+        const USAGE: &str = "Expected 'rule <operation>(<type>, <type>) -> <type> = <function>'";
+
+        let (name, rest) = text.split_once('(').ok_or(USAGE)?;
+
+        let close = matching_paren(rest).ok_or(USAGE)?;
+        let (inputs, rest) = (&rest[..close], &rest[close + 1..]);
+        let inputs = split_top_level(inputs);
+
+        let name = name.trim();
+        let operation = match (name.parse::<Operation>(), self.operations.get(name)) {
+            (Ok(operation), _) => operation,
+            (Err(_), Some(custom)) => Operation::Custom(*custom),
+            (Err(_), None) => {
+                return Err(format!(
+                    "Unknown operation '{}', custom operations are declared with 'operation {} : <arity>'",
+                    name, name
+                ))
+            }
+        };
+        let (left, right) = match inputs[..] {
+            [left, right] if operation.arity() == 2 => (left, right),
+            // Unary rules mirror their input type, see `NonTerminalRule::unary`.
+            [input] if operation.arity() == 1 => (input, input),
+            _ => return Err(format!("{} takes {} inputs, got {}", operation, operation.arity(), inputs.len())),
+        };
+
+        let (output, function) = rest.trim().strip_prefix("->").and_then(|rest| rest.split_once('=')).ok_or(USAGE)?;
+
+        let rule = registry.rule(
+            identifier(function)?,
+            self.resolve_type(left)?,
+            self.resolve_type(right)?,
+            operation,
+            self.resolve_type(output)?,
+        )?;
+        if self.grammar.rules.iter().any(|existing| existing.signature() == rule.signature()) {
+            return Err(format!("Rule {} is already declared", rule.signature()));
+        }
+        self.grammar.add_rule(rule);
+        Ok(())
+    }
+
+    fn resolve_type(&self, text: &str) -> Result<TypeInfo, String> {
+        let text = text.trim();
+        match self.types.get(text) {
+            Some(type_info) => Ok(*type_info),
+            None if text.contains('/') => text.parse::<TypeInfo>(),
+            None => Err(format!("Unknown type '{}'", text)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.grammar.rules.is_empty() {
+            return Err("The grammar declares no rules".to_string());
+        }
+        let Some(output_type) = self.output_type else {
+            return Ok(());
+        };
+        if !self.grammar.rules.iter().any(|rule| rule.output == output_type) {
+            return Err(format!("No rule produces the output type {}", output_type));
+        }
+
+        // Any type is reachable within one level per type and completed within as many, so this depth puts no limit.
+        let mut types: Vec<TypeInfo> = vec![output_type];
+        for type_info in self
+            .grammar
+            .rules
+            .iter()
+            .flat_map(|rule| [rule.output, rule.input_one_type, rule.input_two_type])
+            .chain(self.variable_definitions.variables.iter().map(|var| var._type))
+        {
+            if !types.contains(&type_info) {
+                types.push(type_info);
+            }
+        }
+        let issues: Vec<String> = self
+            .grammar
+            .diagnose(&self.variable_definitions, output_type, 2 * types.len())
+            .into_iter()
+            .filter(|issue| !matches!(issue, GrammarIssue::NoProducer(type_info) if self.constants.contains(type_info)))
+            .map(|issue| issue.to_string())
+            .collect();
+        if !issues.is_empty() {
+            return Err(format!("The grammar has problems: {}", issues.join("; ")));
+        }
+        Ok(())
+    }
+}
+
+fn identifier(text: &str) -> Result<&str, String> {
+    let text = text.trim();
+    let valid = text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if valid {
+        Ok(text)
+    } else {
+        Err(format!("'{}' is not a valid name", text))
+    }
+}
+
+/// Position of the `)` closing a `(` that was just before `text`.
+fn matching_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(idx),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits on commas outside of parentheses, so `Float/Matrix(2,3)` stays whole.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (idx, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
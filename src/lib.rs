@@ -7,4 +7,20 @@ pub mod shape;
 pub mod tree_builder;
 pub mod possibilities_tables;
 pub mod nonterminal;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod compiled;
+pub mod batch;
+pub mod evolution;
+pub mod loader;
+pub mod split;
+pub mod sexpr;
+pub mod render;
+pub mod codegen;
+pub mod grammar_file;
+pub mod standard;
+pub mod linalg;
+pub mod diagnostics;
+pub mod enumerate;
+pub mod uniform;
+#[cfg(feature = "serde")]
+pub mod serialize;
@@ -0,0 +1,167 @@
+//! Linear algebra operations from Montana's STGP experiments: `Dot`, `Outer`, `Transpose`, `MatVec`, `Norm` and
+//! `Element`, for both data types.
+//!
+//! Unlike the arithmetic of `standard` these change shapes, see `node::compatible_outputs` for the types of each.
+//! `Norm` is always a float. `Element` takes an integer index, wrapped into range, and returns an element of a
+//! vector or a row of a matrix. Integer sums wrap on overflow. Shapes with a zero dimension get no rules, an empty
+//! vector has no element to pick.
+
+use crate::{
+    node::compatible_outputs,
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    standard::{self, operand, Number, Operand},
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+use std::any::Any;
+
+pub const OPERATIONS: [Operation; 6] = [
+    Operation::Dot,
+    Operation::Outer,
+    Operation::Transpose,
+    Operation::MatVec,
+    Operation::Norm,
+    Operation::Element,
+];
+
+const INDEX: TypeInfo = TypeInfo {
+    shape: Shape::Scalar,
+    data_type: DataType::Integer,
+};
+
+/// Linear algebra rules over the given shapes, keeping only those whose result has one of the given shapes.
+/// `Element` rules take an `Integer/Scalar` index whatever `data_type` is. Shapes with a zero dimension are skipped.
+pub fn linear_algebra_rules(data_type: DataType, shapes: &[Shape]) -> Vec<NonTerminalRule> {
+    let shapes: Vec<Shape> = shapes
+        .iter()
+        .copied()
+        .filter(|shape| !matches!(shape, Shape::Vector(0) | Shape::Matrix(0, _) | Shape::Matrix(_, 0)))
+        .collect();
+    let mut rules = Vec::new();
+
+    for operation in OPERATIONS {
+        for &left_shape in &shapes {
+            let left = TypeInfo { shape: left_shape, data_type };
+            let rights = match operation {
+                Operation::Element => vec![INDEX],
+                _ if operation.arity() == 1 => vec![left],
+                _ => shapes.iter().map(|&shape| TypeInfo { shape, data_type }).collect(),
+            };
+
+            for right in rights {
+                let output = compatible_outputs(left, right, operation).first().copied();
+                let Some(output) = output.filter(|output| shapes.contains(&output.shape)) else {
+                    continue;
+                };
+                rules.push(match operation.arity() {
+                    1 => NonTerminalRule::unary(left, operation, output, standard::standard_function(operation)),
+                    _ => NonTerminalRule::new(left, right, operation, output, standard::standard_function(operation)),
+                });
+            }
+        }
+    }
+
+    rules
+}
+
+impl NonTerminalGrammar {
+    /// Adds the linear algebra rules, see `linear_algebra_rules`.
+    pub fn with_linear_algebra(mut self, data_type: DataType, shapes: &[Shape]) -> Self {
+        for rule in linear_algebra_rules(data_type, shapes) {
+            self.add_rule(rule);
+        }
+        self
+    }
+}
+
+pub fn dot(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Dot, dot_of::<f64>(a, b).or_else(|| dot_of::<i32>(a, b)))
+}
+
+pub fn outer(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Outer, outer_of::<f64>(a, b).or_else(|| outer_of::<i32>(a, b)))
+}
+
+/// Unary, the second input is ignored.
+pub fn transpose(a: &dyn Any, _: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Transpose, transpose_of::<f64>(a).or_else(|| transpose_of::<i32>(a)))
+}
+
+pub fn matvec(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::MatVec, matvec_of::<f64>(a, b).or_else(|| matvec_of::<i32>(a, b)))
+}
+
+/// Unary, the second input is ignored.
+pub fn norm(a: &dyn Any, _: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Norm, norm_of::<f64>(a).or_else(|| norm_of::<i32>(a)))
+}
+
+pub fn element(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Element, element_of::<f64>(a, b).or_else(|| element_of::<i32>(a, b)))
+}
+
+fn evaluate(operation: Operation, result: Option<Box<AnyValue>>) -> Box<AnyValue> {
+    result.unwrap_or_else(|| panic!("{} is not defined for these operands", operation))
+}
+
+fn vector<T: Number>(value: &dyn Any) -> Option<&[T]> {
+    match operand::<T>(value)? {
+        Operand::Vector(vector) => Some(vector),
+        _ => None,
+    }
+}
+
+fn matrix<T: Number>(value: &dyn Any) -> Option<&[Vec<T>]> {
+    match operand::<T>(value)? {
+        Operand::Matrix(matrix) => Some(matrix),
+        _ => None,
+    }
+}
+
+fn inner<T: Number>(xs: &[T], ys: &[T]) -> T {
+    xs.iter()
+        .zip(ys)
+        .fold(T::ZERO, |sum, (x, y)| sum.apply(Operation::Add, x.apply(Operation::Multiply, *y)))
+}
+
+fn dot_of<T: Number>(a: &dyn Any, b: &dyn Any) -> Option<Box<AnyValue>> {
+    let (xs, ys) = (vector::<T>(a)?, vector::<T>(b)?);
+    (xs.len() == ys.len()).then(|| Box::new(inner(xs, ys)) as Box<AnyValue>)
+}
+
+fn outer_of<T: Number>(a: &dyn Any, b: &dyn Any) -> Option<Box<AnyValue>> {
+    let (xs, ys) = (vector::<T>(a)?, vector::<T>(b)?);
+    let product: Vec<Vec<T>> = xs.iter().map(|x| ys.iter().map(|y| x.apply(Operation::Multiply, *y)).collect()).collect();
+    Some(Box::new(product))
+}
+
+fn transpose_of<T: Number>(a: &dyn Any) -> Option<Box<AnyValue>> {
+    let rows = matrix::<T>(a)?;
+    let cols = rows.first().map_or(0, |row| row.len());
+    let transposed: Vec<Vec<T>> = (0..cols).map(|c| rows.iter().map(|row| row[c]).collect()).collect();
+    Some(Box::new(transposed))
+}
+
+fn matvec_of<T: Number>(a: &dyn Any, b: &dyn Any) -> Option<Box<AnyValue>> {
+    let (rows, xs) = (matrix::<T>(a)?, vector::<T>(b)?);
+    if rows.iter().any(|row| row.len() != xs.len()) {
+        return None;
+    }
+    Some(Box::new(rows.iter().map(|row| inner(row, xs)).collect::<Vec<T>>()))
+}
+
+fn norm_of<T: Number>(a: &dyn Any) -> Option<Box<AnyValue>> {
+    let xs = vector::<T>(a)?;
+    Some(Box::new(xs.iter().map(|x| x.to_f64() * x.to_f64()).sum::<f64>().sqrt()))
+}
+
+fn element_of<T: Number>(a: &dyn Any, b: &dyn Any) -> Option<Box<AnyValue>> {
+    let index = *b.downcast_ref::<i32>()?;
+    let wrap = |len: usize| (index as i64).rem_euclid(len as i64) as usize;
+
+    match operand::<T>(a)? {
+        Operand::Vector(xs) if !xs.is_empty() => Some(Box::new(xs[wrap(xs.len())])),
+        Operand::Matrix(rows) if !rows.is_empty() => Some(Box::new(rows[wrap(rows.len())].clone())),
+        _ => None,
+    }
+}
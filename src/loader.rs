@@ -0,0 +1,238 @@
+//! Dataset loaders.
+//!
+//! `CsvLoader` builds a `Dataset` from a CSV file with a header row. Columns are mapped to `Variable`s by name and
+//! parsed according to each variable's `TypeInfo`. Vector and matrix variables are packed from several columns:
+//! by default `v_0, v_1, ...` for a `Vector` and `m_0_0, m_0_1, ...` (row-major) for a `Matrix`, or any explicit
+//! list of columns given to `pack`. An optional weight column fills `Dataset::weights`.
+
+use crate::types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, VariableDefinitions};
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+const WEIGHT_TYPE: TypeInfo = TypeInfo {
+    shape: Shape::Scalar,
+    data_type: DataType::Float,
+};
+
+#[derive(Debug, Clone)]
+pub struct CsvLoader<'a> {
+    variable_defs: &'a VariableDefinitions,
+    packed_columns: HashMap<String, Vec<String>>,
+    target_columns: Vec<String>,
+    target_type: Option<TypeInfo>,
+    weight_column: Option<String>,
+    delimiter: u8,
+}
+
+impl<'a> CsvLoader<'a> {
+    pub fn new(variable_defs: &'a VariableDefinitions) -> Self {
+        CsvLoader {
+            variable_defs,
+            packed_columns: HashMap::new(),
+            target_columns: Vec::new(),
+            target_type: None,
+            weight_column: None,
+            delimiter: b',',
+        }
+    }
+
+    /// Reads the target from a single column.
+    pub fn target(self, column: &str, target_type: TypeInfo) -> Self {
+        self.targets(&[column], target_type)
+    }
+
+    /// Reads the target from several columns, packed into a vector or matrix (row-major) of `target_type`.
+    pub fn targets(mut self, columns: &[&str], target_type: TypeInfo) -> Self {
+        self.target_columns = columns.iter().map(|column| column.to_string()).collect();
+        self.target_type = Some(target_type);
+        self
+    }
+
+    /// Packs the given columns into a variable, in order. Matrices are filled row-major.
+    pub fn pack(mut self, variable: &str, columns: &[&str]) -> Self {
+        self.packed_columns.insert(
+            variable.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Reads per-row loss weights from a float column.
+    pub fn weight_column(mut self, column: &str) -> Self {
+        self.weight_column = Some(column.to_string());
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn load_path(&self, path: impl AsRef<Path>) -> Result<Dataset, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| format!("Cannot open '{}': {}", path.display(), err))?;
+        self.load_reader(file)
+    }
+
+    pub fn load_reader(&self, reader: impl Read) -> Result<Dataset, String> {
+        let target_type = self.target_type.ok_or("No target column set on CsvLoader")?;
+
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let mut header: HashMap<String, usize> = HashMap::new();
+        let names = csv_reader.headers().map_err(|err| format!("line 1: cannot read header: {}", err))?;
+        for (idx, name) in names.iter().enumerate() {
+            if header.insert(name.to_string(), idx).is_some() {
+                return Err(format!("line 1: duplicate column '{}'", name));
+            }
+        }
+
+        // Resolve every variable and the target to column positions once, up front.
+        let mut variable_columns = Vec::with_capacity(self.variable_defs.variables.len());
+        for var in &self.variable_defs.variables {
+            let names = self.columns_for_variable(&var.name, var._type);
+            variable_columns.push(Self::resolve_columns(&header, &var.name, var._type, &names)?);
+        }
+        let target_columns = Self::resolve_columns(&header, "target", target_type, &self.target_columns)?;
+        let weight_columns = match &self.weight_column {
+            Some(column) => Some(Self::resolve_columns(&header, "weight", WEIGHT_TYPE, std::slice::from_ref(column))?),
+            None => None,
+        };
+
+        let mut features = Vec::new();
+        let mut targets = Vec::new();
+        let mut weights = Vec::new();
+
+        for record in csv_reader.records() {
+            let record = record.map_err(|err| match err.position() {
+                Some(position) => format!("line {}: {}", position.line(), err),
+                None => err.to_string(),
+            })?;
+            let line = record.position().map_or(0, |position| position.line());
+
+            let mut values = Vec::with_capacity(variable_columns.len());
+            for (var, columns) in self.variable_defs.variables.iter().zip(&variable_columns) {
+                values.push(Self::parse_value(&record, line, &var.name, var._type, columns)?);
+            }
+
+            features.push(DataRow::new(self.variable_defs, values).map_err(|err| format!("line {}: {}", line, err))?);
+            targets.push(Self::parse_value(&record, line, "target", target_type, &target_columns)?);
+            if let Some(columns) = &weight_columns {
+                let weight = Self::parse_cells::<f64>(&record, line, "weight", columns)?[0];
+                if !weight.is_finite() || weight < 0.0 {
+                    return Err(format!("line {}: weight {} must be finite and non-negative", line, weight));
+                }
+                weights.push(weight);
+            }
+        }
+
+        let dataset = Dataset::new(features, targets)?;
+        match weight_columns {
+            Some(_) => dataset.with_weights(weights),
+            None => Ok(dataset),
+        }
+    }
+
+    fn columns_for_variable(&self, name: &str, type_info: TypeInfo) -> Vec<String> {
+        if let Some(columns) = self.packed_columns.get(name) {
+            return columns.clone();
+        }
+
+        match type_info.shape {
+            Shape::Scalar => vec![name.to_string()],
+            Shape::Vector(size) => (0..size).map(|i| format!("{}_{}", name, i)).collect(),
+            Shape::Matrix(rows, cols) => (0..rows)
+                .flat_map(|r| (0..cols).map(move |c| format!("{}_{}_{}", name, r, c)))
+                .collect(),
+        }
+    }
+
+    fn resolve_columns(
+        header: &HashMap<String, usize>,
+        name: &str,
+        type_info: TypeInfo,
+        columns: &[String],
+    ) -> Result<Vec<usize>, String> {
+        let expected = match type_info.shape {
+            Shape::Scalar => 1,
+            Shape::Vector(size) => size,
+            Shape::Matrix(rows, cols) => rows * cols,
+        };
+        if expected == 0 {
+            return Err(format!("'{}' has shape {:?}, which holds no values", name, type_info.shape));
+        }
+        if columns.len() != expected {
+            return Err(format!(
+                "'{}' has shape {:?} and needs {} columns, got {}",
+                name,
+                type_info.shape,
+                expected,
+                columns.len()
+            ));
+        }
+
+        columns
+            .iter()
+            .map(|column| {
+                header
+                    .get(column)
+                    .copied()
+                    .ok_or_else(|| format!("line 1: missing column '{}' for '{}'", column, name))
+            })
+            .collect()
+    }
+
+    fn parse_value(
+        record: &csv::StringRecord,
+        line: u64,
+        name: &str,
+        type_info: TypeInfo,
+        columns: &[usize],
+    ) -> Result<Box<AnyValue>, String> {
+        match type_info.data_type {
+            DataType::Integer => {
+                let cells = Self::parse_cells::<i32>(record, line, name, columns)?;
+                Ok(Self::shape_cells(cells, type_info.shape))
+            }
+            DataType::Float => {
+                let cells = Self::parse_cells::<f64>(record, line, name, columns)?;
+                Ok(Self::shape_cells(cells, type_info.shape))
+            }
+        }
+    }
+
+    fn parse_cells<T: std::str::FromStr>(
+        record: &csv::StringRecord,
+        line: u64,
+        name: &str,
+        columns: &[usize],
+    ) -> Result<Vec<T>, String> {
+        columns
+            .iter()
+            .map(|&column| {
+                let cell = record
+                    .get(column)
+                    .ok_or_else(|| format!("line {}: missing field {} for '{}'", line, column + 1, name))?;
+                cell.parse::<T>().map_err(|_| {
+                    format!(
+                        "line {}: cannot parse '{}' as {} for '{}'",
+                        line,
+                        cell,
+                        std::any::type_name::<T>(),
+                        name
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn shape_cells<T: Clone + Send + Sync + 'static>(cells: Vec<T>, shape: Shape) -> Box<AnyValue> {
+        match shape {
+            Shape::Scalar => Box::new(cells[0].clone()),
+            Shape::Vector(_) => Box::new(cells),
+            Shape::Matrix(_, cols) => Box::new(cells.chunks(cols).map(|row| row.to_vec()).collect::<Vec<Vec<T>>>()),
+        }
+    }
+}
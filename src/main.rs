@@ -1,12 +1,14 @@
+// scratch binary: the test_* functions are toggled on and off from main.
+#![allow(dead_code, unused_variables)]
+
 use stsr::node::{Node, NodeType};
 // use stsr::arena::{Arena, GenerationMethod};
 use stsr::nonterminal::NonTerminalGrammar;
-use stsr::types::{DataType, Dataset, EvalInput, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions};
+use stsr::types::{AnyValue, DataType, Dataset, EvalInput, Shape, TypeInfo, Variable, VariableDefinitions};
 use stsr::ops::Operation;
 use stsr::tree_builder::{ParseTree, TreeOrchestrator};
-use std::any::Any;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 fn main() {
     // test_arena_constrution();
@@ -23,22 +25,22 @@ fn test_create_nonterminal_registry() {
 
     // the user should be able to very easily define the operations they want to support.
 
-    let ScalarFloat = TypeInfo { 
+    let scalar_float = TypeInfo { 
             shape: Shape::Scalar, 
             data_type: DataType::Float 
         };
 
-    let ScalarInt = TypeInfo { 
+    let scalar_int = TypeInfo { 
             shape: Shape::Scalar, 
             data_type: DataType::Integer 
         };
 
     // I think it will be on the user to define their operations and how interactions should work.
     let float_add_rule = stsr::nonterminal::NonTerminalRule::new(
-        ScalarFloat,
-        ScalarInt,
+        scalar_float,
+        scalar_int,
         Operation::Add,
-         ScalarInt,
+         scalar_int,
         |a, b| {
             // downcast the float to a int, losing precision. 
             let val_a = a.downcast_ref::<i32>().unwrap();
@@ -181,10 +183,10 @@ fn test_random_tree_generation() {
     let mut input_values_3 = HashMap::new();
     let mut input_values_4 = HashMap::new();
 
-    input_values_1.insert("x".to_string(), Box::new(1i32) as Box<dyn Any>);
-    input_values_2.insert("x".to_string(), Box::new(2i32) as Box<dyn Any>);
-    input_values_3.insert("x".to_string(), Box::new(3i32) as Box<dyn Any>);
-    input_values_4.insert("x".to_string(), Box::new(4i32) as Box<dyn Any>);
+    input_values_1.insert("x".to_string(), Box::new(1i32) as Box<AnyValue>);
+    input_values_2.insert("x".to_string(), Box::new(2i32) as Box<AnyValue>);
+    input_values_3.insert("x".to_string(), Box::new(3i32) as Box<AnyValue>);
+    input_values_4.insert("x".to_string(), Box::new(4i32) as Box<AnyValue>);
 
 
     let data_row_1 = stsr::types::DataRow::from_map(&variable_definitions, input_values_1).unwrap();
@@ -194,7 +196,7 @@ fn test_random_tree_generation() {
 
     let dataset = Dataset::new(
         vec![data_row_1, data_row_2,data_row_3,data_row_4],
-        vec![Box::new(1i32) as Box<dyn Any>,Box::new(4i32) as Box<dyn Any>,Box::new(9i32) as Box<dyn Any>, Box::new(16i32) as Box<dyn Any>] // Float output target
+        vec![Box::new(1i32) as Box<AnyValue>,Box::new(4i32) as Box<AnyValue>,Box::new(9i32) as Box<AnyValue>, Box::new(16i32) as Box<AnyValue>] // Float output target
     ).unwrap();
     
     // Create tree orchestrator targeting Float output to test mixed-type propagation
@@ -229,15 +231,15 @@ fn test_random_tree_generation() {
 
     // Create a simple dataset with mixed types
     let mut input_values_two = HashMap::new();
-    input_values_two.insert("x".to_string(), Box::new(5i32) as Box<dyn Any>);
-    input_values_two.insert("y".to_string(), Box::new(3i32) as Box<dyn Any>);
-    // input_values_two.insert("z".to_string(), Box::new(2.5f64) as Box<dyn Any>);
-    // input_values_two.insert("w".to_string(), Box::new(1.5f64) as Box<dyn Any>);
+    input_values_two.insert("x".to_string(), Box::new(5i32) as Box<AnyValue>);
+    input_values_two.insert("y".to_string(), Box::new(3i32) as Box<AnyValue>);
+    // input_values_two.insert("z".to_string(), Box::new(2.5f64) as Box<AnyValue>);
+    // input_values_two.insert("w".to_string(), Box::new(1.5f64) as Box<AnyValue>);
 
     let rdata = stsr::types::DataRow::from_map(&variable_definitions_two, input_values_two).unwrap();
 
-    let tval = Rc::new(8.0f64) as Rc<dyn Any>;
-    let data = EvalInput::Data(&rdata, &tval);
+    let tval = Arc::new(8.0f64) as Arc<AnyValue>;
+    let data = EvalInput::Data(&rdata, &tval, 1.0);
 
     orchestrator.evaluate_fitness();
 }
@@ -318,16 +320,17 @@ fn test_perfect_tree_fitness() {
     tree.tree.push(terminal_node_two);
 
     print_tree_structure(&tree);
+    println!("{:#}", tree);
 
     let mut input_values_1 = HashMap::new();
     let mut input_values_2 = HashMap::new();
     let mut input_values_3 = HashMap::new();
     let mut input_values_4 = HashMap::new();
 
-    input_values_1.insert("x".to_string(), Box::new(1i32) as Box<dyn Any>);
-    input_values_2.insert("x".to_string(), Box::new(2i32) as Box<dyn Any>);
-    input_values_3.insert("x".to_string(), Box::new(3i32) as Box<dyn Any>);
-    input_values_4.insert("x".to_string(), Box::new(4i32) as Box<dyn Any>);
+    input_values_1.insert("x".to_string(), Box::new(1i32) as Box<AnyValue>);
+    input_values_2.insert("x".to_string(), Box::new(2i32) as Box<AnyValue>);
+    input_values_3.insert("x".to_string(), Box::new(3i32) as Box<AnyValue>);
+    input_values_4.insert("x".to_string(), Box::new(4i32) as Box<AnyValue>);
 
 
     let data_row_1 = stsr::types::DataRow::from_map(&variable_definitions, input_values_1).unwrap();
@@ -337,10 +340,10 @@ fn test_perfect_tree_fitness() {
 
     let dataset = Dataset::new(
         vec![data_row_1, data_row_2,data_row_3,data_row_4],
-        vec![Box::new(1i32) as Box<dyn Any>,Box::new(4i32) as Box<dyn Any>,Box::new(9i32) as Box<dyn Any>, Box::new(16i32) as Box<dyn Any>] // Float output target
+        vec![Box::new(1i32) as Box<AnyValue>,Box::new(4i32) as Box<AnyValue>,Box::new(9i32) as Box<AnyValue>, Box::new(16i32) as Box<AnyValue>] // Float output target
     ).unwrap();
 
-    tree.evaluate_fitness(&dataset, &nt_grammar);
+    tree.evaluate_fitness(&dataset, &nt_grammar, &variable_definitions);
 }
 
 fn print_tree_structure(tree: &stsr::tree_builder::ParseTree) {
@@ -1,8 +1,9 @@
-use crate::nonterminal::NonTerminalGrammar;
+use crate::nonterminal::RuleId;
 use crate::ops::Operation;
-use crate::types::{DataRow, DataType, Shape, TypeInfo};
+use crate::tree_builder::ParseTree;
+use crate::types::{AnyValue, TypeInfo};
+use crate::utils::clone_value;
 // use crate::registry::TypeRegistry;
-use crate::variable::VariableContext;
 use std::any::Any;
 
 type InputOneType = TypeInfo;
@@ -10,6 +11,7 @@ type InputTwoType = TypeInfo;
 type OutputType = TypeInfo;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeType {
     // Heap-allocated NonTerminal
     /// input type, input type, operation, output type
@@ -19,10 +21,61 @@ pub enum NodeType {
     Terminal(TypeInfo),
 }
 
-/// Given two inputs and an operation, return possible output types
+impl NodeType {
+    /// Type of the value this node produces, regardless of whether it is a terminal or a nonterminal.
+    pub fn output_type(&self) -> TypeInfo {
+        match self {
+            NodeType::NonTerminal(_, _, _, output) => *output,
+            NodeType::Terminal(type_info) => *type_info,
+        }
+    }
+
+    /// Identifier of the rule a nonterminal was built from.
+    pub fn rule_id(&self) -> Option<RuleId> {
+        match *self {
+            NodeType::NonTerminal(input_one_type, input_two_type, operation, output) => Some(RuleId {
+                operation,
+                input_one_type,
+                input_two_type,
+                output,
+            }),
+            NodeType::Terminal(_) => None,
+        }
+    }
+}
+
+/// Given two inputs and an operation, return possible output types.
+/// For unary operations (`Operation::arity`) input2 is ignored.
 pub fn compatible_outputs(input1: TypeInfo, input2: TypeInfo, op: Operation) -> Vec<TypeInfo> {
     use crate::types::{DataType, Shape};
-    
+
+    match (op, input1.shape, input2.shape) {
+        (Operation::Add | Operation::Subtract | Operation::Multiply | Operation::Divide, _, _) => {}
+        (Operation::Dot, Shape::Vector(n1), Shape::Vector(n2)) if n1 == n2 && input1.data_type == input2.data_type => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Scalar }];
+        }
+        (Operation::Outer, Shape::Vector(m), Shape::Vector(n)) if input1.data_type == input2.data_type => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Matrix(m, n) }];
+        }
+        (Operation::Transpose, Shape::Matrix(r, c), _) => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Matrix(c, r) }];
+        }
+        (Operation::MatVec, Shape::Matrix(r, c), Shape::Vector(n)) if c == n && input1.data_type == input2.data_type => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Vector(r) }];
+        }
+        (Operation::Norm, Shape::Vector(_), _) => {
+            return vec![TypeInfo { data_type: DataType::Float, shape: Shape::Scalar }];
+        }
+        // Element of a vector, row of a matrix
+        (Operation::Element, Shape::Vector(_), Shape::Scalar) if input2.data_type == DataType::Integer => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Scalar }];
+        }
+        (Operation::Element, Shape::Matrix(_, c), Shape::Scalar) if input2.data_type == DataType::Integer => {
+            return vec![TypeInfo { data_type: input1.data_type, shape: Shape::Vector(c) }];
+        }
+        _ => return vec![],
+    }
+
     match (input1.shape, input2.shape, op, input1.data_type == input2.data_type) {
         // Scalar + Scalar
         (Shape::Scalar, Shape::Scalar, _, true) => {
@@ -39,6 +92,11 @@ pub fn compatible_outputs(input1: TypeInfo, input2: TypeInfo, op: Operation) ->
             vec![TypeInfo { data_type: input1.data_type, shape: Shape::Vector(n) }]
         },
         
+        // Matrix + Scalar broadcasting
+        (Shape::Matrix(r, c), Shape::Scalar, _, true) | (Shape::Scalar, Shape::Matrix(r, c), _, true) => {
+            vec![TypeInfo { data_type: input1.data_type, shape: Shape::Matrix(r, c) }]
+        },
+
         // Matrix + Matrix
         (Shape::Matrix(r1, c1), Shape::Matrix(r2, c2), Operation::Add | Operation::Subtract, true) 
             if r1 == r2 && c1 == c2 => {
@@ -54,7 +112,7 @@ pub fn compatible_outputs(input1: TypeInfo, input2: TypeInfo, op: Operation) ->
     }
 }
 
-/// Given an operation and desired output, return possible input pairs
+// Given an operation and desired output, return possible input pairs
 // pub fn compatible_inputs(op: Operation, output: TypeInfo) -> Vec<(TypeInfo, TypeInfo)> {
 //     use crate::types::{DataType, Shape};
     
@@ -103,11 +161,17 @@ pub fn compatible_outputs(input1: TypeInfo, input2: TypeInfo, op: Operation) ->
 //     inputs
 // }
 
+/// With the `serde` feature, serialized through `serialize::NodeRecord`, which keeps only the values of constants.
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "crate::serialize::NodeRecord", try_from = "crate::serialize::NodeRecord")
+)]
 pub struct Node {
     pub idx: usize,
     pub _type: NodeType, // for GPSR
-    pub value: Box<dyn Any>,
+    pub value: Box<AnyValue>,
     pub variable_id: Option<String>, // for generics that need to pull value from variable.rs HashMap.
     pub left_index: Option<usize>,
     pub right_index: Option<usize>,
@@ -115,6 +179,27 @@ pub struct Node {
     pub depth: usize,
 }
 
+impl Clone for Node {
+    /// Terminal values are cloned as-is. Cached nonterminal outputs of a custom representation
+    /// are reset to a placeholder, they get overwritten by the next evaluation anyway.
+    fn clone(&self) -> Self {
+        let output_type = self._type.output_type();
+        let value = clone_value(self.value.as_ref(), output_type)
+            .unwrap_or_else(|| ParseTree::create_placeholder_value(output_type));
+
+        Node {
+            idx: self.idx,
+            _type: self._type,
+            value,
+            variable_id: self.variable_id.clone(),
+            left_index: self.left_index,
+            right_index: self.right_index,
+            parent_index: self.parent_index,
+            depth: self.depth,
+        }
+    }
+}
+
 // pub trait MatchesTerminal {
 //     const DATA_TYPE: DataType;
 //     fn get_shape(&self) -> Shape; 
@@ -248,7 +333,8 @@ impl Node {
         self.depth
     }
 
-    pub fn new_non_terminal<T: 'static>(
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_non_terminal<T: Any + Send + Sync>(
         idx: usize,
         variable_id: Option<String>,
         operation: Operation,
@@ -1,21 +1,37 @@
-use crate::{ops::Operation, types::TypeInfo};
+use crate::{batch::BatchFn, codegen::Target, ops::Operation, types::{AnyValue, TypeInfo}};
+use std::collections::HashMap;
 
-/// implementation of non-terminals
+// implementation of non-terminals
 
 // I think the idea here is that some sort of type registry will determine if the inputs can ever correspond to the output (based on the op).
+#[allow(dead_code)]
 struct NonTerminal {
     inputs: Vec<TypeInfo>,
     output: TypeInfo,
     operation: Operation 
 }
-#[derive(Debug)]
+/// Identifies a rule within a grammar: its operation, input types and output type. Nonterminal nodes carry the
+/// same information in their `NodeType` and are bound to their rule through it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RuleId {
+    pub operation: Operation,
+    pub input_one_type: TypeInfo,
+    pub input_two_type: TypeInfo,
+    pub output: TypeInfo,
+}
+
+#[derive(Debug, Clone)]
 
 pub struct NonTerminalRule {
     pub input_one_type: TypeInfo,
     pub input_two_type: TypeInfo,
     pub operation: Operation,
     pub output: TypeInfo,
-    pub func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<dyn std::any::Any>,
+    pub func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<AnyValue>,
+    /// Optional column-wise implementation used by batched evaluation.
+    pub batch_func: Option<BatchFn>,
+    /// Source code of this rule per export target, see `codegen`.
+    pub templates: HashMap<Target, String>,
 }
 
 impl NonTerminalRule {
@@ -24,46 +40,108 @@ impl NonTerminalRule {
         input_two_type: TypeInfo, 
         operation: Operation, 
         output: TypeInfo,
-        func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<dyn std::any::Any>
+        func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<AnyValue>
     ) -> Self {
         NonTerminalRule {
             input_one_type,
             input_two_type,
             operation,
             output,
-            func
+            func,
+            batch_func: None,
+            templates: HashMap::new(),
+        }
+    }
+
+    /// Rule of a unary operation (`Operation::arity`). Its second input type mirrors the first one and its
+    /// function gets `&()` as second input.
+    pub fn unary(
+        input_type: TypeInfo,
+        operation: Operation,
+        output: TypeInfo,
+        func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<AnyValue>
+    ) -> Self {
+        Self::new(input_type, input_type, operation, output, func)
+    }
+
+    pub fn arity(&self) -> usize {
+        self.operation.arity()
+    }
+
+    pub fn id(&self) -> RuleId {
+        RuleId {
+            operation: self.operation,
+            input_one_type: self.input_one_type,
+            input_two_type: self.input_two_type,
+            output: self.output,
         }
     }
 
+    /// Attach a column-wise implementation of this rule for batched evaluation.
+    /// Integer columns are carried as f64, so the implementation should keep integer semantics itself. The
+    /// output of an integer rule is truncated and wrapped to i32 afterwards either way.
+    pub fn with_batch(mut self, batch_func: BatchFn) -> Self {
+        self.batch_func = Some(batch_func);
+        self
+    }
+
+    /// Attach the source of this rule for an export target. `{0}` and `{1}` stand for the two operands,
+    /// e.g. `"{0} + {1}"`. The expression has to produce the rule's output type in the target language.
+    pub fn with_template(mut self, target: Target, template: &str) -> Self {
+        self.templates.insert(target, template.to_string());
+        self
+    }
+
+    pub fn template(&self, target: Target) -> Option<&str> {
+        self.templates.get(&target).map(|template| template.as_str())
+    }
+
     /// Helper to create scalar arithmetic rules
     pub fn scalar_arithmetic(
         data_type: crate::types::DataType,
         operation: Operation,
-        func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<dyn std::any::Any>
+        func: fn(&dyn std::any::Any, &dyn std::any::Any) -> Box<AnyValue>
     ) -> Self {
         let scalar_type = TypeInfo { 
             shape: crate::types::Shape::Scalar, 
-            data_type: data_type 
+            data_type
         };
         Self::new(scalar_type, scalar_type, operation, scalar_type, func)
     }
 
+    /// Stable identifier of the rule, e.g. `Add(Float/Scalar, Float/Scalar) -> Float/Scalar`.
+    /// Checkpoints refer to rules by it, since the function pointers cannot be saved.
+    pub fn signature(&self) -> String {
+        match self.arity() {
+            1 => format!("{}({}) -> {}", self.operation, self.input_one_type, self.output),
+            _ => format!("{}({}, {}) -> {}", self.operation, self.input_one_type, self.input_two_type, self.output),
+        }
+    }
+
+    /// Execute the operation on a node's children values, `right` is None for unary rules.
+    pub fn execute_children(&self, left: &dyn std::any::Any, right: Option<&dyn std::any::Any>) -> Box<AnyValue> {
+        (self.func)(left, right.unwrap_or(&()))
+    }
+
     /// Execute the operation with the given inputs
-    pub fn execute(&self, input1: &dyn std::any::Any, input2: &dyn std::any::Any) -> Box<dyn std::any::Any> {
+    pub fn execute(&self, input1: &dyn std::any::Any, input2: &dyn std::any::Any) -> Box<AnyValue> {
         (self.func)(input1, input2)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 /// meant to be user-defined
 pub struct NonTerminalGrammar {
-    pub rules: Vec<NonTerminalRule>
+    pub rules: Vec<NonTerminalRule>,
+    /// Position in `rules` of the first rule with each id, kept up to date by `add_rule`.
+    index: HashMap<RuleId, usize>,
 }
 
 impl NonTerminalGrammar {
     pub fn new() -> Self {
         NonTerminalGrammar {
-            rules: Vec::new()
+            rules: Vec::new(),
+            index: HashMap::new(),
         }
     }
 
@@ -81,9 +159,20 @@ impl NonTerminalGrammar {
         // if (rule.input_one_type != rule.input_two_type) {
         //     self.rules.push(swapped);
         // }
+        self.index.entry(rule.id()).or_insert(self.rules.len());
         self.rules.push(rule);
     }
 
+    /// The first rule with this id. Rules pushed to `rules` directly are not indexed and are found by a scan.
+    pub fn find_rule(&self, id: RuleId) -> Option<&NonTerminalRule> {
+        let indexed = self.index.get(&id).and_then(|&idx| self.rules.get(idx)).filter(|rule| rule.id() == id);
+        indexed.or_else(|| self.rules.iter().find(|rule| rule.id() == id))
+    }
+
+    pub fn signatures(&self) -> Vec<String> {
+        self.rules.iter().map(|rule| rule.signature()).collect()
+    }
+
     pub fn get_all_possible_input_types_with_operations(&self, output_type: TypeInfo) -> Vec<(TypeInfo, TypeInfo, Operation)> {
         let mut temp: Vec<(TypeInfo, TypeInfo, Operation)> = Vec::new();
 
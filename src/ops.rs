@@ -1,9 +1,201 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
     Add,
     Subtract,
     Divide, 
-    Multiply
+    Multiply,
+    /// Dot product of two vectors.
+    Dot,
+    /// Outer product of two vectors, a matrix.
+    Outer,
+    Transpose,
+    /// Product of a matrix and a vector.
+    MatVec,
+    /// Euclidean norm of a vector.
+    Norm,
+    /// Element of a vector, or row of a matrix, at an integer index.
+    Element,
+    /// A user defined operation, see `CustomOperation`.
+    Custom(CustomOperation),
 }
 
+impl Operation {
+    /// Shorthand for `Operation::Custom(CustomOperation::new(name, arity))`.
+    pub const fn custom(name: &str, arity: usize) -> Self {
+        Operation::Custom(CustomOperation::new(name, arity))
+    }
 
+    /// Number of inputs. Nodes of unary operations only have a left child.
+    pub fn arity(&self) -> usize {
+        match self {
+            Operation::Transpose | Operation::Norm => 1,
+            Operation::Custom(custom) => custom.arity,
+            _ => 2,
+        }
+    }
+
+    /// Name used when rendering the operation for people, which only differs from `Display` for custom
+    /// operations given a display name.
+    pub fn display_name(&self) -> String {
+        match self {
+            Operation::Custom(custom) => custom.display_name().to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// A domain specific operation, declared once and used in rules like the built-in ones:
+///
+/// ```ignore
+/// const SIGMOID: Operation = Operation::Custom(CustomOperation::new("Sigmoid", 1).with_display_name("σ"));
+/// ```
+///
+/// The name identifies the operation: it is what S-expressions, saved trees and rule signatures contain, so it
+/// has to be unique and must not clash with a built-in operation. The display name is only used by `render`.
+/// Both are stored inline, at most `OpName::MAX_LEN` bytes each, so the operation stays `Copy` whether it was
+/// declared in code or read from a file.
+#[derive(Copy, Clone, Debug)]
+pub struct CustomOperation {
+    name: OpName,
+    display_name: OpName,
+    /// 1 or 2.
+    pub arity: usize,
+}
+
+impl CustomOperation {
+    /// Panics, at compile time for constants, if the arity is not 1 or 2 or the name is too long.
+    pub const fn new(name: &str, arity: usize) -> Self {
+        assert!(arity == 1 || arity == 2, "operations take one or two inputs");
+        let name = OpName::new(name);
+        CustomOperation {
+            name,
+            display_name: name,
+            arity,
+        }
+    }
+
+    /// `new` for names only known at runtime, e.g. read from a grammar file or a saved tree.
+    pub fn try_new(name: &str, arity: usize) -> Result<Self, String> {
+        if arity != 1 && arity != 2 {
+            return Err(format!("Operation '{}' has arity {}, operations take one or two inputs", name, arity));
+        }
+        OpName::check(name)?;
+        Ok(Self::new(name, arity))
+    }
+
+    pub const fn with_display_name(mut self, display_name: &str) -> Self {
+        self.display_name = OpName::new(display_name);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_str()
+    }
+}
+
+/// Operations are identified by name and arity, the display name is cosmetic.
+impl PartialEq for CustomOperation {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.arity == other.arity
+    }
+}
+
+impl Eq for CustomOperation {}
+
+impl std::hash::Hash for CustomOperation {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.arity.hash(state);
+    }
+}
+
+/// A short string stored by value.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct OpName {
+    bytes: [u8; OpName::MAX_LEN],
+    len: u8,
+}
+
+impl OpName {
+    pub const MAX_LEN: usize = 23;
+
+    const fn new(text: &str) -> Self {
+        let text = text.as_bytes();
+        assert!(text.len() <= Self::MAX_LEN, "operation names are at most 23 bytes long");
+        let mut bytes = [0; Self::MAX_LEN];
+        let mut idx = 0;
+        while idx < text.len() {
+            bytes[idx] = text[idx];
+            idx += 1;
+        }
+        OpName { bytes, len: text.len() as u8 }
+    }
+
+    fn check(text: &str) -> Result<(), String> {
+        if text.len() > Self::MAX_LEN {
+            return Err(format!("'{}' is longer than {} bytes", text, Self::MAX_LEN));
+        }
+        Ok(())
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).expect("copied from a str")
+    }
+}
+
+impl std::fmt::Debug for OpName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CustomOperation {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.name(), self.display_name(), self.arity).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CustomOperation {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (name, display_name, arity) = <(String, String, usize)>::deserialize(deserializer)?;
+        OpName::check(&display_name).map_err(serde::de::Error::custom)?;
+        Ok(CustomOperation::try_new(&name, arity).map_err(serde::de::Error::custom)?.with_display_name(&display_name))
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Custom(custom) => write!(f, "{}", custom.name()),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Built-in operations only, custom ones are not known by name.
+impl std::str::FromStr for Operation {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "Add" => Ok(Operation::Add),
+            "Subtract" => Ok(Operation::Subtract),
+            "Divide" => Ok(Operation::Divide),
+            "Multiply" => Ok(Operation::Multiply),
+            "Dot" => Ok(Operation::Dot),
+            "Outer" => Ok(Operation::Outer),
+            "Transpose" => Ok(Operation::Transpose),
+            "MatVec" => Ok(Operation::MatVec),
+            "Norm" => Ok(Operation::Norm),
+            "Element" => Ok(Operation::Element),
+            other => Err(format!("Unknown operation '{}'", other)),
+        }
+    }
+}
@@ -1,15 +1,23 @@
-/// Possibility tables outlined in Montana's paper on page 10.
-/// Each row represents the possible types at a specific depth of the tree.
-/// Derived from nonterminal rules to ensure type safety during tree generation.
+//! Possibility tables outlined in Montana's paper on page 10.
+//! Each row represents the possible types at a specific depth of the tree.
+//! Derived from nonterminal rules to ensure type safety during tree generation.
+//!
+//! A type is in a row only if it is reachable from the target type through rules and can still be completed into a
+//! whole subtree in the depth left below it, so generation never runs into a type it cannot finish. Terminal types
+//! are those of the variables, plus types no variable can end, which are filled with random constants.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 use crate::types::{TypeInfo, VariableDefinitions};
 use crate::nonterminal::NonTerminalGrammar;
+use crate::ops::Operation;
 
 #[derive(Debug)]
 pub struct PossibilityTable {
     possibilities: Vec<HashSet<TypeInfo>>,
+    /// Minimum height of a subtree of each type, a single terminal being 1.
+    min_heights: HashMap<TypeInfo, usize>,
+    terminals: HashSet<TypeInfo>,
     max_depth: usize,
 }
 
@@ -17,6 +25,8 @@ impl PossibilityTable {
     pub fn empty(max_depth: usize) -> Self {        
         PossibilityTable { 
             possibilities: Vec::with_capacity(max_depth),
+            min_heights: HashMap::new(),
+            terminals: HashSet::new(),
             max_depth,
         }
     }
@@ -38,39 +48,115 @@ impl PossibilityTable {
         variables: &VariableDefinitions,
         target_type: TypeInfo,
     ) {
+        self.build_min_heights(grammar, variables, target_type);
+
         self.possibilities.clear();
         self.possibilities.resize(self.max_depth, HashSet::new());
 
-        // Depth 0 (root) must produce the target type
-        self.possibilities[0].insert(target_type);
-        // Build possibilities for each depth level
-        for depth in 0..self.max_depth - 1 {
+        // Depth 0 (root) must produce the target type, if a tree of it fits at all
+        if self.fits_at_depth(0, target_type) {
+            self.possibilities[0].insert(target_type);
+        }
+        // Top-down: the inputs of every rule that can still be completed below the current depth
+        for depth in 0..self.max_depth.saturating_sub(1) {
             let current_types = self.possibilities[depth].clone();
-            println!("CURRENT TYPES: {:?} DEPTH [{:?}]", depth, current_types);
 
             for current_type in current_types {
-                // Get all possible input combinations that can produce this type
-                let input_combinations = grammar.get_all_possible_input_types_with_operations(current_type);
-                println!("INPUT COMBINATIONS for depth {:?}: {:?}", &depth, input_combinations);
-                for (input1_type, input2_type, _) in input_combinations {
-                    // Add both input types as possibilities for the next depth
+                for (input1_type, input2_type, operation) in self.expansions(grammar, depth, current_type) {
+                    // Add the input types as possibilities for the next depth, unary operations only have one
                     self.possibilities[depth + 1].insert(input1_type);
-                    self.possibilities[depth + 1].insert(input2_type);
+                    if operation.arity() == 2 {
+                        self.possibilities[depth + 1].insert(input2_type);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bottom-up pass: the minimum height of each type, iterated to a fixed point over the rules. Types that no
+    /// variable can end, directly or through rules, become constant terminals of height 1.
+    fn build_min_heights(&mut self, grammar: &NonTerminalGrammar, variables: &VariableDefinitions, target_type: TypeInfo) {
+        let mut all_types: Vec<TypeInfo> = vec![target_type];
+        for rule in &grammar.rules {
+            let inputs = [rule.input_one_type, rule.input_two_type];
+            for type_info in inputs[..rule.arity()].iter().chain([&rule.output]) {
+                if !all_types.contains(type_info) {
+                    all_types.push(*type_info);
                 }
             }
         }
 
-        // At maximum depth, we can only have terminals (variables)
-        let terminal_types: HashSet<TypeInfo> = variables.variables
-            .iter()
-            .map(|var| var._type)
+        self.terminals = variables.variables.iter().map(|var| var._type).collect();
+        self.min_heights = self.terminals.iter().map(|type_info| (*type_info, 1)).collect();
+        self.relax_min_heights(grammar);
+
+        let constants: Vec<TypeInfo> = all_types
+            .into_iter()
+            .filter(|type_info| !self.min_heights.contains_key(type_info))
             .collect();
-        
-        if let Some(last_depth) = self.possibilities.last_mut() {
-            last_depth.extend(terminal_types);
+        if !constants.is_empty() {
+            for type_info in constants {
+                self.terminals.insert(type_info);
+                self.min_heights.insert(type_info, 1);
+            }
+            // Constant terminals can shorten the types built from them
+            self.relax_min_heights(grammar);
         }
     }
 
+    fn relax_min_heights(&mut self, grammar: &NonTerminalGrammar) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &grammar.rules {
+                let inputs = [rule.input_one_type, rule.input_two_type];
+                let height = inputs[..rule.arity()]
+                    .iter()
+                    .map(|input| self.min_heights.get(input).copied())
+                    .try_fold(0, |height, input| Some(height.max(input?)));
+                let Some(height) = height.map(|height| height + 1) else {
+                    continue;
+                };
+                if self.min_heights.get(&rule.output).is_none_or(|current| height < *current) {
+                    self.min_heights.insert(rule.output, height);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn fits_at_depth(&self, depth: usize, type_info: TypeInfo) -> bool {
+        self.min_height(type_info).is_some_and(|height| depth + height <= self.max_depth)
+    }
+
+    /// The input types and operations of the rules producing `type_info` that can be used at `depth`, i.e. whose
+    /// inputs can all be completed within the remaining depth.
+    pub fn expansions(
+        &self,
+        grammar: &NonTerminalGrammar,
+        depth: usize,
+        type_info: TypeInfo,
+    ) -> Vec<(TypeInfo, TypeInfo, Operation)> {
+        grammar
+            .get_all_possible_input_types_with_operations(type_info)
+            .into_iter()
+            .filter(|(input1_type, input2_type, operation)| {
+                self.fits_at_depth(depth + 1, *input1_type)
+                    && (operation.arity() == 1 || self.fits_at_depth(depth + 1, *input2_type))
+            })
+            .collect()
+    }
+
+    /// Minimum height of a subtree producing `type_info`, None if it can never be completed.
+    pub fn min_height(&self, type_info: TypeInfo) -> Option<usize> {
+        self.min_heights.get(&type_info).copied()
+    }
+
+    /// Whether a node of this type can be a leaf, a variable or a constant.
+    pub fn is_terminal(&self, type_info: TypeInfo) -> bool {
+        self.terminals.contains(&type_info)
+    }
+
     pub fn get_possible_types_at_depth(&self, depth: usize) -> Option<&HashSet<TypeInfo>> {
         self.possibilities.get(depth)
     }
@@ -78,17 +164,17 @@ impl PossibilityTable {
     pub fn can_produce_type_at_depth(&self, depth: usize, type_info: TypeInfo) -> bool {
         self.possibilities
             .get(depth)
-            .map_or(false, |types| types.contains(&type_info))
+            .is_some_and(|types| types.contains(&type_info))
     }
 
     pub fn get_max_depth(&self) -> usize {
         self.max_depth
     }
 
+    /// Whether the table has been built and a tree of the target type fits within max depth. Deeper levels may be
+    /// empty when every branch ends earlier. `NonTerminalGrammar::diagnose` reports what is wrong with a grammar.
     pub fn is_valid_for_generation(&self) -> bool {
-        // Check if table has been built and all depth levels have at least one possible type
-        !self.possibilities.is_empty() && 
-        self.possibilities.iter().all(|types| !types.is_empty())
+        self.possibilities.first().is_some_and(|types| !types.is_empty())
     }
 }
 
@@ -1,3 +1,260 @@
+//! Named built-in implementations of rules, so rules can be declared outside of Rust code, see `grammar_file`.
+//!
+//! A `BuiltinFunction` carries the per-value function of a rule, an optional column implementation for batched
+//! evaluation, a check of which input and output types it can serve, its number of inputs and, for the built-in
+//! ones, the operation it computes. A rule of a built-in operation can only be bound to a function computing that
+//! operation; custom operations can use any function taking as many inputs. `FunctionRegistry::with_builtins` holds
+//! the standard operations of `standard` and the scalar arithmetic below; projects add their own functions with
+//! `register`.
+
+use crate::{
+    batch::BatchFn,
+    node::compatible_outputs,
+    nonterminal::NonTerminalRule,
+    ops::Operation,
+    standard::{self, standard_output},
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+use std::{any::Any, collections::HashMap};
+
+/// Per-value implementation of a rule: left operand, right operand.
+pub type RuleFn = fn(&dyn Any, &dyn Any) -> Box<AnyValue>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BuiltinFunction {
+    pub func: RuleFn,
+    pub batch_func: Option<BatchFn>,
+    /// Whether the function can implement a rule with these left, right and output types.
+    pub accepts: fn(TypeInfo, TypeInfo, TypeInfo) -> bool,
+    /// Number of inputs, 2 by default. Unary functions get `&()` as their right operand.
+    pub arity: usize,
+    /// The built-in operation the function computes, None for functions only meant for custom operations.
+    pub operation: Option<Operation>,
+}
+
+impl BuiltinFunction {
+    pub fn new(func: RuleFn, accepts: fn(TypeInfo, TypeInfo, TypeInfo) -> bool) -> Self {
+        BuiltinFunction {
+            func,
+            batch_func: None,
+            accepts,
+            arity: 2,
+            operation: None,
+        }
+    }
+
+    pub fn with_batch(mut self, batch_func: BatchFn) -> Self {
+        self.batch_func = Some(batch_func);
+        self
+    }
+
+    pub fn with_arity(mut self, arity: usize) -> Self {
+        self.arity = arity;
+        self
+    }
+
+    /// Marks the function as computing a built-in operation, taking that operation's number of inputs.
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.arity = operation.arity();
+        self.operation = Some(operation);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, BuiltinFunction>,
+}
+
+impl FunctionRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `add`, `subtract`, `multiply` and `divide` from `standard`, for any shapes the standard rules support, and
+    /// `dot`, `outer`, `transpose`, `matvec`, `norm` and `element` from `linalg`.
+    /// Scalar only: `add_i32`, `sub_i32`, `mul_i32` (wrapping) and `add_f64`, `sub_f64`, `mul_f64`, `div_f64`
+    /// (protected like `divide`: a zero divisor gives 1).
+    pub fn with_builtins() -> Self {
+        Self::new()
+            .with_function(
+                "add",
+                BuiltinFunction::new(standard::add, |l, r, o| standard_output(Operation::Add, l, r) == Some(o))
+                    .with_operation(Operation::Add),
+            )
+            .with_function(
+                "subtract",
+                BuiltinFunction::new(standard::subtract, |l, r, o| standard_output(Operation::Subtract, l, r) == Some(o))
+                    .with_operation(Operation::Subtract),
+            )
+            .with_function(
+                "multiply",
+                BuiltinFunction::new(standard::multiply, |l, r, o| standard_output(Operation::Multiply, l, r) == Some(o))
+                    .with_operation(Operation::Multiply),
+            )
+            .with_function(
+                "divide",
+                BuiltinFunction::new(standard::divide, |l, r, o| standard_output(Operation::Divide, l, r) == Some(o))
+                    .with_operation(Operation::Divide),
+            )
+            .with_function("dot", linear_algebra(Operation::Dot))
+            .with_function("outer", linear_algebra(Operation::Outer))
+            .with_function("transpose", linear_algebra(Operation::Transpose))
+            .with_function("matvec", linear_algebra(Operation::MatVec))
+            .with_function("norm", linear_algebra(Operation::Norm))
+            .with_function("element", linear_algebra(Operation::Element))
+            .with_function("add_i32", scalar(add_i32, integer_scalars, Operation::Add, standard::add_i32_batch))
+            .with_function("sub_i32", scalar(sub_i32, integer_scalars, Operation::Subtract, standard::subtract_i32_batch))
+            .with_function("mul_i32", scalar(mul_i32, integer_scalars, Operation::Multiply, standard::multiply_i32_batch))
+            .with_function("add_f64", scalar(add_f64, float_scalars, Operation::Add, standard::add_batch))
+            .with_function("sub_f64", scalar(sub_f64, float_scalars, Operation::Subtract, standard::subtract_batch))
+            .with_function("mul_f64", scalar(mul_f64, float_scalars, Operation::Multiply, standard::multiply_batch))
+            .with_function("div_f64", scalar(div_f64, float_scalars, Operation::Divide, standard::divide_batch))
+    }
+
+    /// Adds a function, replacing any previous one of the same name.
+    pub fn register(&mut self, name: &str, function: BuiltinFunction) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    pub fn with_function(mut self, name: &str, function: BuiltinFunction) -> Self {
+        self.register(name, function);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BuiltinFunction> {
+        self.functions.get(name)
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Builds a rule bound to the function called `name`, checking that the function computes the rule's operation,
+    /// or takes as many inputs for a custom one, and accepts the rule's types.
+    pub fn rule(
+        &self,
+        name: &str,
+        input_one_type: TypeInfo,
+        input_two_type: TypeInfo,
+        operation: Operation,
+        output: TypeInfo,
+    ) -> Result<NonTerminalRule, String> {
+        let function = self
+            .get(name)
+            .ok_or_else(|| format!("Unknown function '{}', available: {}", name, self.names().join(", ")))?;
+        if function.arity != operation.arity() {
+            return Err(format!(
+                "Function '{}' takes {} inputs, {} takes {}",
+                name,
+                function.arity,
+                operation,
+                operation.arity()
+            ));
+        }
+        match function.operation {
+            Some(computed) if computed != operation && !matches!(operation, Operation::Custom(_)) => {
+                return Err(format!("Function '{}' computes {}, not {}", name, computed, operation));
+            }
+            _ => {}
+        }
+        if !(function.accepts)(input_one_type, input_two_type, output) {
+            return Err(format!(
+                "Function '{}' cannot implement {}({}, {}) -> {}",
+                name, operation, input_one_type, input_two_type, output
+            ));
+        }
+
+        let rule = NonTerminalRule::new(input_one_type, input_two_type, operation, output, function.func);
+        Ok(match function.batch_func {
+            Some(batch_func) => rule.with_batch(batch_func),
+            None => rule,
+        })
+    }
+}
+
+/// A `linalg` function, accepting the types `node::compatible_outputs` gives for its operation.
+fn linear_algebra(operation: Operation) -> BuiltinFunction {
+    let accepts: fn(TypeInfo, TypeInfo, TypeInfo) -> bool = match operation {
+        Operation::Dot => |l, r, o| compatible_outputs(l, r, Operation::Dot).contains(&o),
+        Operation::Outer => |l, r, o| compatible_outputs(l, r, Operation::Outer).contains(&o),
+        Operation::Transpose => |l, r, o| l == r && compatible_outputs(l, r, Operation::Transpose).contains(&o),
+        Operation::MatVec => |l, r, o| compatible_outputs(l, r, Operation::MatVec).contains(&o),
+        Operation::Norm => |l, r, o| l == r && compatible_outputs(l, r, Operation::Norm).contains(&o),
+        Operation::Element => |l, r, o| compatible_outputs(l, r, Operation::Element).contains(&o),
+        _ => |_, _, _| false,
+    };
+    BuiltinFunction::new(standard::standard_function(operation), accepts).with_operation(operation)
+}
+
+fn scalar(
+    func: RuleFn,
+    accepts: fn(TypeInfo, TypeInfo, TypeInfo) -> bool,
+    operation: Operation,
+    batch_func: BatchFn,
+) -> BuiltinFunction {
+    BuiltinFunction::new(func, accepts).with_operation(operation).with_batch(batch_func)
+}
+
+fn scalars_of(data_type: DataType, types: [TypeInfo; 3]) -> bool {
+    types.iter().all(|type_info| type_info.shape == Shape::Scalar && type_info.data_type == data_type)
+}
+
+fn integer_scalars(left: TypeInfo, right: TypeInfo, output: TypeInfo) -> bool {
+    scalars_of(DataType::Integer, [left, right, output])
+}
+
+fn float_scalars(left: TypeInfo, right: TypeInfo, output: TypeInfo) -> bool {
+    scalars_of(DataType::Float, [left, right, output])
+}
+
+fn integers(a: &dyn Any, b: &dyn Any) -> (i32, i32) {
+    (*a.downcast_ref::<i32>().unwrap(), *b.downcast_ref::<i32>().unwrap())
+}
+
+fn floats(a: &dyn Any, b: &dyn Any) -> (f64, f64) {
+    (*a.downcast_ref::<f64>().unwrap(), *b.downcast_ref::<f64>().unwrap())
+}
+
+fn add_i32(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = integers(a, b);
+    Box::new(a.wrapping_add(b))
+}
+
+fn sub_i32(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = integers(a, b);
+    Box::new(a.wrapping_sub(b))
+}
+
+fn mul_i32(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = integers(a, b);
+    Box::new(a.wrapping_mul(b))
+}
+
+fn add_f64(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = floats(a, b);
+    Box::new(a + b)
+}
+
+fn sub_f64(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = floats(a, b);
+    Box::new(a - b)
+}
+
+fn mul_f64(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = floats(a, b);
+    Box::new(a * b)
+}
+
+fn div_f64(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    let (a, b) = floats(a, b);
+    Box::new(if b == 0.0 { 1.0 } else { a / b })
+}
+
 // use crate::types::{DataType::{self, *}, Shape::{self, *}};
 // use crate::ops::Operation;
 // use rand::Rng;
@@ -0,0 +1,296 @@
+//! Human readable rendering of trees for reports.
+//!
+//! `Renderer` writes a `ParseTree` as conventional infix math, `x + x * 3`, or as LaTeX, `x + x \cdot 3`.
+//! Parentheses are only added where precedence or associativity requires them. Operator symbols default to the
+//! usual ones for each `Operation` and can be overridden per notation; in LaTeX, `Divide` is written as `\frac`
+//! unless it has been given a symbol. The linear algebra operations are written as calls, `dot(u, v)`, whose name
+//! the symbol overrides; in LaTeX `Dot`, `Norm`, `Transpose` and `Element` use their usual notation instead.
+//! Custom operations are written as calls too, under their display name.
+//!
+//! `Renderer::dot` draws the tree for Graphviz instead, one graph node per tree node labelled with its operation,
+//! variable or constant and its type, e.g. `Add : Float/Scalar`, so the flow of types through the tree is visible.
+
+use crate::{
+    node::NodeType,
+    ops::Operation,
+    tree_builder::ParseTree,
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+use std::collections::HashMap;
+
+/// Binding strength of a constant, a variable or anything already enclosed (parentheses, `\frac`).
+const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notation {
+    Infix,
+    Latex,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Renderer {
+    /// Digits after the decimal point for float constants. `None` prints the shortest exact form.
+    precision: Option<usize>,
+    infix_symbols: HashMap<Operation, String>,
+    latex_symbols: HashMap<Operation, String>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_precision(mut self, digits: usize) -> Self {
+        self.precision = Some(digits);
+        self
+    }
+
+    /// Symbol used for `operation` in infix output.
+    pub fn with_symbol(mut self, operation: Operation, symbol: &str) -> Self {
+        self.infix_symbols.insert(operation, symbol.to_string());
+        self
+    }
+
+    /// Symbol used for `operation` in LaTeX output.
+    pub fn with_latex_symbol(mut self, operation: Operation, symbol: &str) -> Self {
+        self.latex_symbols.insert(operation, symbol.to_string());
+        self
+    }
+
+    pub fn infix(&self, tree: &ParseTree) -> String {
+        self.render(tree, Notation::Infix)
+    }
+
+    pub fn latex(&self, tree: &ParseTree) -> String {
+        self.render(tree, Notation::Latex)
+    }
+
+    /// Graphviz DOT graph of the tree, edges go from each nonterminal to its left then right child.
+    pub fn dot(&self, tree: &ParseTree) -> String {
+        let mut dot = format!(
+            "digraph tree_{} {{\n    graph [ordering=out];\n    node [fontname=\"Helvetica\"];\n",
+            tree.id
+        );
+
+        for node in &tree.tree {
+            let output_type = node._type.output_type();
+            let (text, shape) = match (node._type, &node.variable_id) {
+                (NodeType::NonTerminal(_, _, operation, _), _) => (operation.display_name(), "ellipse"),
+                (NodeType::Terminal(_), Some(name)) => (name.clone(), "box"),
+                (NodeType::Terminal(_), None) => (self.constant(node.value.as_ref(), output_type, Notation::Infix), "plaintext"),
+            };
+            let label = format!("{} : {}", text, output_type).replace('\\', "\\\\").replace('"', "\\\"");
+            dot.push_str(&format!("    n{} [label=\"{}\", shape={}];\n", node.idx, label, shape));
+        }
+        for node in &tree.tree {
+            for child_idx in node.left_index.iter().chain(node.right_index.iter()) {
+                dot.push_str(&format!("    n{} -> n{};\n", node.idx, child_idx));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn render(&self, tree: &ParseTree, notation: Notation) -> String {
+        if tree.tree.is_empty() {
+            return String::new();
+        }
+        self.render_node(tree, 0, notation).0
+    }
+
+    /// Renders the subtree at idx, returning the text and how tightly it binds.
+    fn render_node(&self, tree: &ParseTree, idx: usize, notation: Notation) -> (String, u8) {
+        let node = &tree.tree[idx];
+
+        let (operation, left_idx, right_idx) = match (node._type, node.left_index) {
+            (NodeType::NonTerminal(_, _, operation, _), Some(left_idx)) => (operation, left_idx, node.right_index),
+            _ => {
+                return match &node.variable_id {
+                    Some(name) => (self.variable(name, notation), ATOM_PRECEDENCE),
+                    None => {
+                        let text = self.constant(node.value.as_ref(), node._type.output_type(), notation);
+                        // A negative operand would read as a subtraction, `x - -3`, so it binds loosest.
+                        let precedence = if text.starts_with('-') { 0 } else { ATOM_PRECEDENCE };
+                        (text, precedence)
+                    }
+                };
+            }
+        };
+
+        let (left, left_precedence) = self.render_node(tree, left_idx, notation);
+        let right = right_idx.map(|right_idx| self.render_node(tree, right_idx, notation));
+
+        let (precedence, (right, right_precedence)) = match (precedence(operation), right) {
+            (Some(precedence), Some(right)) => (precedence, right),
+            (_, right) => {
+                let right = right.map(|(right, _)| right);
+                return (self.function(operation, (left, left_precedence), right, notation), ATOM_PRECEDENCE);
+            }
+        };
+
+        if notation == Notation::Latex && operation == Operation::Divide && !self.latex_symbols.contains_key(&operation) {
+            return (format!("\\frac{{{}}}{{{}}}", left, right), ATOM_PRECEDENCE);
+        }
+
+        let left = if left_precedence < precedence {
+            self.parenthesize(&left, notation)
+        } else {
+            left
+        };
+        // `a - (b - c)`, `a + (b - c)` and `a * (b / c)` keep their parentheses, `a + (b + c)` does not: with
+        // integer or protected division only the same associative operation can be regrouped.
+        let right_operation = right_idx.and_then(|right_idx| tree.tree[right_idx]._type.rule_id()).map(|id| id.operation);
+        let regroupable = is_associative(operation) && right_operation == Some(operation);
+        let right = if right_precedence < precedence || (right_precedence == precedence && !regroupable) {
+            self.parenthesize(&right, notation)
+        } else {
+            right
+        };
+
+        (format!("{} {} {}", left, self.symbol(operation, notation), right), precedence)
+    }
+
+    /// Operations without an infix symbol, written as a function call or with their usual LaTeX notation.
+    fn function(&self, operation: Operation, left: (String, u8), right: Option<String>, notation: Notation) -> String {
+        let (left, left_precedence) = left;
+        let operand = || if left_precedence < ATOM_PRECEDENCE { self.parenthesize(&left, notation) } else { left.clone() };
+        let overridden = self.symbols(notation).contains_key(&operation);
+
+        match (operation, notation, &right) {
+            (Operation::Dot, Notation::Latex, Some(right)) if !overridden => {
+                format!("\\langle {}, {} \\rangle", left, right)
+            }
+            (Operation::Norm, Notation::Latex, None) if !overridden => format!("\\lVert {} \\rVert", left),
+            (Operation::Transpose, Notation::Latex, None) if !overridden => format!("{{{}}}^\\top", operand()),
+            (Operation::Element, Notation::Latex, Some(right)) if !overridden => format!("{{{}}}_{{{}}}", operand(), right),
+            _ => {
+                let arguments = match &right {
+                    Some(right) => format!("{}, {}", left, right),
+                    None => left.clone(),
+                };
+                format!("{}{}", self.symbol(operation, notation), self.parenthesize(&arguments, notation))
+            }
+        }
+    }
+
+    fn symbols(&self, notation: Notation) -> &HashMap<Operation, String> {
+        match notation {
+            Notation::Infix => &self.infix_symbols,
+            Notation::Latex => &self.latex_symbols,
+        }
+    }
+
+    fn symbol(&self, operation: Operation, notation: Notation) -> String {
+        if let Some(symbol) = self.symbols(notation).get(&operation) {
+            return symbol.clone();
+        }
+
+        match (operation, notation) {
+            (Operation::Add, _) => "+",
+            (Operation::Subtract, _) => "-",
+            (Operation::Multiply, Notation::Infix) => "*",
+            (Operation::Multiply, Notation::Latex) => "\\cdot",
+            (Operation::Divide, _) => "/",
+            (Operation::Custom(custom), Notation::Infix) => return custom.display_name().to_string(),
+            (Operation::Custom(custom), Notation::Latex) => return format!("\\operatorname{{{}}}", custom.display_name()),
+            (_, Notation::Infix) => return operation.to_string().to_lowercase(),
+            (_, Notation::Latex) => return format!("\\operatorname{{{}}}", operation.to_string().to_lowercase()),
+        }
+        .to_string()
+    }
+
+    fn parenthesize(&self, text: &str, notation: Notation) -> String {
+        match notation {
+            Notation::Infix => format!("({})", text),
+            Notation::Latex => format!("\\left({}\\right)", text),
+        }
+    }
+
+    fn variable(&self, name: &str, notation: Notation) -> String {
+        match notation {
+            Notation::Infix => name.to_string(),
+            Notation::Latex if name.chars().count() == 1 => name.to_string(),
+            Notation::Latex => format!("\\mathrm{{{}}}", name.replace('_', "\\_")),
+        }
+    }
+
+    fn constant(&self, value: &AnyValue, type_info: TypeInfo, notation: Notation) -> String {
+        let integers = |values: &[i32]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+        let floats = |values: &[f64]| values.iter().map(|v| self.float(*v)).collect::<Vec<String>>();
+
+        let text = match (type_info.data_type, type_info.shape) {
+            (DataType::Integer, Shape::Scalar) => value.downcast_ref::<i32>().map(|v| v.to_string()),
+            (DataType::Float, Shape::Scalar) => value.downcast_ref::<f64>().map(|v| self.float(*v)),
+            (DataType::Integer, Shape::Vector(_)) => {
+                value.downcast_ref::<Vec<i32>>().map(|v| vector(&integers(v), notation))
+            }
+            (DataType::Float, Shape::Vector(_)) => value.downcast_ref::<Vec<f64>>().map(|v| vector(&floats(v), notation)),
+            (DataType::Integer, Shape::Matrix(_, _)) => value
+                .downcast_ref::<Vec<Vec<i32>>>()
+                .map(|m| matrix(m.iter().map(|row| integers(row)).collect(), notation)),
+            (DataType::Float, Shape::Matrix(_, _)) => value
+                .downcast_ref::<Vec<Vec<f64>>>()
+                .map(|m| matrix(m.iter().map(|row| floats(row)).collect(), notation)),
+        };
+        text.unwrap_or_else(|| "?".to_string())
+    }
+
+    fn float(&self, value: f64) -> String {
+        match self.precision {
+            Some(digits) => format!("{:.*}", digits, value),
+            None => value.to_string(),
+        }
+    }
+}
+
+impl ParseTree {
+    /// Infix form with the default `Renderer`.
+    pub fn to_infix(&self) -> String {
+        Renderer::new().infix(self)
+    }
+
+    /// LaTeX form with the default `Renderer`.
+    pub fn to_latex(&self) -> String {
+        Renderer::new().latex(self)
+    }
+
+    /// Graphviz DOT form with the default `Renderer`.
+    pub fn to_dot(&self) -> String {
+        Renderer::new().dot(self)
+    }
+}
+
+/// None for operations written as functions.
+fn precedence(operation: Operation) -> Option<u8> {
+    match operation {
+        Operation::Add | Operation::Subtract => Some(1),
+        Operation::Multiply | Operation::Divide => Some(2),
+        _ => None,
+    }
+}
+
+/// Whether `a op (b op c)` equals `a op b op c`.
+fn is_associative(operation: Operation) -> bool {
+    matches!(operation, Operation::Add | Operation::Multiply)
+}
+
+fn vector(elements: &[String], notation: Notation) -> String {
+    match notation {
+        Notation::Infix => format!("[{}]", elements.join(", ")),
+        Notation::Latex => format!("\\begin{{bmatrix}} {} \\end{{bmatrix}}", elements.join(" \\\\ ")),
+    }
+}
+
+fn matrix(rows: Vec<Vec<String>>, notation: Notation) -> String {
+    match notation {
+        Notation::Infix => format!(
+            "[{}]",
+            rows.iter().map(|row| format!("[{}]", row.join(", "))).collect::<Vec<String>>().join(", ")
+        ),
+        Notation::Latex => format!(
+            "\\begin{{bmatrix}} {} \\end{{bmatrix}}",
+            rows.iter().map(|row| row.join(" & ")).collect::<Vec<String>>().join(" \\\\ ")
+        ),
+    }
+}
@@ -0,0 +1,266 @@
+//! Saving and loading trees, populations and run settings. Only available with the `serde` feature.
+//!
+//! Node values are type erased, so they go through `Value`, which covers the built-in representations. Only the
+//! values of constant terminals are stored: variables and nonterminals get a fresh placeholder on load, their
+//! value is recomputed by the next evaluation anyway. Nonterminals keep their full `NodeType`, i.e. the operation
+//! and the types of the rule they were built from.
+//!
+//! JSON has no NaN or infinity, so non-finite fitness values and constants are written as the strings `"NaN"`,
+//! `"inf"` and `"-inf"`. The binary format is bincode and stores them as is. Both read every float back bit for bit,
+//! for JSON through serde_json's `float_roundtrip` parser, so a resumed run continues exactly where it stopped.
+
+use crate::{
+    node::{Node, NodeType},
+    tree_builder::ParseTree,
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// bincode, compact but not self-describing.
+    Binary,
+}
+
+pub fn to_bytes<T: Serialize>(value: &T, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Json => serde_json::to_vec_pretty(value).map_err(|err| format!("Cannot write JSON: {}", err)),
+        Format::Binary => bincode::serialize(value).map_err(|err| format!("Cannot write binary: {}", err)),
+    }
+}
+
+pub fn from_bytes<T: DeserializeOwned>(bytes: &[u8], format: Format) -> Result<T, String> {
+    match format {
+        Format::Json => serde_json::from_slice(bytes).map_err(|err| format!("Cannot read JSON: {}", err)),
+        Format::Binary => bincode::deserialize(bytes).map_err(|err| format!("Cannot read binary: {}", err)),
+    }
+}
+
+pub fn save<T: Serialize>(value: &T, path: impl AsRef<Path>, format: Format) -> Result<(), String> {
+    let path = path.as_ref();
+    std::fs::write(path, to_bytes(value, format)?).map_err(|err| format!("Cannot write '{}': {}", path.display(), err))
+}
+
+pub fn load<T: DeserializeOwned>(path: impl AsRef<Path>, format: Format) -> Result<T, String> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|err| format!("Cannot read '{}': {}", path.display(), err))?;
+    from_bytes(&bytes, format)
+}
+
+/// Serializable form of a node value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Integer(i32),
+    Float(#[serde(with = "float")] f64),
+    IntegerVector(Vec<i32>),
+    FloatVector(#[serde(with = "float_vec")] Vec<f64>),
+    IntegerMatrix(Vec<Vec<i32>>),
+    FloatMatrix(#[serde(with = "float_matrix")] Vec<Vec<f64>>),
+}
+
+impl Value {
+    /// Reads a value stored in the representation `type_info` describes.
+    pub fn from_any(value: &AnyValue, type_info: TypeInfo) -> Option<Value> {
+        match (type_info.data_type, type_info.shape) {
+            (DataType::Integer, Shape::Scalar) => value.downcast_ref::<i32>().map(|v| Value::Integer(*v)),
+            (DataType::Float, Shape::Scalar) => value.downcast_ref::<f64>().map(|v| Value::Float(*v)),
+            (DataType::Integer, Shape::Vector(_)) => value.downcast_ref::<Vec<i32>>().map(|v| Value::IntegerVector(v.clone())),
+            (DataType::Float, Shape::Vector(_)) => value.downcast_ref::<Vec<f64>>().map(|v| Value::FloatVector(v.clone())),
+            (DataType::Integer, Shape::Matrix(_, _)) => {
+                value.downcast_ref::<Vec<Vec<i32>>>().map(|v| Value::IntegerMatrix(v.clone()))
+            }
+            (DataType::Float, Shape::Matrix(_, _)) => {
+                value.downcast_ref::<Vec<Vec<f64>>>().map(|v| Value::FloatMatrix(v.clone()))
+            }
+        }
+    }
+
+    /// Whether this value has the data type and dimensions of `type_info`.
+    pub fn matches(&self, type_info: TypeInfo) -> bool {
+        fn has_dimensions<T>(matrix: &[Vec<T>], rows: usize, cols: usize) -> bool {
+            matrix.len() == rows && matrix.iter().all(|row| row.len() == cols)
+        }
+
+        match (self, type_info.data_type, type_info.shape) {
+            (Value::Integer(_), DataType::Integer, Shape::Scalar) => true,
+            (Value::Float(_), DataType::Float, Shape::Scalar) => true,
+            (Value::IntegerVector(v), DataType::Integer, Shape::Vector(size)) => v.len() == size,
+            (Value::FloatVector(v), DataType::Float, Shape::Vector(size)) => v.len() == size,
+            (Value::IntegerMatrix(m), DataType::Integer, Shape::Matrix(rows, cols)) => has_dimensions(m, rows, cols),
+            (Value::FloatMatrix(m), DataType::Float, Shape::Matrix(rows, cols)) => has_dimensions(m, rows, cols),
+            _ => false,
+        }
+    }
+
+    pub fn into_boxed(self) -> Box<AnyValue> {
+        match self {
+            Value::Integer(v) => Box::new(v),
+            Value::Float(v) => Box::new(v),
+            Value::IntegerVector(v) => Box::new(v),
+            Value::FloatVector(v) => Box::new(v),
+            Value::IntegerMatrix(v) => Box::new(v),
+            Value::FloatMatrix(v) => Box::new(v),
+        }
+    }
+}
+
+/// Serialized form of a `Node`, see `Node`'s serde attributes.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct NodeRecord {
+    idx: usize,
+    #[serde(rename = "type")]
+    node_type: NodeType,
+    /// Only set for constant terminals.
+    value: Option<Value>,
+    variable_id: Option<String>,
+    left_index: Option<usize>,
+    right_index: Option<usize>,
+    parent_index: usize,
+    depth: usize,
+}
+
+impl From<Node> for NodeRecord {
+    fn from(node: Node) -> Self {
+        let is_constant = matches!(node._type, NodeType::Terminal(_)) && node.variable_id.is_none();
+        let value = if is_constant {
+            Value::from_any(node.value.as_ref(), node._type.output_type())
+        } else {
+            None
+        };
+
+        NodeRecord {
+            idx: node.idx,
+            node_type: node._type,
+            value,
+            variable_id: node.variable_id,
+            left_index: node.left_index,
+            right_index: node.right_index,
+            parent_index: node.parent_index,
+            depth: node.depth,
+        }
+    }
+}
+
+impl TryFrom<NodeRecord> for Node {
+    type Error = String;
+
+    fn try_from(record: NodeRecord) -> Result<Self, Self::Error> {
+        let output_type = record.node_type.output_type();
+        let is_constant = matches!(record.node_type, NodeType::Terminal(_)) && record.variable_id.is_none();
+
+        let value = match record.value {
+            Some(value) if value.matches(output_type) => value.into_boxed(),
+            Some(value) => return Err(format!("Node {}: value {:?} is not {}", record.idx, value, output_type)),
+            None if is_constant => return Err(format!("Node {}: constant without a value", record.idx)),
+            None => ParseTree::create_placeholder_value(output_type),
+        };
+
+        Ok(Node {
+            idx: record.idx,
+            _type: record.node_type,
+            value,
+            variable_id: record.variable_id,
+            left_index: record.left_index,
+            right_index: record.right_index,
+            parent_index: record.parent_index,
+            depth: record.depth,
+        })
+    }
+}
+
+/// f64 that survives JSON: non-finite values become strings in human readable formats.
+#[derive(Clone, Copy)]
+struct Float(f64);
+
+impl Serialize for Float {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_finite() || !serializer.is_human_readable() {
+            serializer.serialize_f64(self.0)
+        } else if self.0.is_nan() {
+            serializer.serialize_str("NaN")
+        } else if self.0 > 0.0 {
+            serializer.serialize_str("inf")
+        } else {
+            serializer.serialize_str("-inf")
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Float {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return f64::deserialize(deserializer).map(Float);
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => Ok(Float(value)),
+            Repr::Text(text) => match text.as_str() {
+                "NaN" => Ok(Float(f64::NAN)),
+                "inf" => Ok(Float(f64::INFINITY)),
+                "-inf" => Ok(Float(f64::NEG_INFINITY)),
+                other => Err(serde::de::Error::custom(format!("invalid float '{}'", other))),
+            },
+        }
+    }
+}
+
+pub(crate) mod float {
+    use super::Float;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        Float(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Float::deserialize(deserializer).map(|float| float.0)
+    }
+}
+
+pub(crate) mod float_option {
+    use super::Float;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        value.map(Float).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+        Option::<Float>::deserialize(deserializer).map(|value| value.map(|float| float.0))
+    }
+}
+
+pub(crate) mod float_vec {
+    use super::Float;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| Float(*value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        Vec::<Float>::deserialize(deserializer).map(|values| values.into_iter().map(|float| float.0).collect())
+    }
+}
+
+pub(crate) mod float_matrix {
+    use super::Float;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(rows: &[Vec<f64>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(rows.iter().map(|row| row.iter().map(|value| Float(*value)).collect::<Vec<Float>>()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<f64>>, D::Error> {
+        Vec::<Vec<Float>>::deserialize(deserializer)
+            .map(|rows| rows.into_iter().map(|row| row.into_iter().map(|float| float.0).collect()).collect())
+    }
+}
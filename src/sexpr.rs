@@ -0,0 +1,473 @@
+//! S-expression form of a `ParseTree`.
+//!
+//! `Display` renders a tree as `(Add x (Multiply x 3))`. The alternate form, `{:#}`, annotates every node with
+//! its type: `(Add:Integer/Scalar x:Integer/Scalar 3:Integer/Scalar)`. Vector constants are written `[1 2 3]` and
+//! matrices row by row, `[[1 2] [3 4]]`. Floats always keep a decimal point or exponent.
+//!
+//! `ParseTree::from_sexpr` reads the same text back. Types are resolved top down from the required output type:
+//! an operation name selects the grammar rules that produce the expected type, and each one is tried until its
+//! arguments type check. Atoms name a variable if one is defined with that name, otherwise they are constants.
+//! Annotations are optional when parsing; when present they have to agree with the inferred type.
+
+use crate::{
+    node::{Node, NodeType},
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    tree_builder::ParseTree,
+    types::{AnyValue, DataType, Shape, TypeInfo, VariableDefinitions},
+};
+use std::{fmt, str::FromStr};
+
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tree.is_empty() {
+            return write!(f, "()");
+        }
+        self.fmt_node(0, f)
+    }
+}
+
+impl ParseTree {
+    fn fmt_node(&self, idx: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node = &self.tree[idx];
+        let output_type = node._type.output_type();
+
+        match (node._type, node.left_index) {
+            (NodeType::NonTerminal(_, _, operation, _), Some(left_idx)) => {
+                write!(f, "({}", operation)?;
+                if f.alternate() {
+                    write!(f, ":{}", output_type)?;
+                }
+                write!(f, " ")?;
+                self.fmt_node(left_idx, f)?;
+                if let Some(right_idx) = node.right_index {
+                    write!(f, " ")?;
+                    self.fmt_node(right_idx, f)?;
+                }
+                write!(f, ")")
+            }
+            _ => {
+                match &node.variable_id {
+                    Some(name) => write!(f, "{}", name)?,
+                    None => write_value(f, node.value.as_ref(), output_type)?,
+                }
+                if f.alternate() {
+                    write!(f, ":{}", output_type)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Rebuilds a tree from its S-expression form. The root has to produce `required_type`, every operation has to
+    /// match a rule of the grammar and every atom a variable of the right type or a constant literal.
+    pub fn from_sexpr(
+        text: &str,
+        grammar: &NonTerminalGrammar,
+        variable_definitions: &VariableDefinitions,
+        required_type: TypeInfo,
+    ) -> Result<ParseTree, String> {
+        let tokens = tokenize(text)?;
+        let mut position = 0;
+        let expr = parse_expr(&tokens, &mut position)?;
+        if position != tokens.len() {
+            return Err(format!("Unexpected {} after the end of the expression", tokens[position]));
+        }
+
+        let mut builder = TreeBuilder {
+            grammar,
+            variable_definitions,
+            tree: ParseTree::empty(0),
+        };
+        builder.build(&expr, required_type, 0, 0)?;
+        Ok(builder.tree)
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &AnyValue, type_info: TypeInfo) -> fmt::Result {
+    fn write_row<T: fmt::Debug>(f: &mut fmt::Formatter<'_>, row: &[T]) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, element) in row.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:?}", element)?;
+        }
+        write!(f, "]")
+    }
+
+    fn write_matrix<T: fmt::Debug>(f: &mut fmt::Formatter<'_>, matrix: &[Vec<T>]) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, row) in matrix.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write_row(f, row)?;
+        }
+        write!(f, "]")
+    }
+
+    // Debug formatting of f64 always round trips and keeps the decimal point.
+    let written = match (type_info.data_type, type_info.shape) {
+        (DataType::Integer, Shape::Scalar) => value.downcast_ref::<i32>().map(|v| write!(f, "{}", v)),
+        (DataType::Float, Shape::Scalar) => value.downcast_ref::<f64>().map(|v| write!(f, "{:?}", v)),
+        (DataType::Integer, Shape::Vector(_)) => value.downcast_ref::<Vec<i32>>().map(|v| write_row(f, v)),
+        (DataType::Float, Shape::Vector(_)) => value.downcast_ref::<Vec<f64>>().map(|v| write_row(f, v)),
+        (DataType::Integer, Shape::Matrix(_, _)) => value.downcast_ref::<Vec<Vec<i32>>>().map(|v| write_matrix(f, v)),
+        (DataType::Float, Shape::Matrix(_, _)) => value.downcast_ref::<Vec<Vec<f64>>>().map(|v| write_matrix(f, v)),
+    };
+    written.unwrap_or_else(|| write!(f, "<opaque>"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Atom(String),
+    /// Type following a `:`.
+    Annotation(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::OpenBracket => write!(f, "'['"),
+            Token::CloseBracket => write!(f, "']'"),
+            Token::Atom(text) => write!(f, "'{}'", text),
+            Token::Annotation(text) => write!(f, "':{}'", text),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '[' | ']' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    '[' => Token::OpenBracket,
+                    _ => Token::CloseBracket,
+                });
+            }
+            ':' => {
+                chars.next();
+                // Shapes carry their dimensions in parentheses, e.g. `Float/Matrix(2,3)`.
+                let mut annotation = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == '(' {
+                        for c in chars.by_ref() {
+                            annotation.push(c);
+                            if c == ')' {
+                                break;
+                            }
+                        }
+                    } else if c.is_whitespace() || matches!(c, ')' | '[' | ']' | ':') {
+                        break;
+                    } else {
+                        annotation.push(c);
+                        chars.next();
+                    }
+                }
+                if annotation.is_empty() {
+                    return Err("Expected a type after ':'".to_string());
+                }
+                tokens.push(Token::Annotation(annotation));
+            }
+            _ => {
+                let mut atom = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | ':') {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Atom(atom));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Untyped syntax tree, before it is checked against the grammar.
+#[derive(Debug, Clone)]
+enum SExpr {
+    Atom {
+        text: String,
+        annotation: Option<TypeInfo>,
+    },
+    List {
+        operation: String,
+        annotation: Option<TypeInfo>,
+        args: Vec<SExpr>,
+    },
+    Array {
+        items: Vec<SExpr>,
+        annotation: Option<TypeInfo>,
+    },
+}
+
+fn parse_annotation(tokens: &[Token], position: &mut usize) -> Result<Option<TypeInfo>, String> {
+    match tokens.get(*position) {
+        Some(Token::Annotation(text)) => {
+            *position += 1;
+            TypeInfo::from_str(text).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_expr(tokens: &[Token], position: &mut usize) -> Result<SExpr, String> {
+    let token = tokens.get(*position).ok_or("Unexpected end of input")?;
+    *position += 1;
+
+    match token {
+        Token::Atom(text) => Ok(SExpr::Atom {
+            text: text.clone(),
+            annotation: parse_annotation(tokens, position)?,
+        }),
+        Token::Open => {
+            let operation = match tokens.get(*position) {
+                Some(Token::Atom(name)) => name.clone(),
+                Some(other) => return Err(format!("Expected an operation name after '(', got {}", other)),
+                None => return Err("Unexpected end of input after '('".to_string()),
+            };
+            *position += 1;
+            let annotation = parse_annotation(tokens, position)?;
+
+            let mut args = Vec::new();
+            loop {
+                match tokens.get(*position) {
+                    Some(Token::Close) => {
+                        *position += 1;
+                        break;
+                    }
+                    Some(_) => args.push(parse_expr(tokens, position)?),
+                    None => return Err(format!("Missing ')' for '({}'", operation)),
+                }
+            }
+            Ok(SExpr::List {
+                operation,
+                annotation,
+                args,
+            })
+        }
+        Token::OpenBracket => {
+            let mut items = Vec::new();
+            loop {
+                match tokens.get(*position) {
+                    Some(Token::CloseBracket) => {
+                        *position += 1;
+                        break;
+                    }
+                    Some(_) => items.push(parse_expr(tokens, position)?),
+                    None => return Err("Missing ']'".to_string()),
+                }
+            }
+            Ok(SExpr::Array {
+                items,
+                annotation: parse_annotation(tokens, position)?,
+            })
+        }
+        other => Err(format!("Unexpected {}", other)),
+    }
+}
+
+/// Checks an `SExpr` against the grammar while laying it out in pre-order, the way the generator does.
+struct TreeBuilder<'g> {
+    grammar: &'g NonTerminalGrammar,
+    variable_definitions: &'g VariableDefinitions,
+    tree: ParseTree,
+}
+
+impl TreeBuilder<'_> {
+    fn build(&mut self, expr: &SExpr, expected: TypeInfo, depth: usize, parent_idx: usize) -> Result<usize, String> {
+        match expr {
+            SExpr::Atom { annotation, .. } | SExpr::List { annotation, .. } | SExpr::Array { annotation, .. } => {
+                if let Some(annotation) = annotation {
+                    if *annotation != expected {
+                        return Err(format!("Annotated as {}, but {} is expected here", annotation, expected));
+                    }
+                }
+            }
+        }
+
+        match expr {
+            SExpr::List { operation, args, .. } => self.build_operation(operation, args, expected, depth, parent_idx),
+            SExpr::Atom { text, .. } => {
+                if let Some(var) = self.variable_definitions.variables.iter().find(|var| &var.name == text) {
+                    if var._type != expected {
+                        return Err(format!("Variable '{}' is {}, but {} is expected here", text, var._type, expected));
+                    }
+                    return Ok(self.push_terminal(Some(text.clone()), ParseTree::create_placeholder_value(expected), expected, depth, parent_idx));
+                }
+                if expected.shape != Shape::Scalar {
+                    return Err(format!("'{}' is not a variable or a {} constant", text, expected));
+                }
+                let value: Box<AnyValue> = match expected.data_type {
+                    DataType::Integer => Box::new(parse_scalar::<i32>(text, expected)?),
+                    DataType::Float => Box::new(parse_scalar::<f64>(text, expected)?),
+                };
+                Ok(self.push_terminal(None, value, expected, depth, parent_idx))
+            }
+            SExpr::Array { items, .. } => {
+                let value: Box<AnyValue> = match (expected.data_type, expected.shape) {
+                    (DataType::Integer, Shape::Vector(size)) => Box::new(parse_vector::<i32>(items, size, expected)?),
+                    (DataType::Float, Shape::Vector(size)) => Box::new(parse_vector::<f64>(items, size, expected)?),
+                    (DataType::Integer, Shape::Matrix(rows, cols)) => {
+                        Box::new(parse_matrix::<i32>(items, rows, cols, expected)?)
+                    }
+                    (DataType::Float, Shape::Matrix(rows, cols)) => {
+                        Box::new(parse_matrix::<f64>(items, rows, cols, expected)?)
+                    }
+                    (_, Shape::Scalar) => return Err(format!("Found an array literal, but {} is expected here", expected)),
+                };
+                Ok(self.push_terminal(None, value, expected, depth, parent_idx))
+            }
+        }
+    }
+
+    fn build_operation(
+        &mut self,
+        operation: &str,
+        args: &[SExpr],
+        expected: TypeInfo,
+        depth: usize,
+        parent_idx: usize,
+    ) -> Result<usize, String> {
+        let named: Vec<&NonTerminalRule> = self
+            .grammar
+            .rules
+            .iter()
+            .filter(|rule| rule.operation.to_string() == operation)
+            .collect();
+        if named.is_empty() {
+            return Err(format!("Unknown operation '{}'", operation));
+        }
+        // Operations of different arities can share a name, e.g. custom ones
+        if named.iter().all(|rule| rule.arity() != args.len()) {
+            let mut arities: Vec<usize> = named.iter().map(|rule| rule.arity()).collect();
+            arities.sort();
+            arities.dedup();
+            let arities: Vec<String> = arities.iter().map(|arity| arity.to_string()).collect();
+            return Err(format!("'{}' takes {} arguments, got {}", operation, arities.join(" or "), args.len()));
+        }
+
+        let candidates: Vec<&NonTerminalRule> = named.into_iter().filter(|rule| rule.output == expected).collect();
+        let mut errors = Vec::new();
+
+        // Several rules can share an operation and output type; backtrack until one of them fits the arguments.
+        for rule in &candidates {
+            if rule.arity() != args.len() {
+                errors.push(format!("'{}' producing {} takes {} arguments, got {}", operation, expected, rule.arity(), args.len()));
+                continue;
+            }
+            let idx = self.tree.tree.len();
+            self.tree.tree.push(Node {
+                idx,
+                _type: NodeType::NonTerminal(rule.input_one_type, rule.input_two_type, rule.operation, expected),
+                value: ParseTree::create_placeholder_value(expected),
+                variable_id: None,
+                left_index: None,
+                right_index: None,
+                parent_index: parent_idx,
+                depth,
+            });
+
+            let children = self.build(&args[0], rule.input_one_type, depth + 1, idx).and_then(|left_idx| {
+                let right_idx = match args.get(1) {
+                    Some(right) => Some(self.build(right, rule.input_two_type, depth + 1, idx)?),
+                    None => None,
+                };
+                Ok((left_idx, right_idx))
+            });
+            match children {
+                Ok((left_idx, right_idx)) => {
+                    self.tree.tree[idx].left_index = Some(left_idx);
+                    self.tree.tree[idx].right_index = right_idx;
+                    return Ok(idx);
+                }
+                Err(err) => {
+                    self.tree.tree.truncate(idx);
+                    errors.push(err);
+                }
+            }
+        }
+
+        match errors.len() {
+            0 => Err(format!("No rule for '{}' produces {}", operation, expected)),
+            1 => Err(errors.remove(0)),
+            _ => Err(format!(
+                "No rule for '{}' producing {} accepts these arguments: {}",
+                operation,
+                expected,
+                errors.join("; ")
+            )),
+        }
+    }
+
+    fn push_terminal(
+        &mut self,
+        variable_id: Option<String>,
+        value: Box<AnyValue>,
+        type_info: TypeInfo,
+        depth: usize,
+        parent_idx: usize,
+    ) -> usize {
+        let idx = self.tree.tree.len();
+        self.tree.tree.push(Node {
+            idx,
+            _type: NodeType::Terminal(type_info),
+            value,
+            variable_id,
+            left_index: None,
+            right_index: None,
+            parent_index: parent_idx,
+            depth,
+        });
+        idx
+    }
+}
+
+fn parse_scalar<T: FromStr>(text: &str, expected: TypeInfo) -> Result<T, String> {
+    text.parse::<T>()
+        .map_err(|_| format!("'{}' is not a variable or a {} constant", text, expected))
+}
+
+fn parse_vector<T: FromStr>(items: &[SExpr], size: usize, expected: TypeInfo) -> Result<Vec<T>, String> {
+    if items.len() != size {
+        return Err(format!("Expected {} elements for {}, got {}", size, expected, items.len()));
+    }
+    items
+        .iter()
+        .map(|item| match item {
+            SExpr::Atom { text, annotation: None } => parse_scalar::<T>(text, expected),
+            _ => Err(format!("Elements of a {} literal must be plain numbers", expected)),
+        })
+        .collect()
+}
+
+fn parse_matrix<T: FromStr>(items: &[SExpr], rows: usize, cols: usize, expected: TypeInfo) -> Result<Vec<Vec<T>>, String> {
+    if items.len() != rows {
+        return Err(format!("Expected {} rows for {}, got {}", rows, expected, items.len()));
+    }
+    items
+        .iter()
+        .map(|item| match item {
+            SExpr::Array { items, annotation: None } => parse_vector::<T>(items, cols, expected),
+            _ => Err(format!("Rows of a {} literal must be written as [..]", expected)),
+        })
+        .collect()
+}
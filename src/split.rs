@@ -0,0 +1,182 @@
+//! Dataset splitting for detecting overfitting.
+//!
+//! Splits are index based: the resulting datasets share their values with the original one.
+//! Fractions are of the whole dataset, the training set gets whatever is left over.
+
+use crate::types::{AnyValue, Dataset};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct DatasetSplit {
+    pub train: Dataset,
+    pub validation: Dataset,
+    pub test: Dataset,
+}
+
+/// Held-out fitness on each of k folds, see `TreeOrchestrator::cross_validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidation {
+    pub fold_fitness: Vec<f64>,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl CrossValidation {
+    pub fn from_folds(fold_fitness: Vec<f64>) -> Self {
+        let k = fold_fitness.len().max(1) as f64;
+        let mean = fold_fitness.iter().sum::<f64>() / k;
+        let variance = fold_fitness.iter().map(|fitness| (fitness - mean).powi(2)).sum::<f64>() / k;
+
+        CrossValidation {
+            fold_fitness,
+            mean,
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+/// Groups of rows sharing a target value, for stratified splits. Targets that are not scalars share one group.
+/// Floats are kept as `ordered_bits`, so strata sort by value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stratum {
+    Integer(i32),
+    Float(u64),
+    Other,
+}
+
+/// Bits of a float whose unsigned order is the order of `f64::total_cmp`: negatives have all their bits flipped
+/// so larger magnitudes come first, non-negatives get the sign bit set so they come after every negative.
+fn ordered_bits(value: f64) -> u64 {
+    let bits = value.to_bits();
+    if bits >> 63 == 1 { !bits } else { bits | 1 << 63 }
+}
+
+impl Stratum {
+    fn of(target: &AnyValue) -> Self {
+        if let Some(value) = target.downcast_ref::<i32>() {
+            Stratum::Integer(*value)
+        } else if let Some(value) = target.downcast_ref::<f64>() {
+            Stratum::Float(ordered_bits(*value))
+        } else {
+            Stratum::Other
+        }
+    }
+}
+
+impl Dataset {
+    /// Shuffles the rows, then cuts them into train, validation and test.
+    pub fn split_random(&self, validation_fraction: f64, test_fraction: f64, rng: &mut impl Rng) -> Result<DatasetSplit, String> {
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        indices.shuffle(rng);
+        self.split_ordered(&indices, validation_fraction, test_fraction)
+    }
+
+    /// Like `split_random`, but each part keeps the distribution of target values. Rows are grouped by target and
+    /// dealt out in target order, so every class gets its share, and continuous targets are spread over each part.
+    pub fn split_stratified(&self, validation_fraction: f64, test_fraction: f64, rng: &mut impl Rng) -> Result<DatasetSplit, String> {
+        check_fractions(validation_fraction, test_fraction)?;
+
+        let mut strata: BTreeMap<Stratum, Vec<usize>> = BTreeMap::new();
+        for (idx, target) in self.targets.iter().enumerate() {
+            strata.entry(Stratum::of(target.as_ref())).or_default().push(idx);
+        }
+
+        let (mut train, mut validation, mut test) = (Vec::new(), Vec::new(), Vec::new());
+        let mut position = 0;
+        for mut indices in strata.into_values() {
+            indices.shuffle(rng);
+            for idx in indices {
+                // Keep each part at its quota of the rows dealt so far.
+                position += 1;
+                let (validation_quota, test_quota) = part_sizes(position, validation_fraction, test_fraction);
+                if test.len() < test_quota {
+                    test.push(idx);
+                } else if validation.len() < validation_quota {
+                    validation.push(idx);
+                } else {
+                    train.push(idx);
+                }
+            }
+        }
+
+        // Interleave the strata again so no part is ordered by target.
+        train.shuffle(rng);
+        validation.shuffle(rng);
+        test.shuffle(rng);
+
+        Ok(DatasetSplit {
+            train: self.subset(&train),
+            validation: self.subset(&validation),
+            test: self.subset(&test),
+        })
+    }
+
+    /// For time series: rows are assumed to be in time order. The oldest rows are used for training,
+    /// followed by validation, then test, so nothing is ever evaluated on data older than what it was trained on.
+    pub fn split_time_ordered(&self, validation_fraction: f64, test_fraction: f64) -> Result<DatasetSplit, String> {
+        let len = self.len();
+        check_fractions(validation_fraction, test_fraction)?;
+        let (validation_count, test_count) = part_sizes(len, validation_fraction, test_fraction);
+        let train_count = len - validation_count - test_count;
+
+        let indices: Vec<usize> = (0..len).collect();
+        Ok(DatasetSplit {
+            train: self.subset(&indices[..train_count]),
+            validation: self.subset(&indices[train_count..train_count + validation_count]),
+            test: self.subset(&indices[train_count + validation_count..]),
+        })
+    }
+
+    /// Shuffles the rows and deals them into k folds. Returns (training rows, held-out fold) for each fold.
+    pub fn k_folds(&self, k: usize, rng: &mut impl Rng) -> Result<Vec<(Dataset, Dataset)>, String> {
+        if k < 2 || k > self.len() {
+            return Err(format!("k must be between 2 and the number of rows ({}), got {}", self.len(), k));
+        }
+
+        let mut indices: Vec<usize> = (0..self.len()).collect();
+        indices.shuffle(rng);
+
+        Ok((0..k)
+            .map(|fold| {
+                let (mut train, mut held_out) = (Vec::new(), Vec::new());
+                for (position, &idx) in indices.iter().enumerate() {
+                    if position % k == fold {
+                        held_out.push(idx);
+                    } else {
+                        train.push(idx);
+                    }
+                }
+                (self.subset(&train), self.subset(&held_out))
+            })
+            .collect())
+    }
+
+    fn split_ordered(&self, indices: &[usize], validation_fraction: f64, test_fraction: f64) -> Result<DatasetSplit, String> {
+        check_fractions(validation_fraction, test_fraction)?;
+        let (validation_count, test_count) = part_sizes(indices.len(), validation_fraction, test_fraction);
+
+        Ok(DatasetSplit {
+            test: self.subset(&indices[..test_count]),
+            validation: self.subset(&indices[test_count..test_count + validation_count]),
+            train: self.subset(&indices[test_count + validation_count..]),
+        })
+    }
+}
+
+fn check_fractions(validation_fraction: f64, test_fraction: f64) -> Result<(), String> {
+    // Written so NaN fails every comparison and is rejected
+    if !(validation_fraction >= 0.0 && test_fraction >= 0.0 && validation_fraction + test_fraction < 1.0) {
+        return Err(format!(
+            "Validation ({}) and test ({}) fractions must be non-negative and leave rows for training",
+            validation_fraction, test_fraction
+        ));
+    }
+    Ok(())
+}
+
+fn part_sizes(len: usize, validation_fraction: f64, test_fraction: f64) -> (usize, usize) {
+    let validation_count = (len as f64 * validation_fraction).round() as usize;
+    let test_count = ((len as f64 * test_fraction).round() as usize).min(len - validation_count);
+    (validation_count, test_count)
+}
@@ -0,0 +1,289 @@
+//! Ready-made rules for every `Operation` on scalars, vectors and matrices of both data types.
+//!
+//! The shapes follow `node::compatible_outputs`: operands of the same shape combine element-wise, a scalar is
+//! broadcast against a vector or matrix, and `Multiply` on two matrices is the matrix product. Integer arithmetic
+//! wraps on overflow. Division is protected: dividing by zero gives 1 instead of a panic, infinity or NaN.
+//!
+//! `NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar])` is the usual arithmetic grammar.
+
+use crate::{
+    codegen::Target,
+    linalg,
+    node::compatible_outputs,
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    registry::RuleFn,
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+use std::any::Any;
+
+pub const OPERATIONS: [Operation; 4] = [Operation::Add, Operation::Subtract, Operation::Multiply, Operation::Divide];
+
+/// Output type of the standard rule for `operation` on these inputs, if there is one.
+pub fn standard_output(operation: Operation, left: TypeInfo, right: TypeInfo) -> Option<TypeInfo> {
+    compatible_outputs(left, right, operation).first().copied()
+}
+
+/// Standard rules over every pair of the given shapes whose result also has one of the given shapes, so the
+/// grammar stays closed over them. Leave out `Shape::Scalar` to get no broadcasting.
+pub fn standard_rules(data_type: DataType, shapes: &[Shape]) -> Vec<NonTerminalRule> {
+    let mut rules = Vec::new();
+
+    for operation in OPERATIONS {
+        for &left_shape in shapes {
+            for &right_shape in shapes {
+                let left = TypeInfo { shape: left_shape, data_type };
+                let right = TypeInfo { shape: right_shape, data_type };
+                let Some(output) = standard_output(operation, left, right).filter(|output| shapes.contains(&output.shape))
+                else {
+                    continue;
+                };
+                rules.push(standard_rule(operation, left, right, output));
+            }
+        }
+    }
+
+    rules
+}
+
+fn standard_rule(operation: Operation, left: TypeInfo, right: TypeInfo, output: TypeInfo) -> NonTerminalRule {
+    let rule = NonTerminalRule::new(left, right, operation, output, standard_function(operation));
+    let scalar = left.shape == Shape::Scalar && right.shape == Shape::Scalar;
+
+    match (operation, output.data_type) {
+        (_, DataType::Float) if scalar && operation != Operation::Divide => rule.with_batch(match operation {
+            Operation::Add => add_batch,
+            Operation::Subtract => subtract_batch,
+            _ => multiply_batch,
+        }),
+        (_, DataType::Integer) if scalar && operation != Operation::Divide => rule.with_batch(match operation {
+            Operation::Add => add_i32_batch,
+            Operation::Subtract => subtract_i32_batch,
+            _ => multiply_i32_batch,
+        }),
+        (Operation::Divide, DataType::Float) if scalar => rule
+            .with_batch(divide_batch)
+            .with_template(Target::Rust, "if {1} == 0.0 { 1.0 } else { {0} / {1} }")
+            .with_template(Target::Python, "np.where({1} == 0, 1.0, {0} / np.where({1} == 0, 1.0, {1}))")
+            .with_template(Target::C, "({1} == 0.0 ? 1.0 : {0} / {1})"),
+        (Operation::Divide, DataType::Integer) if scalar => rule
+            .with_batch(divide_i32_batch)
+            .with_template(Target::Rust, "if {1} == 0 { 1 } else { {0}.wrapping_div({1}) }")
+            // float64 holds every int32 quotient exactly, trunc rounds toward zero like Rust
+            .with_template(
+                Target::Python,
+                "np.where({1} == 0, 1, np.trunc({0} / np.where({1} == 0, 1, {1}))).astype(np.int32)",
+            )
+            // INT32_MIN / -1 overflows, negate through uint32_t to wrap like wrapping_div
+            .with_template(Target::C, "({1} == 0 ? 1 : {1} == -1 ? (int32_t) (0u - (uint32_t) {0}) : {0} / {1})"),
+        _ => rule,
+    }
+}
+
+/// The shape generic implementation of a built-in operation, see the module documentation and `linalg`.
+pub fn function(operation: Operation) -> Option<RuleFn> {
+    Some(match operation {
+        Operation::Add => add,
+        Operation::Subtract => subtract,
+        Operation::Multiply => multiply,
+        Operation::Divide => divide,
+        Operation::Dot => linalg::dot,
+        Operation::Outer => linalg::outer,
+        Operation::Transpose => linalg::transpose,
+        Operation::MatVec => linalg::matvec,
+        Operation::Norm => linalg::norm,
+        Operation::Element => linalg::element,
+        Operation::Custom(_) => return None,
+    })
+}
+
+pub(crate) fn standard_function(operation: Operation) -> RuleFn {
+    function(operation).unwrap_or_else(|| panic!("{} is not a built-in operation", operation))
+}
+
+pub fn add(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Add, a, b)
+}
+
+pub fn subtract(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Subtract, a, b)
+}
+
+/// Element-wise, except for two matrices which are multiplied as matrices.
+pub fn multiply(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Multiply, a, b)
+}
+
+/// Protected: a zero divisor gives 1.
+pub fn divide(a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    evaluate(Operation::Divide, a, b)
+}
+
+fn evaluate(operation: Operation, a: &dyn Any, b: &dyn Any) -> Box<AnyValue> {
+    apply::<f64>(operation, a, b)
+        .or_else(|| apply::<i32>(operation, a, b))
+        .unwrap_or_else(|| panic!("{} is not defined for these operands", operation))
+}
+
+/// Element type of the built-in representations, i32 or f64.
+pub(crate) trait Number: Copy + Send + Sync + 'static {
+    const ZERO: Self;
+
+    /// One of the four arithmetic operations, with the semantics described in the module documentation.
+    fn apply(self, operation: Operation, other: Self) -> Self;
+
+    fn to_f64(self) -> f64;
+}
+
+impl Number for i32 {
+    const ZERO: Self = 0;
+
+    fn apply(self, operation: Operation, other: Self) -> Self {
+        match operation {
+            Operation::Add => self.wrapping_add(other),
+            Operation::Subtract => self.wrapping_sub(other),
+            Operation::Multiply => self.wrapping_mul(other),
+            Operation::Divide if other == 0 => 1,
+            Operation::Divide => self.wrapping_div(other),
+            other => unreachable!("{} is not arithmetic", other),
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Number for f64 {
+    const ZERO: Self = 0.0;
+
+    fn apply(self, operation: Operation, other: Self) -> Self {
+        match operation {
+            Operation::Add => self + other,
+            Operation::Subtract => self - other,
+            Operation::Multiply => self * other,
+            Operation::Divide if other == 0.0 => 1.0,
+            Operation::Divide => self / other,
+            other => unreachable!("{} is not arithmetic", other),
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+pub(crate) enum Operand<'a, T> {
+    Scalar(T),
+    Vector(&'a [T]),
+    Matrix(&'a [Vec<T>]),
+}
+
+pub(crate) fn operand<T: Number>(value: &dyn Any) -> Option<Operand<'_, T>> {
+    if let Some(scalar) = value.downcast_ref::<T>() {
+        Some(Operand::Scalar(*scalar))
+    } else if let Some(vector) = value.downcast_ref::<Vec<T>>() {
+        Some(Operand::Vector(vector))
+    } else {
+        value.downcast_ref::<Vec<Vec<T>>>().map(|matrix| Operand::Matrix(matrix))
+    }
+}
+
+fn apply<T: Number>(operation: Operation, a: &dyn Any, b: &dyn Any) -> Option<Box<AnyValue>> {
+    let op = |x: T, y: T| x.apply(operation, y);
+    let map = |values: &[T], f: &dyn Fn(T) -> T| values.iter().map(|v| f(*v)).collect::<Vec<T>>();
+    let zip = |xs: &[T], ys: &[T]| xs.iter().zip(ys).map(|(x, y)| op(*x, *y)).collect::<Vec<T>>();
+
+    Some(match (operand::<T>(a)?, operand::<T>(b)?) {
+        (Operand::Scalar(x), Operand::Scalar(y)) => Box::new(op(x, y)),
+        (Operand::Vector(xs), Operand::Vector(ys)) if xs.len() == ys.len() => Box::new(zip(xs, ys)),
+        (Operand::Vector(xs), Operand::Scalar(y)) => Box::new(map(xs, &|x| op(x, y))),
+        (Operand::Scalar(x), Operand::Vector(ys)) => Box::new(map(ys, &|y| op(x, y))),
+        (Operand::Matrix(xs), Operand::Matrix(ys)) if operation == Operation::Multiply => Box::new(matmul(xs, ys)?),
+        (Operand::Matrix(xs), Operand::Matrix(ys)) if xs.len() == ys.len() => Box::new(
+            xs.iter()
+                .zip(ys)
+                .map(|(x, y)| (x.len() == y.len()).then(|| zip(x, y)))
+                .collect::<Option<Vec<Vec<T>>>>()?,
+        ),
+        (Operand::Matrix(xs), Operand::Scalar(y)) => {
+            Box::new(xs.iter().map(|row| map(row, &|x| op(x, y))).collect::<Vec<Vec<T>>>())
+        }
+        (Operand::Scalar(x), Operand::Matrix(ys)) => {
+            Box::new(ys.iter().map(|row| map(row, &|y| op(x, y))).collect::<Vec<Vec<T>>>())
+        }
+        _ => return None,
+    })
+}
+
+fn matmul<T: Number>(a: &[Vec<T>], b: &[Vec<T>]) -> Option<Vec<Vec<T>>> {
+    let inner = b.len();
+    let cols = b.first().map_or(0, |row| row.len());
+    if a.iter().any(|row| row.len() != inner) {
+        return None;
+    }
+
+    Some(
+        a.iter()
+            .map(|row| {
+                (0..cols)
+                    .map(|c| {
+                        (0..inner).fold(T::ZERO, |sum, k| {
+                            sum.apply(Operation::Add, row[k].apply(Operation::Multiply, b[k][c]))
+                        })
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+// Column implementations of the scalar rules, shared with `registry`. Integer columns hold i32 values as f64, the
+// integer kernels convert them back so they wrap and truncate like the per-value functions.
+
+fn zip_columns(left: &[f64], right: &[f64], out: &mut [f64], f: impl Fn(f64, f64) -> f64) {
+    out.iter_mut().zip(left.iter().zip(right)).for_each(|(out, (a, b))| *out = f(*a, *b));
+}
+
+pub(crate) fn add_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| a + b);
+}
+
+pub(crate) fn subtract_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| a - b);
+}
+
+pub(crate) fn multiply_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| a * b);
+}
+
+pub(crate) fn divide_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| if b == 0.0 { 1.0 } else { a / b });
+}
+
+pub(crate) fn add_i32_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| (a as i32).apply(Operation::Add, b as i32) as f64);
+}
+
+pub(crate) fn subtract_i32_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| (a as i32).apply(Operation::Subtract, b as i32) as f64);
+}
+
+pub(crate) fn multiply_i32_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| (a as i32).apply(Operation::Multiply, b as i32) as f64);
+}
+
+pub(crate) fn divide_i32_batch(left: &[f64], right: &[f64], out: &mut [f64]) {
+    zip_columns(left, right, out, |a, b| (a as i32).apply(Operation::Divide, b as i32) as f64);
+}
+
+impl NonTerminalGrammar {
+    /// Grammar of the standard rules, see `standard_rules`.
+    pub fn standard(data_type: DataType, shapes: &[Shape]) -> Self {
+        let mut grammar = NonTerminalGrammar::new();
+        for rule in standard_rules(data_type, shapes) {
+            grammar.add_rule(rule);
+        }
+        grammar
+    }
+}
@@ -6,17 +6,24 @@
 //! the outputs/inputs defined in the Non-terminal Grammar.
 
 use crate::{
-    node::{Node, NodeType}, nonterminal::{NonTerminalGrammar, NonTerminalRule}, possibilities_tables::PossibilityTable, types::{
-        DataRow, DataType, Dataset, EvalInput, GenerationMethod, Shape, TypeInfo, Variable,
+    batch::{check_batchable, BatchScratch, ColumnarDataset}, compiled::{CompiledTree, Instruction}, diagnostics::GrammarIssue, evolution::{
+        Checkpoint, EvolutionConfig, FitnessMode, GenerationStats, PopulationSnapshot, RngState, RunConfig, RunResult,
+    }, node::Node, nonterminal::{NonTerminalGrammar, NonTerminalRule}, possibilities_tables::PossibilityTable, uniform::{UniformCounts, UniformSampler}, split::CrossValidation, types::{
+        AnyValue, DataRow, DataType, Dataset, EvalInput, GenerationMethod, Shape, TypeInfo, Variable,
         VariableDefinitions,
-    }, utils::l1_loss_to_reciprocal_fitness, variable::VariableContext
+    }, utils::clone_value
 };
-use rand::Rng;
-use std::{any::Any, rc::Rc};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+#[cfg(feature = "serde")]
+use crate::serialize::{self, Format};
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParseTree {
     pub id: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::float"))]
     pub fitness: f64,
     pub tree: Vec<Node>,
 }
@@ -40,64 +47,284 @@ impl ParseTree {
         rng.random_range(0..self.tree.len())
     }
 
-    /// todo: remove pub or figure out better permissions for testing.
-    pub fn evaluate_fitness(&mut self, dataset: &Dataset, grammar: &NonTerminalGrammar) -> f64 {
-        let mut agg_loss: f64 = 0.0;
+    /// Number of levels in the subtree rooted at idx, a lone terminal has height 1.
+    fn subtree_height(&self, idx: usize) -> usize {
+        let node = &self.tree[idx];
+        let left = node.left_index.map_or(0, |left_idx| self.subtree_height(left_idx));
+        let right = node.right_index.map_or(0, |right_idx| self.subtree_height(right_idx));
+        1 + left.max(right)
+    }
 
-        for eval_input in dataset.iter() {
-            self.evaluate(&eval_input, grammar);
-            // Extract target from EvalInput
-            let target = match eval_input {
-                EvalInput::Data(_, target_rc) => target_rc,
-            };
+    /// Copies the subtree of `source` rooted at idx onto the end of `out`, re-indexing it for its new position.
+    fn copy_subtree(source: &ParseTree, idx: usize, parent_idx: usize, depth: usize, out: &mut Vec<Node>) -> usize {
+        let new_idx = out.len();
+        let mut node = source.tree[idx].clone();
+        node.idx = new_idx;
+        node.parent_index = parent_idx;
+        node.depth = depth;
+        out.push(node);
+
+        if let Some(left_idx) = source.tree[idx].left_index {
+            out[new_idx].left_index = Some(Self::copy_subtree(source, left_idx, new_idx, depth + 1, out));
+        }
+        if let Some(right_idx) = source.tree[idx].right_index {
+            out[new_idx].right_index = Some(Self::copy_subtree(source, right_idx, new_idx, depth + 1, out));
+        }
+
+        new_idx
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn copy_with_replacement(
+        &self,
+        idx: usize,
+        target: usize,
+        donor: &ParseTree,
+        donor_idx: usize,
+        parent_idx: usize,
+        depth: usize,
+        out: &mut Vec<Node>,
+    ) -> usize {
+        if idx == target {
+            return Self::copy_subtree(donor, donor_idx, parent_idx, depth, out);
+        }
+
+        let new_idx = out.len();
+        let mut node = self.tree[idx].clone();
+        node.idx = new_idx;
+        node.parent_index = parent_idx;
+        node.depth = depth;
+        out.push(node);
+
+        if let Some(left_idx) = self.tree[idx].left_index {
+            let left = self.copy_with_replacement(left_idx, target, donor, donor_idx, new_idx, depth + 1, out);
+            out[new_idx].left_index = Some(left);
+        }
+        if let Some(right_idx) = self.tree[idx].right_index {
+            let right = self.copy_with_replacement(right_idx, target, donor, donor_idx, new_idx, depth + 1, out);
+            out[new_idx].right_index = Some(right);
+        }
+
+        new_idx
+    }
+
+    /// Returns a copy of this tree where the subtree at `target` is replaced by the subtree of `donor` at `donor_idx`.
+    /// The caller is responsible for the two subtrees having the same output type.
+    pub fn with_subtree_replaced(&self, target: usize, donor: &ParseTree, donor_idx: usize) -> ParseTree {
+        let mut nodes = Vec::with_capacity(self.tree.len());
+        self.copy_with_replacement(0, target, donor, donor_idx, 0, 0, &mut nodes);
+
+        ParseTree {
+            id: self.id,
+            fitness: 0.0,
+            tree: nodes,
+        }
+    }
+
+    /// Subtree crossover. Picks a random node in `self`, then a node of the same output type in `other` such that
+    /// both offspring stay within max_depth, and swaps the two subtrees. Returns clones of the parents if no such
+    /// pair is found.
+    pub fn crossover(&self, other: &ParseTree, max_depth: usize, rng: &mut impl Rng) -> (ParseTree, ParseTree) {
+        const ATTEMPTS: usize = 8;
+
+        for _ in 0..ATTEMPTS {
+            let idx = self.sample_random_node_idx(rng);
+            let required_type = self.tree[idx]._type.output_type();
+            let height = self.subtree_height(idx);
+            let depth = self.get_node_depth(idx);
+
+            let candidates: Vec<usize> = (0..other.tree.len())
+                .filter(|&other_idx| {
+                    other.tree[other_idx]._type.output_type() == required_type
+                        && depth + other.subtree_height(other_idx) <= max_depth
+                        && other.get_node_depth(other_idx) + height <= max_depth
+                })
+                .collect();
+
+            if candidates.is_empty() {
+                continue;
+            }
+
+            let other_idx = candidates[rng.random_range(0..candidates.len())];
+            return (
+                self.with_subtree_replaced(idx, other, other_idx),
+                other.with_subtree_replaced(other_idx, self, idx),
+            );
+        }
+
+        (self.clone(), other.clone())
+    }
+
+    /// Compiles the tree once and runs the compiled program over every row of the dataset.
+    pub fn evaluate_fitness(&mut self, dataset: &Dataset, grammar: &NonTerminalGrammar, variables: &VariableDefinitions) -> f64 {
+        let fitness = self
+            .compile(grammar, variables)
+            .and_then(|program| program.evaluate_fitness(dataset))
+            .unwrap_or_else(|err| panic!("Cannot evaluate fitness of tree {}: {}", self.id, err));
+
+        self.fitness = fitness;
+        self.fitness
+    }
+
+    /// Fitness over a subset of the dataset's rows, used for mini-batch evaluation.
+    pub fn evaluate_fitness_rows(
+        &mut self,
+        dataset: &Dataset,
+        grammar: &NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        rows: &[usize],
+    ) -> f64 {
+        let fitness = self
+            .compile(grammar, variables)
+            .and_then(|program| program.evaluate_fitness_rows(dataset, rows))
+            .unwrap_or_else(|err| panic!("Cannot evaluate fitness of tree {}: {}", self.id, err));
+
+        self.fitness = fitness;
+        self.fitness
+    }
+
+    /// Column-wise variant of `evaluate_fitness`, see `batch.rs`.
+    pub fn evaluate_fitness_columns<'r>(
+        &mut self,
+        data: &'r ColumnarDataset,
+        grammar: &NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        scratch: &mut BatchScratch<'r>,
+    ) -> f64 {
+        let fitness = self
+            .compile(grammar, variables)
+            .and_then(|program| program.evaluate_fitness_columns(data, scratch))
+            .unwrap_or_else(|err| panic!("Cannot evaluate fitness of tree {}: {}", self.id, err));
+
+        self.fitness = fitness;
+        self.fitness
+    }
+
+    /// Resolves every nonterminal to its grammar rule and every variable to its slot in rows built with
+    /// `variables`, and flattens the tree into a postfix program.
+    /// The compiled program borrows the constants of this tree and the rules of the grammar.
+    pub fn compile<'a>(
+        &'a self,
+        grammar: &'a NonTerminalGrammar,
+        variables: &VariableDefinitions,
+    ) -> Result<CompiledTree<'a>, String> {
+        if self.tree.is_empty() {
+            return Err("Cannot compile an empty tree".to_string());
+        }
 
-            // Get prediction from tree
-            let prediction = match &self.tree[0]._type {
-                NodeType::NonTerminal(_,_,_,TypeInfo {
-                    shape: _,
-                    data_type: DataType::Float,
-                }) => {
-                    let value = self.tree[0].value.downcast_ref::<f64>().unwrap();
-                    *value
+        let mut instructions = Vec::with_capacity(self.tree.len());
+        self.compile_node(0, grammar, variables, &mut instructions)?;
+        Ok(CompiledTree::new(instructions, self.tree[0]._type.output_type()))
+    }
+
+    fn compile_node<'a>(
+        &'a self,
+        idx: usize,
+        grammar: &'a NonTerminalGrammar,
+        variables: &VariableDefinitions,
+        instructions: &mut Vec<Instruction<'a>>,
+    ) -> Result<(), String> {
+        let node = &self.tree[idx];
+        match (node.left_index, node.right_index) {
+            (Some(left_idx), right_idx) => {
+                let rule = self.find_matching_rule_for_node(idx, grammar)?;
+                if right_idx.is_some() != (rule.arity() == 2) {
+                    return Err(format!("Node {}: the number of children does not match {}", idx, rule.signature()));
                 }
-                NodeType::NonTerminal(_,_,_,TypeInfo {
-                    shape: _,
-                    data_type: DataType::Integer,
-                }) => {
-                    let value = self.tree[0].value.downcast_ref::<i32>().unwrap();
-                    *value as f64 // Convert to f64 for consistent math
+                self.compile_node(left_idx, grammar, variables, instructions)?;
+                if let Some(right_idx) = right_idx {
+                    self.compile_node(right_idx, grammar, variables, instructions)?;
                 }
-                _ => {
-                    panic!(
-                        "Cannot evaluate fitness: node at index 0 has invalid type {:?}",
-                        self.tree[0]._type
-                    );
+                instructions.push(Instruction::Apply(rule));
+            }
+            (None, None) => match &node.variable_id {
+                Some(variable_id) => {
+                    let slot = variables
+                        .variables
+                        .iter()
+                        .position(|var| &var.name == variable_id)
+                        .ok_or_else(|| format!("Node {}: variable '{}' is not defined", idx, variable_id))?;
+                    instructions.push(Instruction::Variable { name: variable_id, slot });
                 }
-            };
-
-            // Downcast target and compute loss
-            // This is hardcoded to the 2 fundamental types for now but something more generic is hopefully possible.
-            // this is not a flexible piece and needs to get refactored.
-            let loss = if let Some(target_f64) = target.downcast_ref::<f64>() {
-                (prediction - target_f64).abs() // L1 loss
-            } else if let Some(target_i32) = target.downcast_ref::<i32>() {
-                (prediction - (*target_i32 as f64)).abs() // L1 loss
-            } else {
-                // Handle unsupported target type
-                eprintln!("Unsupported target type");
-                continue;
-            };
+                None => instructions.push(Instruction::Constant(node.value.as_ref())),
+            },
+            _ => return Err("Invalid node configuration: partial children".to_string()),
+        }
+        Ok(())
+    }
 
-            agg_loss += loss;
+    /// Checks that the tree is well formed and well typed: the root produces `required_type`, parent, child and
+    /// depth links agree, every nonterminal matches a rule of the grammar whose inputs are its children's types,
+    /// and every variable is defined with the type of its node. For trees that were loaded or edited by hand.
+    pub fn validate(
+        &self,
+        grammar: &NonTerminalGrammar,
+        variable_definitions: &VariableDefinitions,
+        required_type: TypeInfo,
+    ) -> Result<(), String> {
+        let root = self.tree.first().ok_or("The tree is empty")?;
+        if root._type.output_type() != required_type {
+            return Err(format!("The root produces {}, {} is required", root._type.output_type(), required_type));
         }
 
-        self.fitness = l1_loss_to_reciprocal_fitness(agg_loss);
-        return self.fitness;
+        for (idx, node) in self.tree.iter().enumerate() {
+            if node.idx != idx {
+                return Err(format!("Node {} is stored at index {}", node.idx, idx));
+            }
+            if idx > 0 {
+                let parent = self.tree.get(node.parent_index).filter(|parent| {
+                    parent.left_index == Some(idx) || parent.right_index == Some(idx)
+                });
+                if parent.is_none() {
+                    return Err(format!("Node {}: parent {} does not link to it", idx, node.parent_index));
+                }
+            }
+
+            match (node._type, node.left_index, node.right_index) {
+                (crate::node::NodeType::NonTerminal(left_type, right_type, operation, _), Some(left_idx), right_idx)
+                    if right_idx.is_some() == (operation.arity() == 2) =>
+                {
+                    let right = right_idx.map(|right_idx| (right_idx, right_type));
+                    for (child_idx, expected) in std::iter::once((left_idx, left_type)).chain(right) {
+                        let child = self
+                            .tree
+                            .get(child_idx)
+                            .filter(|child| child_idx > idx && child.parent_index == idx && child.depth == node.depth + 1)
+                            .ok_or_else(|| format!("Node {}: child {} is not linked back to it", idx, child_idx))?;
+                        if child._type.output_type() != expected {
+                            return Err(format!(
+                                "Node {}: child {} produces {}, {} is expected",
+                                idx,
+                                child_idx,
+                                child._type.output_type(),
+                                expected
+                            ));
+                        }
+                    }
+                    self.find_matching_rule_for_node(idx, grammar)
+                        .map_err(|err| format!("Node {}: {}", idx, err))?;
+                }
+                (crate::node::NodeType::Terminal(type_info), None, None) => {
+                    if let Some(name) = &node.variable_id {
+                        let var = variable_definitions
+                            .variables
+                            .iter()
+                            .find(|var| &var.name == name)
+                            .ok_or_else(|| format!("Node {}: unknown variable '{}'", idx, name))?;
+                        if var._type != type_info {
+                            return Err(format!("Node {}: variable '{}' is {}, not {}", idx, name, var._type, type_info));
+                        }
+                    }
+                }
+                _ => return Err(format!("Node {}: children do not match its node type", idx)),
+            }
+        }
+        Ok(())
     }
 
     fn evaluate(&mut self, data: &EvalInput, grammar: &NonTerminalGrammar) {
         match data {
-            EvalInput::Data(vars, _) => {
+            EvalInput::Data(vars, _, _) => {
                 for i in (0..self.tree.len()).rev() {
                     self.evaluate_node_at_index(i, vars, grammar);
                 }
@@ -107,15 +334,15 @@ impl ParseTree {
 
     fn evaluate_node_at_index(&mut self, idx: usize, vars: &DataRow, grammar: &NonTerminalGrammar) {
         match (self.tree[idx].left_index, self.tree[idx].right_index) {
-            // NonTerminal node - evaluate using child values
-            (Some(left_idx), Some(right_idx)) => {
+            // NonTerminal node - evaluate using child values, unary nodes only have a left child
+            (Some(left_idx), right_idx) => {
                 // Find matching rule in grammar
                 let rule = self.find_matching_rule_for_node(idx, grammar).unwrap();
 
                 // Execute the operation with child values
-                let result = rule.execute(
+                let result = rule.execute_children(
                     self.tree[left_idx].value.as_ref(),
-                    self.tree[right_idx].value.as_ref(),
+                    right_idx.map(|right_idx| self.tree[right_idx].value.as_ref() as &dyn std::any::Any),
                 );
                 // Store result in current node
                 self.tree[idx].value = result;
@@ -126,16 +353,12 @@ impl ParseTree {
                     let var_value = vars
                         .values
                         .get(variable_id)
-                        .expect(&format!("Variable '{}' not found in data row", variable_id));
-
-                    // Convert Rc<dyn Any> to Box<dyn Any> by cloning the inner value
-                    if let Some(int_val) = var_value.downcast_ref::<i32>() {
-                        self.tree[idx].value = Box::new(*int_val);
-                    } else if let Some(float_val) = var_value.downcast_ref::<f64>() {
-                        self.tree[idx].value = Box::new(*float_val);
-                    } else {
-                        panic!("Unsupported variable type in evaluation");
-                    }
+                        .unwrap_or_else(|| panic!("Variable '{}' not found in data row", variable_id));
+
+                    // Convert Arc<dyn Any> to Box<dyn Any> by cloning the inner value
+                    let type_info = self.tree[idx]._type.output_type();
+                    self.tree[idx].value = clone_value(var_value.as_ref(), type_info)
+                        .unwrap_or_else(|| panic!("Variable '{}' is not stored as {}", variable_id, type_info));
                 }
                 // If no variable_id, value is already set (constant terminal)
             }
@@ -143,42 +366,174 @@ impl ParseTree {
         }
     }
 
-    fn find_matching_rule_for_node<'a>(
+    pub(crate) fn find_matching_rule_for_node<'a>(
         &self,
         idx: usize,
         grammar: &'a NonTerminalGrammar,
     ) -> Result<&'a NonTerminalRule, String> {
-        let (input1_type, input2_type, operation, output_type) = match self.tree[idx]._type {
-            crate::node::NodeType::NonTerminal(i1, i2, op, o) => (i1, i2, op, o),
-            _ => return Err("Expected NonTerminal node type".to_string()),
-        };
+        let id = self.tree[idx]._type.rule_id().ok_or("Expected NonTerminal node type")?;
 
         grammar
-            .rules
-            .iter()
-            .find(|rule| {
-                rule.operation == operation
-                    && rule.input_one_type == input1_type
-                    && rule.input_two_type == input2_type
-                    && rule.output == output_type
-            })
-            .ok_or_else(|| format!("No matching rule found for operation {:?}", operation))
+            .find_rule(id)
+            .ok_or_else(|| format!("No matching rule found for operation {}", id.operation))
     }
 
-    fn mutate(&mut self,         max_depth: usize,          required_type: TypeInfo,
+    /// Subtree mutation. Replaces a random node with a freshly generated subtree of the same type,
+    /// grown from the node's depth so the tree stays within max_depth. Only nodes whose type the possibility table
+    /// has at their depth are picked, trees built elsewhere, e.g. parsed, may have others. The Uniform method reads
+    /// and fills `uniform_counts`, which has to come from the same grammar and table. Returns whether a node was
+    /// replaced.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mutate(
+        &mut self,
+        max_depth: usize,
         nt_grammar: &NonTerminalGrammar,
         variable_definitions: &VariableDefinitions,
         rng: &mut impl Rng,
         generation_method: GenerationMethod,
-        parent_idx: usize,
         possibilities_table: &PossibilityTable,
-        ) {
-        
-        let depth = self.get_node_depth(self.sample_random_node_idx(rng));
-        self.generate_node_recursive(depth, max_depth, required_type, nt_grammar, variable_definitions, rng, generation_method, parent_idx, possibilities_table);
+        uniform_counts: &mut UniformCounts,
+    ) -> bool {
+        // Every node of a tree generated from the table qualifies, so this draws like `sample_random_node_idx`.
+        let candidates: Vec<usize> = (0..self.tree.len())
+            .filter(|&idx| {
+                possibilities_table.can_produce_type_at_depth(self.get_node_depth(idx), self.tree[idx]._type.output_type())
+            })
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+        let idx = candidates[rng.random_range(0..candidates.len())];
+        let depth = self.get_node_depth(idx);
+        let required_type = self.tree[idx]._type.output_type();
+
+        let mut subtree = ParseTree::empty(self.id);
+        subtree.generate_subtree(
+            depth,
+            max_depth,
+            required_type,
+            nt_grammar,
+            variable_definitions,
+            rng,
+            generation_method,
+            0,
+            possibilities_table,
+            uniform_counts,
+        );
+
+        *self = self.with_subtree_replaced(idx, &subtree, 0);
+        true
     }
 
-    fn generate_random(
+    /// Point mutation. Keeps the tree's shape and changes a single random node: a nonterminal gets another
+    /// operation of a rule with the same input and output types, a terminal is redrawn as another variable or
+    /// constant of its type. Nonterminals without such an alternative rule are never picked. Returns whether a
+    /// node was changed, false for an empty tree or when a redrawn constant kept coming out the same.
+    pub fn point_mutate(
+        &mut self,
+        nt_grammar: &NonTerminalGrammar,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let alternatives = |node: &Node| -> Vec<crate::ops::Operation> {
+            let Some(id) = node._type.rule_id() else {
+                return Vec::new();
+            };
+            let mut operations = Vec::new();
+            for rule in &nt_grammar.rules {
+                let same_signature = rule.input_one_type == id.input_one_type
+                    && rule.input_two_type == id.input_two_type
+                    && rule.output == id.output
+                    && rule.arity() == id.operation.arity();
+                if same_signature && rule.operation != id.operation && !operations.contains(&rule.operation) {
+                    operations.push(rule.operation);
+                }
+            }
+            operations
+        };
+
+        let candidates: Vec<usize> = (0..self.tree.len())
+            .filter(|idx| self.tree[*idx].left_index.is_none() || !alternatives(&self.tree[*idx]).is_empty())
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+        let idx = candidates[rng.random_range(0..candidates.len())];
+
+        match self.tree[idx]._type {
+            crate::node::NodeType::NonTerminal(left_type, right_type, _, output) => {
+                let operations = alternatives(&self.tree[idx]);
+                let operation = operations[rng.random_range(0..operations.len())];
+                self.tree[idx]._type = crate::node::NodeType::NonTerminal(left_type, right_type, operation, output);
+                true
+            }
+            crate::node::NodeType::Terminal(type_info) => {
+                // Same odds as `create_terminal_node`, leaving out the current variable
+                let node = &self.tree[idx];
+                let others: Vec<&Variable> = variable_definitions
+                    .variables
+                    .iter()
+                    .filter(|var| var._type == type_info && Some(&var.name) != node.variable_id.as_ref())
+                    .collect();
+                if !others.is_empty() && rng.random_bool(0.5) {
+                    let chosen = others[rng.random_range(0..others.len())];
+                    self.tree[idx].value = Self::create_placeholder_value(type_info);
+                    self.tree[idx].variable_id = Some(chosen.name.clone());
+                    return true;
+                }
+
+                // A constant replacing a constant is redrawn a few times if it comes out the same, e.g. small integers
+                const ATTEMPTS: usize = 8;
+                for _ in 0..ATTEMPTS {
+                    let value = Self::create_random_value(type_info, rng);
+                    let node = &mut self.tree[idx];
+                    if node.variable_id.is_none() && same_value(node.value.as_ref(), value.as_ref()) {
+                        continue;
+                    }
+                    node.value = value;
+                    node.variable_id = None;
+                    return true;
+                }
+                false
+            }
+        }
+    }
+
+    /// Constant perturbation. Adds noise in place to every constant terminal: Gaussian noise with standard
+    /// deviation `scale` to floats, a uniform step in -k..=k to integers with k = `scale` rounded (at least 1),
+    /// element-wise for vectors and matrices. Integers wrap on overflow. Variables and values of other
+    /// representations are left as they are, and so is every constant for a zero scale.
+    pub fn perturb_constants(&mut self, scale: f64, rng: &mut impl Rng) {
+        if scale == 0.0 {
+            return;
+        }
+        let steps = (scale.round() as i32).max(1);
+        let float = |x: &mut f64, rng: &mut dyn rand::RngCore| *x += scale * gaussian(rng);
+        let integer = |x: &mut i32, rng: &mut dyn rand::RngCore| *x = x.wrapping_add(rng.random_range(-steps..=steps));
+
+        for node in self.tree.iter_mut() {
+            if node.left_index.is_some() || node.variable_id.is_some() {
+                continue;
+            }
+            let value = node.value.as_mut() as &mut dyn std::any::Any;
+            if let Some(x) = value.downcast_mut::<f64>() {
+                float(x, rng);
+            } else if let Some(xs) = value.downcast_mut::<Vec<f64>>() {
+                xs.iter_mut().for_each(|x| float(x, rng));
+            } else if let Some(rows) = value.downcast_mut::<Vec<Vec<f64>>>() {
+                rows.iter_mut().flatten().for_each(|x| float(x, rng));
+            } else if let Some(x) = value.downcast_mut::<i32>() {
+                integer(x, rng);
+            } else if let Some(xs) = value.downcast_mut::<Vec<i32>>() {
+                xs.iter_mut().for_each(|x| integer(x, rng));
+            } else if let Some(rows) = value.downcast_mut::<Vec<Vec<i32>>>() {
+                rows.iter_mut().flatten().for_each(|x| integer(x, rng));
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_random(
         id: usize,
         max_depth: usize,
         required_output_type: TypeInfo,
@@ -186,26 +541,66 @@ impl ParseTree {
         variable_definitions: &VariableDefinitions,
         generation_method: GenerationMethod,
         possibilities_table: &PossibilityTable,
+        rng: &mut impl Rng,
     ) -> Self {
         let mut tree = ParseTree::empty(id);
-        let mut rng = rand::rng();
 
-        // Generate the root node
-        let root_idx = tree.generate_node_recursive(
+        // Generate the root node, counting trees afresh for the Uniform method
+        tree.generate_subtree(
             0, // current depth
             max_depth,
             required_output_type,
             nt_grammar,
             variable_definitions,
-            &mut rng,
+            rng,
             generation_method,
             0, // parent index (root has no parent, will be adjusted)
             possibilities_table,
+            &mut UniformCounts::default(),
         );
 
         tree
     }
 
+    /// `generate_node_recursive`, except that the Uniform method samples the subtree with a `UniformSampler` over
+    /// `uniform_counts` and only grows it when nothing fits within max_size.
+    #[allow(clippy::too_many_arguments)]
+    fn generate_subtree(
+        &mut self,
+        current_depth: usize,
+        max_depth: usize,
+        required_type: TypeInfo,
+        nt_grammar: &NonTerminalGrammar,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+        generation_method: GenerationMethod,
+        parent_idx: usize,
+        possibilities_table: &PossibilityTable,
+        uniform_counts: &mut UniformCounts,
+    ) -> usize {
+        if let GenerationMethod::Uniform { max_size } = generation_method {
+            let mut sampler = UniformSampler::new(nt_grammar, possibilities_table, uniform_counts);
+            let levels = max_depth - current_depth;
+            if let Some(idx) =
+                sampler.sample_into(self, required_type, max_size, levels, current_depth, parent_idx, variable_definitions, rng)
+            {
+                return idx;
+            }
+        }
+        self.generate_node_recursive(
+            current_depth,
+            max_depth,
+            required_type,
+            nt_grammar,
+            variable_definitions,
+            rng,
+            generation_method,
+            parent_idx,
+            possibilities_table,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn generate_node_recursive(
         &mut self,
         current_depth: usize,
@@ -220,47 +615,31 @@ impl ParseTree {
     ) -> usize {
         let current_idx = self.tree.len();
 
-        // Check if the required type is possible at this depth using the PossibilityTable
-        if !possibilities_table.can_produce_type_at_depth(current_depth, required_type) {
-            // If the type is not possible at this depth, we have a constraint violation
-            // This should not happen with a properly constructed possibilities table,
-            // but we'll handle it gracefully by creating a terminal
-
-            // todo, make this panic?
-            return self.create_terminal_node(
-                required_type,
-                variable_definitions,
-                current_depth,
-                rng,
-                current_idx,
-                parent_idx,
-            );
-        }
+        // Every type in the table can be completed within the depth left, and children are only asked for types
+        // the table has at their depth. Anything else is a bug of the table or of the caller.
+        assert!(
+            possibilities_table.can_produce_type_at_depth(current_depth, required_type),
+            "{} cannot be generated at depth {} within max depth {}, see NonTerminalGrammar::diagnose",
+            required_type,
+            current_depth,
+            max_depth
+        );
 
-        // Determine if we should create a terminal or non-terminal
+        // Determine if we should create a terminal or non-terminal, the table guarantees at least one is possible
+        let can_be_nonterminal = current_depth + 1 < max_depth
+            && !possibilities_table
+                .expansions(nt_grammar, current_depth, required_type)
+                .is_empty();
         let should_be_terminal = match generation_method {
-            GenerationMethod::Full => current_depth >= max_depth - 1,
-            GenerationMethod::Grow => {
-                if current_depth >= max_depth - 1 {
-                    true // Must be terminal at max depth
+            GenerationMethod::Full => !can_be_nonterminal,
+            // Uniform trees are sampled by `generate_subtree`, getting here means none fit and they are grown
+            GenerationMethod::Grow | GenerationMethod::Uniform { .. } => {
+                let can_be_terminal = possibilities_table.is_terminal(required_type);
+
+                if can_be_nonterminal && can_be_terminal {
+                    rng.random_bool(0.3) // 30% chance of terminal at non-leaf levels
                 } else {
-                    // Randomly choose between terminal and non-terminal
-                    // But only if both are possible according to the possibilities table
-                    let can_be_nonterminal = !nt_grammar
-                        .get_all_possible_input_types_with_operations(required_type)
-                        .is_empty();
-                    let can_be_terminal = variable_definitions
-                        .variables
-                        .iter()
-                        .any(|var| var._type == required_type);
-
-                    if can_be_nonterminal && can_be_terminal {
-                        rng.random_bool(0.3) // 30% chance of terminal at non-leaf levels
-                    } else if can_be_terminal {
-                        true // Only terminal is possible
-                    } else {
-                        false // Only non-terminal is possible
-                    }
+                    can_be_terminal
                 }
             }
         };
@@ -349,6 +728,7 @@ impl ParseTree {
         current_idx
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_nonterminal_node(
         &mut self,
         current_depth: usize,
@@ -362,44 +742,14 @@ impl ParseTree {
         parent_idx: usize,
         possibilities_table: &PossibilityTable,
     ) -> usize {
-        // Get possible input combinations that produce the required output type
-        let all_possible_inputs =
-            nt_grammar.get_all_possible_input_types_with_operations(required_type);
-
-        if all_possible_inputs.is_empty() {
-            // No non-terminal rules can produce this type, create a terminal instead
-            return self.create_terminal_node(
-                required_type,
-                variable_definitions,
-                current_depth,
-                rng,
-                current_idx,
-                parent_idx,
-            );
-        }
-
-        // Filter input combinations based on what's possible at the next depth
-        let next_depth = current_depth + 1;
-        let valid_inputs: Vec<_> = all_possible_inputs
-            .into_iter()
-            .filter(|(left_type, right_type, _)| {
-                // Both input types must be possible at the next depth
-                possibilities_table.can_produce_type_at_depth(next_depth, *left_type)
-                    && possibilities_table.can_produce_type_at_depth(next_depth, *right_type)
-            })
-            .collect();
-
-        if valid_inputs.is_empty() {
-            // No valid input combinations according to possibilities table, create terminal
-            return self.create_terminal_node(
-                required_type,
-                variable_definitions,
-                current_depth,
-                rng,
-                current_idx,
-                parent_idx,
-            );
-        }
+        // Filter input combinations to those that can be completed below this depth
+        let valid_inputs = possibilities_table.expansions(nt_grammar, current_depth, required_type);
+        assert!(
+            !valid_inputs.is_empty(),
+            "No rule producing {} can be completed below depth {}",
+            required_type,
+            current_depth
+        );
 
         // Choose a random valid input combination
         let (left_type, right_type, operation) =
@@ -438,26 +788,28 @@ impl ParseTree {
             possibilities_table,
         );
 
-        let right_idx = self.generate_node_recursive(
-            current_depth + 1,
-            max_depth,
-            right_type,
-            nt_grammar,
-            variable_definitions,
-            rng,
-            generation_method,
-            current_idx,
-            possibilities_table,
-        );
+        let right_idx = (operation.arity() == 2).then(|| {
+            self.generate_node_recursive(
+                current_depth + 1,
+                max_depth,
+                right_type,
+                nt_grammar,
+                variable_definitions,
+                rng,
+                generation_method,
+                current_idx,
+                possibilities_table,
+            )
+        });
 
         // Update the non-terminal node with child indices
         self.tree[current_idx].left_index = Some(left_idx);
-        self.tree[current_idx].right_index = Some(right_idx);
+        self.tree[current_idx].right_index = right_idx;
 
         current_idx
     }
 
-    fn create_random_value(type_info: TypeInfo, rng: &mut impl Rng) -> Box<dyn Any> {
+    fn create_random_value(type_info: TypeInfo, rng: &mut impl Rng) -> Box<AnyValue> {
         match (type_info.data_type, type_info.shape) {
             (DataType::Integer, Shape::Scalar) => Box::new(rng.random_range(-100..=100i32)),
             (DataType::Float, Shape::Scalar) => Box::new(rng.random_range(-100.0..=100.0f64)),
@@ -490,7 +842,7 @@ impl ParseTree {
         }
     }
 
-    fn create_placeholder_value(type_info: TypeInfo) -> Box<dyn Any> {
+    pub(crate) fn create_placeholder_value(type_info: TypeInfo) -> Box<AnyValue> {
         match (type_info.data_type, type_info.shape) {
             (DataType::Integer, Shape::Scalar) => Box::new(0i32),
             (DataType::Float, Shape::Scalar) => Box::new(0.0f64),
@@ -506,12 +858,39 @@ impl ParseTree {
     }
 }
 
+/// Equality of two values of the built-in representations, false for anything else.
+fn same_value(a: &AnyValue, b: &AnyValue) -> bool {
+    fn equal<T: PartialEq + 'static>(a: &AnyValue, b: &AnyValue) -> Option<bool> {
+        Some(a.downcast_ref::<T>()? == b.downcast_ref::<T>()?)
+    }
+
+    equal::<f64>(a, b)
+        .or_else(|| equal::<i32>(a, b))
+        .or_else(|| equal::<Vec<f64>>(a, b))
+        .or_else(|| equal::<Vec<i32>>(a, b))
+        .or_else(|| equal::<Vec<Vec<f64>>>(a, b))
+        .or_else(|| equal::<Vec<Vec<i32>>>(a, b))
+        .unwrap_or(false)
+}
+
+/// Standard normal sample, Box-Muller.
+fn gaussian(rng: &mut dyn rand::RngCore) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>(); // in (0, 1], keeps the logarithm finite
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
 #[derive(Debug)]
 pub struct TreeOrchestrator {
     nt_grammar: NonTerminalGrammar,
     variable_definitions: VariableDefinitions, // Static variable type definitions
     dataset: Dataset,                          // Training data with inputs and expected outputs
+    columns: Option<ColumnarDataset>,          // Column layout of the dataset, only present in batched mode
+    validation: Option<Dataset>,               // Held-out data, never used for selection
+    fitness_mode: FitnessMode,                 // Which rows each generation is scored on
     possibilities_table: PossibilityTable,
+    /// Tree counts of the Uniform method, for the current grammar and possibility table.
+    uniform_counts: UniformCounts,
     required_output_type: TypeInfo,
     max_trees: usize,
     max_depth: usize,
@@ -519,6 +898,11 @@ pub struct TreeOrchestrator {
     pub trees: Vec<ParseTree>,
     // fast lookup for tree scores.
     tree_scores: Vec<f64>, // Changed to f64 for fitness scores
+    evolution_config: EvolutionConfig,
+    seed: u64,
+    rng: ChaCha8Rng, // every random decision of the run goes through this, seeded from `seed`
+    generation: usize,
+    history: Vec<GenerationStats>,
 }
 
 impl TreeOrchestrator {
@@ -530,20 +914,83 @@ impl TreeOrchestrator {
         max_depth: usize,
         required_output_type: TypeInfo,
     ) -> Self {
+        // Without an explicit seed, draw one so the run can still be replayed from RunResult.
+        let seed: u64 = rand::rng().random();
+
         TreeOrchestrator {
             nt_grammar,
             variable_definitions,
             dataset,
+            columns: None,
+            validation: None,
+            fitness_mode: FitnessMode::Full,
             required_output_type,
             possibilities_table: PossibilityTable::empty(max_depth),
-            max_trees: max_trees,
-            max_depth: max_depth,
-            grow_method: GenerationMethod::Full, // this is currently the only option supported.
+            uniform_counts: UniformCounts::default(),
+            max_trees,
+            max_depth,
+            grow_method: GenerationMethod::Full,
             trees: Vec::with_capacity(max_trees),
             tree_scores: vec!(0.0f64; max_trees),
+            evolution_config: EvolutionConfig::default(),
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            generation: 0,
+            history: Vec::new(),
         }
     }
 
+    /// Seeds the orchestrator's random number generator, making generation and evolution reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// How the initial population and the subtrees of subtree mutation are generated, Full by default.
+    pub fn with_generation_method(mut self, generation_method: GenerationMethod) -> Self {
+        self.grow_method = generation_method;
+        self
+    }
+
+    /// Held-out data the best trees are scored on each generation, to track overfitting.
+    pub fn with_validation_set(mut self, validation: Dataset) -> Self {
+        self.validation = Some(validation);
+        self
+    }
+
+    /// Fails if the mode would score a generation on no rows of the dataset, see `FitnessMode::validate`.
+    pub fn with_fitness_mode(mut self, fitness_mode: FitnessMode) -> Result<Self, String> {
+        fitness_mode.validate(self.dataset.len())?;
+        self.fitness_mode = fitness_mode;
+        Ok(self)
+    }
+
+    pub fn with_evolution_config(mut self, evolution_config: EvolutionConfig) -> Self {
+        self.evolution_config = evolution_config;
+        self
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn get_history(&self) -> &[GenerationStats] {
+        &self.history
+    }
+
+    /// Runs `f` with the orchestrator's own generator.
+    fn with_internal_rng<T>(&mut self, f: impl FnOnce(&mut Self, &mut ChaCha8Rng) -> T) -> T {
+        let mut rng = self.rng.clone();
+        let result = f(self, &mut rng);
+        self.rng = rng;
+        result
+    }
+
     pub fn generate_empty_trees(&mut self) {
         for i in 0..self.max_trees {
             self.trees.push(ParseTree::empty(i));
@@ -551,20 +998,37 @@ impl TreeOrchestrator {
     }
 
     pub fn generate_trees(&mut self) {
+        self.with_internal_rng(|orchestrator, rng| orchestrator.generate_trees_with_rng(rng));
+    }
+
+    /// Same as `generate_trees`, drawing from a user supplied generator instead of the seeded one.
+    pub fn generate_trees_with_rng(&mut self, rng: &mut impl Rng) {
         // Ensure possibilities table is constructed before generation
         if !self.possibilities_table.is_valid_for_generation() {
             self.construct_possibilities_table();
         }
 
+        // The counts are kept in the orchestrator, so the population and later mutations compute them once
+        let mut sampler = UniformSampler::new(&self.nt_grammar, &self.possibilities_table, &mut self.uniform_counts);
         for i in 0..self.max_trees {
+            let mut method = self.grow_method;
+            if let GenerationMethod::Uniform { max_size } = method {
+                if let Some(tree) = sampler.sample_up_to(i, self.required_output_type, max_size, &self.variable_definitions, rng) {
+                    self.trees.push(tree);
+                    continue;
+                }
+                // Nothing fits within max_size, grow the tree instead
+                method = GenerationMethod::Grow;
+            }
             self.trees.push(ParseTree::generate_random(
                 i,
                 self.max_depth,
                 self.required_output_type,
                 &self.nt_grammar,
                 &self.variable_definitions,
-                self.grow_method,
+                method,
                 &self.possibilities_table,
+                rng,
             ));
         }
     }
@@ -591,7 +1055,7 @@ impl TreeOrchestrator {
     }
 
     // Helper method to get expected output for a row
-    pub fn get_expected_output(&self, row_index: usize) -> Result<&Rc<dyn Any>, String> {
+    pub fn get_expected_output(&self, row_index: usize) -> Result<&Arc<AnyValue>, String> {
         if row_index >= self.dataset.targets.len() {
             return Err(format!("Output index {} out of bounds", row_index));
         }
@@ -602,22 +1066,457 @@ impl TreeOrchestrator {
     // now for the time being this is operating under the understanding that runtime variables is a hashmap in memory that gets updated EVERYTIME evaluate wants to get called.
     // I don't know if this should happen in evaluate_trees or elsewhere, but that can be figured out.
     // perhaps Dataset should have a method that allows it to decompose into runtime variables? that seems cleanish.
-    // also keeping in mind that we want this to be super parallel eventually. t
     // these will own their values, I believe
-    pub fn evaluate_trees<'a>(&mut self, data: &'a EvalInput) {
+    pub fn evaluate_trees(&mut self, data: &EvalInput) {
         for tree in &mut self.trees {
             tree.evaluate(data, &self.nt_grammar);
         }
     }
 
+    /// Switches fitness evaluation to the column-wise mode. Fails unless the required output and every rule of the
+    /// grammar are scalar. Rules without a column implementation fall back to one `func` call per row, see `batch`.
+    pub fn enable_batched_evaluation(&mut self) -> Result<(), String> {
+        check_batchable(&self.nt_grammar, self.required_output_type)?;
+        self.columns = Some(self.dataset.to_columns(&self.variable_definitions)?);
+        Ok(())
+    }
+
     /// Evaluates the trees fitness values against the internally stored Dataset.
     /// Stores their fitness value in each ParseTree.
     pub fn evaluate_fitness(&mut self) {
-        for (idx, tree) in self.trees.iter_mut().enumerate() {
-            self.tree_scores[idx] = tree.evaluate_fitness(&self.dataset, &self.nt_grammar);
+        self.evaluate_fitness_rows(None);
+    }
+
+    /// Evaluates the trees on the given rows, or on every row for None.
+    /// With the `parallel` feature the trees are spread across the rayon thread pool. Each score is written back
+    /// to the index of its tree, so the result does not depend on scheduling.
+    #[cfg(feature = "parallel")]
+    fn evaluate_fitness_rows(&mut self, rows: Option<&[usize]>) {
+        use rayon::prelude::*;
+
+        // One score per tree, whatever the population was resized to
+        self.tree_scores.resize(self.trees.len(), 0.0);
+        let grammar = &self.nt_grammar;
+        let variables = &self.variable_definitions;
+        let dataset = &self.dataset;
+        let scores = self.trees.par_iter_mut().zip(self.tree_scores.par_iter_mut());
+        match (&self.columns, rows) {
+            (Some(columns), rows) => {
+                let selected = rows.map(|rows| columns.select(rows));
+                let columns = selected.as_ref().unwrap_or(columns);
+                scores.for_each_init(BatchScratch::new, |scratch, (tree, score)| {
+                    *score = tree.evaluate_fitness_columns(columns, grammar, variables, scratch);
+                })
+            }
+            (None, Some(rows)) => scores.for_each(|(tree, score)| {
+                *score = tree.evaluate_fitness_rows(dataset, grammar, variables, rows);
+            }),
+            (None, None) => scores.for_each(|(tree, score)| {
+                *score = tree.evaluate_fitness(dataset, grammar, variables);
+            }),
+        }
+    }
+
+    /// Evaluates the trees on the given rows, or on every row for None.
+    #[cfg(not(feature = "parallel"))]
+    fn evaluate_fitness_rows(&mut self, rows: Option<&[usize]>) {
+        // One score per tree, whatever the population was resized to
+        self.tree_scores.resize(self.trees.len(), 0.0);
+        let scores = self.trees.iter_mut().zip(self.tree_scores.iter_mut());
+        match (&self.columns, rows) {
+            (Some(columns), rows) => {
+                let selected = rows.map(|rows| columns.select(rows));
+                let columns = selected.as_ref().unwrap_or(columns);
+                let mut scratch = BatchScratch::new();
+                for (tree, score) in scores {
+                    *score = tree.evaluate_fitness_columns(columns, &self.nt_grammar, &self.variable_definitions, &mut scratch);
+                }
+            }
+            (None, Some(rows)) => {
+                for (tree, score) in scores {
+                    *score = tree.evaluate_fitness_rows(&self.dataset, &self.nt_grammar, &self.variable_definitions, rows);
+                }
+            }
+            (None, None) => {
+                for (tree, score) in scores {
+                    *score = tree.evaluate_fitness(&self.dataset, &self.nt_grammar, &self.variable_definitions);
+                }
+            }
         }
     }
 
+    /// Rows the current generation is scored on, according to the fitness mode. None means every row.
+    /// Never an empty set: a mode that would pick no rows, which `with_fitness_mode` rejects, scores every row.
+    fn sample_fitness_rows(&self, rng: &mut impl Rng) -> Option<Vec<usize>> {
+        let len = self.dataset.len();
+        match self.fitness_mode {
+            FitnessMode::Full => None,
+            FitnessMode::MiniBatch { size } if size > 0 && size < len => {
+                let mut rows = rand::seq::index::sample(rng, len, size).into_vec();
+                rows.sort_unstable();
+                Some(rows)
+            }
+            FitnessMode::MiniBatch { .. } => None,
+            // The offset is below the stride, so below len and the first row always exists
+            FitnessMode::Interleaved { stride } if stride > 1 && stride <= len => {
+                Some((self.generation % stride..len).step_by(stride).collect())
+            }
+            FitnessMode::Interleaved { .. } => None,
+        }
+    }
+
+    /// Scores the population on the rows picked by the fitness mode for this generation.
+    fn evaluate_generation_fitness(&mut self, rng: &mut impl Rng) {
+        let rows = self.sample_fitness_rows(rng);
+        self.evaluate_fitness_rows(rows.as_deref());
+    }
+
+    /// Fitness of a tree on the whole training dataset, whatever the fitness mode.
+    /// Does not touch the fitness stored in the tree.
+    pub fn full_fitness(&self, tree_idx: usize) -> f64 {
+        self.trees[tree_idx]
+            .compile(&self.nt_grammar, &self.variable_definitions)
+            .and_then(|program| program.evaluate_fitness(&self.dataset))
+            .unwrap_or_else(|err| panic!("Cannot evaluate fitness of tree {}: {}", tree_idx, err))
+    }
+
+    /// Tournament selection: the fittest of `tournament_size` trees drawn at random.
+    pub fn tournament_select(&self, rng: &mut impl Rng) -> usize {
+        let mut best = rng.random_range(0..self.trees.len());
+        for _ in 1..self.evolution_config.tournament_size {
+            let contender = rng.random_range(0..self.trees.len());
+            if self.tree_scores[contender] > self.tree_scores[best] {
+                best = contender;
+            }
+        }
+        best
+    }
+
+    /// Index of the fittest tree of the last evaluation. Ties go to the lowest index.
+    pub fn best_tree_index(&self) -> Option<usize> {
+        (0..self.trees.len()).reduce(|best, idx| {
+            if self.tree_scores[idx] > self.tree_scores[best] { idx } else { best }
+        })
+    }
+
+    /// Breeds the next generation from the current, evaluated one and evaluates it.
+    /// Elites are copied over, the rest is filled by tournament selection, subtree crossover and subtree mutation,
+    /// which grows its subtrees with the orchestrator's generation method.
+    pub fn evolve_generation(&mut self) {
+        self.with_internal_rng(|orchestrator, rng| orchestrator.evolve_generation_with_rng(rng));
+    }
+
+    /// Same as `evolve_generation`, drawing from a user supplied generator instead of the seeded one.
+    pub fn evolve_generation_with_rng(&mut self, rng: &mut impl Rng) {
+        // Subtree mutation generates from the table, e.g. after `restore_population` on a fresh orchestrator
+        if !self.possibilities_table.is_valid_for_generation() {
+            self.construct_possibilities_table();
+        }
+        let config = self.evolution_config;
+        let mut next_generation = Vec::with_capacity(self.max_trees);
+
+        let mut ranked: Vec<usize> = (0..self.trees.len()).collect();
+        ranked.sort_by(|&a, &b| self.tree_scores[b].total_cmp(&self.tree_scores[a]));
+        for &idx in ranked.iter().take(config.elitism.min(self.max_trees)) {
+            next_generation.push(self.trees[idx].clone());
+        }
+
+        while next_generation.len() < self.max_trees {
+            let first = self.tournament_select(rng);
+            let offspring = if rng.random_bool(config.crossover_rate) {
+                let second = self.tournament_select(rng);
+                let (a, b) = self.trees[first].crossover(&self.trees[second], self.max_depth, rng);
+                vec![a, b]
+            } else {
+                vec![self.trees[first].clone()]
+            };
+
+            for mut child in offspring {
+                if next_generation.len() >= self.max_trees {
+                    break;
+                }
+                if rng.random_bool(config.mutation_rate) {
+                    child.mutate(
+                        self.max_depth,
+                        &self.nt_grammar,
+                        &self.variable_definitions,
+                        rng,
+                        self.grow_method,
+                        &self.possibilities_table,
+                        &mut self.uniform_counts,
+                    );
+                }
+                // Only drawn when enabled, so runs without point mutation keep their random sequence
+                if config.point_mutation_rate > 0.0 && rng.random_bool(config.point_mutation_rate) {
+                    child.point_mutate(&self.nt_grammar, &self.variable_definitions, rng);
+                }
+                if config.constant_mutation_rate > 0.0 && rng.random_bool(config.constant_mutation_rate) {
+                    child.perturb_constants(config.constant_mutation_scale, rng);
+                }
+                next_generation.push(child);
+            }
+        }
+
+        for (idx, tree) in next_generation.iter_mut().enumerate() {
+            tree.id = idx;
+        }
+        self.trees = next_generation;
+        self.generation += 1;
+        self.evaluate_generation_fitness(rng);
+        self.record_generation_stats();
+    }
+
+    /// Fitness of a tree on the validation set. Does not touch the training fitness stored in the tree.
+    pub fn validation_fitness(&self, tree_idx: usize) -> Option<f64> {
+        let validation = self.validation.as_ref()?;
+        let fitness = self.trees[tree_idx]
+            .compile(&self.nt_grammar, &self.variable_definitions)
+            .and_then(|program| program.evaluate_fitness(validation))
+            .unwrap_or_else(|err| panic!("Cannot evaluate validation fitness of tree {}: {}", tree_idx, err));
+        Some(fitness)
+    }
+
+    /// k-fold cross-validation of the whole run: for each fold, a fresh run with this orchestrator's settings
+    /// evolves for `generations` on the other k-1 folds, and its best tree is scored on the held-out fold.
+    /// The mean estimates how well trees found this way generalize, the spread how much that depends on the data.
+    /// Folds and the seed of each fold's run are drawn from the run's seed, so this is reproducible and does not
+    /// advance the evolution's generator.
+    pub fn cross_validate(&self, k: usize, generations: usize) -> Result<CrossValidation, String> {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let mut fold_fitness = Vec::with_capacity(k);
+
+        for (train, held_out) in self.dataset.k_folds(k, &mut rng)? {
+            let config = RunConfig { seed: rng.random(), ..self.run_config() };
+            let mut fold = TreeOrchestrator::from_run_config(config, self.nt_grammar.clone(), train)?;
+            if self.columns.is_some() {
+                fold.enable_batched_evaluation()?;
+            }
+
+            let best_tree = fold.run(generations).best_tree;
+            let fitness = best_tree
+                .compile(&self.nt_grammar, &self.variable_definitions)
+                .and_then(|program| program.evaluate_fitness(&held_out))?;
+            fold_fitness.push(fitness);
+        }
+
+        Ok(CrossValidation::from_folds(fold_fitness))
+    }
+
+    fn record_generation_stats(&mut self) {
+        let best_fitness = self.tree_scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mean_fitness = self.tree_scores.iter().sum::<f64>() / self.tree_scores.len().max(1) as f64;
+        let validation_fitness = self.best_tree_index().and_then(|best_idx| self.validation_fitness(best_idx));
+
+        self.history.push(GenerationStats {
+            generation: self.generation,
+            best_fitness,
+            mean_fitness,
+            validation_fitness,
+        });
+    }
+
+    /// Generates the initial population if needed, then evolves it for the given number of generations.
+    pub fn run(&mut self, generations: usize) -> RunResult {
+        self.start_run();
+        for _ in 0..generations {
+            self.evolve_generation();
+        }
+
+        self.run_result()
+    }
+
+    /// Generates and scores the initial population, unless the run has already started or was resumed.
+    fn start_run(&mut self) {
+        if self.trees.is_empty() {
+            self.generate_trees();
+        }
+        if self.history.is_empty() {
+            self.with_internal_rng(|orchestrator, rng| orchestrator.evaluate_generation_fitness(rng));
+            self.record_generation_stats();
+        }
+    }
+
+    /// Snapshot of the run so far: seed, generation count, statistics and the fittest tree.
+    /// The best tree is picked on the last generation's scores, then re-evaluated on the full dataset so the
+    /// reported fitness is comparable across fitness modes.
+    pub fn run_result(&self) -> RunResult {
+        let best_idx = self.best_tree_index().expect("run_result requires a generated population");
+        let mut best_tree = self.trees[best_idx].clone();
+        best_tree.fitness = self.full_fitness(best_idx);
+
+        RunResult {
+            seed: self.seed,
+            generations: self.generation,
+            best_fitness: best_tree.fitness,
+            best_validation_fitness: self.validation_fitness(best_idx),
+            best_tree,
+            history: self.history.clone(),
+        }
+    }
+
+    /// Copy of the population and the scores of its last evaluation.
+    pub fn population_snapshot(&self) -> PopulationSnapshot {
+        PopulationSnapshot {
+            generation: self.generation,
+            trees: self.trees.clone(),
+            scores: self.tree_scores.clone(),
+        }
+    }
+
+    /// Replaces the population with a saved one. Every tree is validated against this orchestrator's grammar,
+    /// variables and required output type first.
+    pub fn restore_population(&mut self, snapshot: PopulationSnapshot) -> Result<(), String> {
+        if snapshot.trees.len() != snapshot.scores.len() {
+            return Err(format!(
+                "Snapshot has {} trees but {} scores",
+                snapshot.trees.len(),
+                snapshot.scores.len()
+            ));
+        }
+        for tree in &snapshot.trees {
+            tree.validate(&self.nt_grammar, &self.variable_definitions, self.required_output_type)
+                .map_err(|err| format!("Tree {}: {}", tree.id, err))?;
+        }
+
+        self.max_trees = snapshot.trees.len();
+        self.trees = snapshot.trees;
+        self.tree_scores = snapshot.scores;
+        self.generation = snapshot.generation;
+        Ok(())
+    }
+
+    pub fn run_config(&self) -> RunConfig {
+        RunConfig {
+            seed: self.seed,
+            max_trees: self.max_trees,
+            max_depth: self.max_depth,
+            required_output_type: self.required_output_type,
+            generation_method: self.grow_method,
+            variable_definitions: self.variable_definitions.clone(),
+            evolution_config: self.evolution_config,
+            fitness_mode: self.fitness_mode,
+        }
+    }
+
+    /// Orchestrator with the settings of a saved `RunConfig`, for the given grammar and data.
+    /// Fails if the config's fitness mode does not fit the dataset.
+    pub fn from_run_config(config: RunConfig, nt_grammar: NonTerminalGrammar, dataset: Dataset) -> Result<Self, String> {
+        let mut orchestrator = TreeOrchestrator::new(
+            nt_grammar,
+            config.variable_definitions,
+            dataset,
+            config.max_trees,
+            config.max_depth,
+            config.required_output_type,
+        )
+        .with_seed(config.seed)
+        .with_evolution_config(config.evolution_config)
+        .with_fitness_mode(config.fitness_mode)?;
+        orchestrator.grow_method = config.generation_method;
+        Ok(orchestrator)
+    }
+
+    /// Full state of the run, see `Checkpoint`. Validation data and batched evaluation are not part of it and
+    /// have to be set up again on the resumed orchestrator.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            config: self.run_config(),
+            population: self.population_snapshot(),
+            history: self.history.clone(),
+            rng: RngState {
+                seed: self.rng.get_seed(),
+                stream: self.rng.get_stream(),
+                word_pos: self.rng.get_word_pos(),
+            },
+            rules: self.nt_grammar.signatures(),
+            dataset_rows: self.dataset.len(),
+        }
+    }
+
+    /// Rebuilds an orchestrator from a checkpoint. The grammar has to have the same rules, in the same order, as
+    /// the one the checkpoint was taken with: rule choices are drawn by position, so any difference would
+    /// change the trajectory. Continuing with `run` then gives the same result as the uninterrupted run.
+    pub fn resume(checkpoint: Checkpoint, nt_grammar: NonTerminalGrammar, dataset: Dataset) -> Result<Self, String> {
+        let signatures = nt_grammar.signatures();
+        if signatures != checkpoint.rules {
+            let missing: Vec<&String> = checkpoint.rules.iter().filter(|rule| !signatures.contains(rule)).collect();
+            let added: Vec<&String> = signatures.iter().filter(|rule| !checkpoint.rules.contains(rule)).collect();
+            return Err(format!(
+                "The grammar does not match the checkpoint. Missing rules: {:?}, unknown rules: {:?}{}",
+                missing,
+                added,
+                if missing.is_empty() && added.is_empty() { " (the rules are in a different order)" } else { "" }
+            ));
+        }
+        if dataset.len() != checkpoint.dataset_rows {
+            return Err(format!(
+                "The checkpoint was taken on {} rows, the dataset has {}",
+                checkpoint.dataset_rows,
+                dataset.len()
+            ));
+        }
+
+        let mut orchestrator = TreeOrchestrator::from_run_config(checkpoint.config, nt_grammar, dataset)?;
+        orchestrator.restore_population(checkpoint.population)?;
+        orchestrator.history = checkpoint.history;
+
+        let mut rng = ChaCha8Rng::from_seed(checkpoint.rng.seed);
+        rng.set_stream(checkpoint.rng.stream);
+        rng.set_word_pos(checkpoint.rng.word_pos);
+        orchestrator.rng = rng;
+
+        // Mutation grows subtrees from the table, it is normally built along with the initial population.
+        orchestrator.construct_possibilities_table();
+        Ok(orchestrator)
+    }
+
+    /// Writes a checkpoint to `path`. The file is replaced atomically, so an interrupted write leaves the
+    /// previous checkpoint intact.
+    #[cfg(feature = "serde")]
+    pub fn save_checkpoint(&self, path: impl AsRef<std::path::Path>, format: Format) -> Result<(), String> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        serialize::save(&self.checkpoint(), &partial, format)?;
+        std::fs::rename(&partial, path).map_err(|err| format!("Cannot write '{}': {}", path.display(), err))
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn resume_from_file(
+        path: impl AsRef<std::path::Path>,
+        format: Format,
+        nt_grammar: NonTerminalGrammar,
+        dataset: Dataset,
+    ) -> Result<Self, String> {
+        Self::resume(serialize::load(path, format)?, nt_grammar, dataset)
+    }
+
+    /// `run`, writing a checkpoint to `path` every `every` generations and after the last one.
+    #[cfg(feature = "serde")]
+    pub fn run_with_checkpoints(
+        &mut self,
+        generations: usize,
+        every: usize,
+        path: impl AsRef<std::path::Path>,
+        format: Format,
+    ) -> Result<RunResult, String> {
+        let path = path.as_ref();
+        if every == 0 {
+            return Err("Checkpoint interval must be at least 1".to_string());
+        }
+
+        self.start_run();
+        for done in 1..=generations {
+            self.evolve_generation();
+            if done % every == 0 || done == generations {
+                self.save_checkpoint(path, format)?;
+            }
+        }
+
+        Ok(self.run_result())
+    }
+
     pub fn construct_possibilities_table(&mut self) {
         self.possibilities_table = PossibilityTable::new(
             &self.nt_grammar,
@@ -625,9 +1524,69 @@ impl TreeOrchestrator {
             self.required_output_type,
             self.max_depth,
         );
+        self.uniform_counts.clear();
     }
 
     pub fn get_possibilities_table(&self) -> &PossibilityTable {
         &self.possibilities_table
     }
+
+    /// Problems of the grammar for this orchestrator's variables, output type and max depth, see
+    /// `NonTerminalGrammar::diagnose`.
+    pub fn grammar_issues(&self) -> Vec<GrammarIssue> {
+        self.nt_grammar
+            .diagnose(&self.variable_definitions, self.required_output_type, self.max_depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orchestrator(rows: usize) -> TreeOrchestrator {
+        let float = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+        let variables = VariableDefinitions::new(vec![Variable { name: "x".to_string(), _type: float }]);
+        let features = (0..rows).map(|i| DataRow::new(&variables, vec![Box::new(i as f64)]).unwrap()).collect();
+        let targets = (0..rows).map(|i| Box::new(i as f64) as Box<AnyValue>).collect();
+        let dataset = Dataset::new(features, targets).unwrap();
+        let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+        TreeOrchestrator::new(grammar, variables, dataset, 4, 3, float).with_seed(1)
+    }
+
+    #[test]
+    fn fitness_modes_that_pick_no_rows_are_rejected() {
+        assert!(orchestrator(5).with_fitness_mode(FitnessMode::MiniBatch { size: 0 }).is_err());
+        assert!(orchestrator(5).with_fitness_mode(FitnessMode::Interleaved { stride: 0 }).is_err());
+        assert!(orchestrator(5).with_fitness_mode(FitnessMode::Interleaved { stride: 6 }).is_err());
+        assert!(orchestrator(5).with_fitness_mode(FitnessMode::Interleaved { stride: 5 }).is_ok());
+        assert!(orchestrator(5).with_fitness_mode(FitnessMode::MiniBatch { size: 9 }).is_ok());
+    }
+
+    #[test]
+    fn sampled_fitness_rows_are_never_empty() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let mut interleaved = orchestrator(5).with_fitness_mode(FitnessMode::Interleaved { stride: 5 }).unwrap();
+        for generation in 0..10 {
+            interleaved.generation = generation;
+            assert_eq!(interleaved.sample_fitness_rows(&mut rng), Some(vec![generation % 5]));
+        }
+
+        // Modes set without validation still score on every row instead of none.
+        let mut unchecked = orchestrator(5);
+        for fitness_mode in [FitnessMode::MiniBatch { size: 0 }, FitnessMode::Interleaved { stride: 0 }, FitnessMode::Interleaved { stride: 7 }] {
+            unchecked.fitness_mode = fitness_mode;
+            for generation in 0..7 {
+                unchecked.generation = generation;
+                assert_eq!(unchecked.sample_fitness_rows(&mut rng), None);
+            }
+        }
+
+        let mini_batch = orchestrator(5).with_fitness_mode(FitnessMode::MiniBatch { size: 2 }).unwrap();
+        let rows = mini_batch.sample_fitness_rows(&mut rng).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0] < rows[1] && rows[1] < 5);
+        let full_batch = orchestrator(5).with_fitness_mode(FitnessMode::MiniBatch { size: 5 }).unwrap();
+        assert_eq!(full_batch.sample_fitness_rows(&mut rng), None);
+    }
 }
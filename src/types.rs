@@ -2,6 +2,7 @@
 /// 
 /// TODO: The developer needs a way to specify a subset of these for their genetic program. 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     Integer,
     Float,
@@ -9,6 +10,7 @@ pub enum DataType {
 
 /// The shape that a terminal can take. 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     Scalar,
     Vector(usize),
@@ -16,13 +18,69 @@ pub enum Shape {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeInfo {
     pub shape: Shape,
     pub data_type: DataType
 }
 
+/// Renders as `Float/Scalar`, `Integer/Vector(3)` or `Float/Matrix(2,3)`.
+impl std::fmt::Display for TypeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}/", self.data_type)?;
+        match self.shape {
+            Shape::Scalar => write!(f, "Scalar"),
+            Shape::Vector(size) => write!(f, "Vector({})", size),
+            Shape::Matrix(rows, cols) => write!(f, "Matrix({},{})", rows, cols),
+        }
+    }
+}
+
+/// Reads the `Display` form back.
+impl std::str::FromStr for TypeInfo {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (data_type, shape) = text
+            .split_once('/')
+            .ok_or_else(|| format!("Expected a type like 'Float/Scalar', got '{}'", text))?;
+
+        let data_type = match data_type.trim() {
+            "Integer" => DataType::Integer,
+            "Float" => DataType::Float,
+            other => return Err(format!("Unknown data type '{}'", other)),
+        };
+
+        let shape = shape.trim();
+        let dimensions = |inner: &str| -> Result<Vec<usize>, String> {
+            inner
+                .split(',')
+                .map(|dim| dim.trim().parse::<usize>().map_err(|_| format!("Invalid dimension in '{}'", shape)))
+                .collect()
+        };
+        let shape = if shape == "Scalar" {
+            Shape::Scalar
+        } else if let Some(inner) = shape.strip_prefix("Vector(").and_then(|rest| rest.strip_suffix(')')) {
+            match dimensions(inner)?[..] {
+                [size] => Shape::Vector(size),
+                _ => return Err(format!("A vector has one dimension, got '{}'", shape)),
+            }
+        } else if let Some(inner) = shape.strip_prefix("Matrix(").and_then(|rest| rest.strip_suffix(')')) {
+            match dimensions(inner)?[..] {
+                [rows, cols] => Shape::Matrix(rows, cols),
+                _ => return Err(format!("A matrix has two dimensions, got '{}'", shape)),
+            }
+        } else {
+            return Err(format!("Unknown shape '{}'", shape));
+        };
+
+        Ok(TypeInfo { shape, data_type })
+    }
+}
+
 // Struct that will be public facing for developers to define their own variables according to their datasets.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variable {
     pub name: String,
     pub _type: TypeInfo
@@ -30,10 +88,15 @@ pub struct Variable {
 
 use std::collections::HashMap;
 use std::any::Any;
-use std::rc::Rc;
+use std::sync::Arc;
+
+/// Type erased value held by terminals, data rows and targets.
+/// Send + Sync so trees and datasets can be shared across threads.
+pub type AnyValue = dyn Any + Send + Sync;
 
 // Variable definitions with explicit ordering and validation
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableDefinitions {
     pub variables: Vec<Variable>,
 }
@@ -69,20 +132,26 @@ impl VariableDefinitions {
 /// In GPSR, there are two generation methods outlined.
 /// Grow - Terminals and Nonterminals can appear at any depth - randomly chosen during construction. Leaves are always Terminals.
 /// Full - The entire tree is filled up until max_depth - 1 with NonTerminals. The leaves are then all populated with Terminals. 
+/// Uniform - A size up to max_size is drawn uniformly, then a tree uniformly among all trees of that size, see `uniform`.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GenerationMethod {
     Full,
     Grow,
+    Uniform { max_size: usize },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DataRow {
-    pub values: HashMap<String, Rc<dyn Any>>,
+    pub values: HashMap<String, Arc<AnyValue>>,
+    /// The same values in the order of the `VariableDefinitions` the row was built with, compiled trees read them
+    /// by position.
+    pub slots: Vec<Arc<AnyValue>>,
 }
 
 impl DataRow {
     // Constructor that enforces variable definitions (positional)
-    pub fn new(variable_defs: &VariableDefinitions, mut values: Vec<Box<dyn Any>>) -> Result<Self, String> {
+    pub fn new(variable_defs: &VariableDefinitions, mut values: Vec<Box<AnyValue>>) -> Result<Self, String> {
         if values.len() != variable_defs.variables.len() {
             return Err(format!(
                 "Expected {} values, got {}", 
@@ -92,38 +161,45 @@ impl DataRow {
         }
         
         let mut row_values = HashMap::new();
-        for (_, var) in variable_defs.variables.iter().enumerate() {
-            row_values.insert(var.name.clone(), Rc::from(values.remove(0)));
+        let mut slots = Vec::with_capacity(values.len());
+        for var in variable_defs.variables.iter() {
+            let value: Arc<AnyValue> = Arc::from(values.remove(0));
+            row_values.insert(var.name.clone(), value.clone());
+            slots.push(value);
         }
         
-        Ok(DataRow { values: row_values })
+        Ok(DataRow { values: row_values, slots })
     }
     
     // Alternative constructor with explicit key-value pairs (with validation)
-    pub fn from_map(variable_defs: &VariableDefinitions, values: HashMap<String, Box<dyn Any>>) -> Result<Self, String> {
-        let rc_values: HashMap<String, Rc<dyn Any>> = values
+    pub fn from_map(variable_defs: &VariableDefinitions, values: HashMap<String, Box<AnyValue>>) -> Result<Self, String> {
+        let arc_values: HashMap<String, Arc<AnyValue>> = values
             .into_iter()
-            .map(|(k, v)| (k, Rc::from(v)))
+            .map(|(k, v)| (k, Arc::from(v)))
             .collect();
-        let row = DataRow { values: rc_values };
+        let mut row = DataRow { values: arc_values, slots: Vec::new() };
         variable_defs.validate_data_row(&row)?;
+        row.slots = variable_defs.variables.iter().map(|var| row.values[&var.name].clone()).collect();
         Ok(row)
     }
 }
+/// row, target, weight of the row in the loss
 #[derive(Debug)]
 pub enum EvalInput<'a> {
-    Data(&'a DataRow, &'a Rc<dyn Any>)
+    Data(&'a DataRow, &'a Arc<AnyValue>, f64)
 }
 
 // Dataset containing input rows and expected outputs
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Dataset {
     pub features: Vec<DataRow>,
-    pub targets: Vec<Rc<dyn Any>>,
+    pub targets: Vec<Arc<AnyValue>>,
+    /// Optional per-row weights of each row's loss in the weighted mean loss. None weighs every row 1.0.
+    pub weights: Option<Vec<f64>>,
 }
 
 impl Dataset {
-    pub fn new(features: Vec<DataRow>, targets: Vec<Box<dyn Any>>) -> Result<Self, String> {
+    pub fn new(features: Vec<DataRow>, targets: Vec<Box<AnyValue>>) -> Result<Self, String> {
         if features.len() != targets.len() {
             return Err(format!(
                 "Number of features ({}) must match number of targets ({})",
@@ -132,19 +208,59 @@ impl Dataset {
             ));
         }
         
-        let rc_targets: Vec<Rc<dyn Any>> = targets.into_iter().map(Rc::from).collect();
-        Ok(Dataset { features, targets: rc_targets })
+        let arc_targets: Vec<Arc<AnyValue>> = targets.into_iter().map(Arc::from).collect();
+        Ok(Dataset { features, targets: arc_targets, weights: None })
     }
 
-    pub fn sample_row(&self, index: usize) -> EvalInput {
-        EvalInput::Data(&self.features[index], &self.targets[index])
+    /// Attach per-row weights, e.g. to counter class imbalance or emphasize recent samples. Only their ratios
+    /// matter, fitness is computed from the weighted mean loss. At least one row has to weigh something.
+    pub fn with_weights(mut self, weights: Vec<f64>) -> Result<Self, String> {
+        if weights.len() != self.features.len() {
+            return Err(format!(
+                "Number of weights ({}) must match number of rows ({})",
+                weights.len(),
+                self.features.len()
+            ));
+        }
+        if let Some((row, weight)) = weights.iter().enumerate().find(|(_, w)| !w.is_finite() || **w < 0.0) {
+            return Err(format!("Row {}: weight {} must be finite and non-negative", row, weight));
+        }
+        if !weights.is_empty() && weights.iter().all(|weight| *weight == 0.0) {
+            return Err("Every weight is zero, at least one row has to count".to_string());
+        }
+
+        self.weights = Some(weights);
+        Ok(self)
+    }
+
+    pub fn weight(&self, index: usize) -> f64 {
+        self.weights.as_ref().map_or(1.0, |weights| weights[index])
+    }
+
+    pub fn sample_row(&self, index: usize) -> EvalInput<'_> {
+        EvalInput::Data(&self.features[index], &self.targets[index], self.weight(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// New dataset made of the given rows, in the given order. Values are shared, not copied.
+    pub fn subset(&self, indices: &[usize]) -> Dataset {
+        Dataset {
+            features: indices.iter().map(|&idx| self.features[idx].clone()).collect(),
+            targets: indices.iter().map(|&idx| self.targets[idx].clone()).collect(),
+            weights: self.weights.as_ref().map(|weights| indices.iter().map(|&idx| weights[idx]).collect()),
+        }
     }
 
     /// Returns an iterator to a Vec<EvalInput>
     pub fn iter(&self) -> impl Iterator<Item = EvalInput<'_>> + '_ {
-        self.features.iter()
-            .zip(self.targets.iter())
-            .map(|(feature, target)| EvalInput::Data(feature, target))
+        (0..self.features.len()).map(move |idx| self.sample_row(idx))
     }
 
 
@@ -0,0 +1,213 @@
+//! Uniform random sampling of trees by size, the `GenerationMethod::Uniform` initialization.
+//!
+//! Full and Grow favour some shapes over others, e.g. Grow makes many tiny trees. Here every type-legal tree of a
+//! given node count, within max depth, is equally likely: rules and the sizes of the subtrees are drawn weighted by
+//! the number of trees they lead to, counted bottom-up from the grammar. A tree's size is drawn uniformly among
+//! the sizes up to the maximum that have any tree, as in PTC2.
+//!
+//! Trees differ by shape, operation and leaf types. Leaves are then filled like in Full and Grow, with a variable
+//! of the type or a random constant. Counts are floats since they grow exponentially with size, only their ratios
+//! matter. Past about 1e308 trees they overflow to infinity, and sizes that large are not sampled.
+
+use crate::{
+    enumerate::distinct_expansions,
+    nonterminal::NonTerminalGrammar,
+    node::{Node, NodeType},
+    possibilities_tables::PossibilityTable,
+    tree_builder::ParseTree,
+    types::{TypeInfo, VariableDefinitions},
+};
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Number of trees of a type with a number of nodes within a number of levels, kept across samplers so e.g.
+/// mutations do not recount. Only valid for the grammar and table it was filled from, clear it when they change.
+#[derive(Debug, Clone, Default)]
+pub struct UniformCounts(HashMap<(TypeInfo, usize, usize), f64>);
+
+impl UniformCounts {
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+pub struct UniformSampler<'a> {
+    grammar: &'a NonTerminalGrammar,
+    table: &'a PossibilityTable,
+    counts: &'a mut UniformCounts,
+}
+
+impl<'a> UniformSampler<'a> {
+    /// Leaf types are taken from the table, see `PossibilityTable::is_terminal`, which has to be built from this
+    /// grammar. Counts are read from and added to `counts`.
+    pub fn new(grammar: &'a NonTerminalGrammar, table: &'a PossibilityTable, counts: &'a mut UniformCounts) -> Self {
+        UniformSampler {
+            grammar,
+            table,
+            counts,
+        }
+    }
+
+    /// Number of trees of `type_info` with exactly `size` nodes and at most `levels` levels, infinite if it does not
+    /// fit in an f64.
+    pub fn count(&mut self, type_info: TypeInfo, size: usize, levels: usize) -> f64 {
+        if size == 0 || levels == 0 {
+            return 0.0;
+        }
+        if size == 1 {
+            return if self.table.is_terminal(type_info) { 1.0 } else { 0.0 };
+        }
+        if let Some(count) = self.counts.0.get(&(type_info, size, levels)) {
+            return *count;
+        }
+
+        let mut count = 0.0;
+        for (left_type, right_type, operation) in distinct_expansions(self.grammar, type_info) {
+            if operation.arity() == 1 {
+                count += self.count(left_type, size - 1, levels - 1);
+            } else {
+                for left_size in 1..size - 1 {
+                    count += self.pair_count(left_type, left_size, right_type, size - 1 - left_size, levels - 1);
+                }
+            }
+        }
+
+        self.counts.0.insert((type_info, size, levels), count);
+        count
+    }
+
+    /// Number of pairs of subtrees, zero rather than NaN when one side has none and the other overflowed.
+    fn pair_count(&mut self, left_type: TypeInfo, left_size: usize, right_type: TypeInfo, right_size: usize, levels: usize) -> f64 {
+        let left = self.count(left_type, left_size, levels);
+        if left == 0.0 {
+            return 0.0;
+        }
+        let right = self.count(right_type, right_size, levels);
+        if right == 0.0 {
+            return 0.0;
+        }
+        left * right
+    }
+
+    /// A tree of `target_type` with exactly `size` nodes within the table's max depth, None if there is none or
+    /// there are too many to count.
+    pub fn sample(
+        &mut self,
+        id: usize,
+        target_type: TypeInfo,
+        size: usize,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+    ) -> Option<ParseTree> {
+        let count = self.count(target_type, size, self.table.get_max_depth());
+        if count == 0.0 || count.is_infinite() {
+            return None;
+        }
+        let mut tree = ParseTree::empty(id);
+        self.build(&mut tree, target_type, size, self.table.get_max_depth(), 0, 0, variable_definitions, rng);
+        Some(tree)
+    }
+
+    /// A tree of `target_type` within the table's max depth, its size drawn uniformly among those up to `max_size`
+    /// that have a tree. None if there is none or the trees of some size are too many to count.
+    pub fn sample_up_to(
+        &mut self,
+        id: usize,
+        target_type: TypeInfo,
+        max_size: usize,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+    ) -> Option<ParseTree> {
+        let mut tree = ParseTree::empty(id);
+        let levels = self.table.get_max_depth();
+        self.sample_into(&mut tree, target_type, max_size, levels, 0, 0, variable_definitions, rng)?;
+        Some(tree)
+    }
+
+    /// Appends a subtree of `type_info` rooted at `depth`, with a size drawn uniformly among those up to
+    /// `max_size` that fit in `levels`. Returns the index of its root, None if no such subtree exists or the
+    /// subtrees of some size are too many to count, since sizes could then not be drawn uniformly.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn sample_into(
+        &mut self,
+        tree: &mut ParseTree,
+        type_info: TypeInfo,
+        max_size: usize,
+        levels: usize,
+        depth: usize,
+        parent_idx: usize,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+    ) -> Option<usize> {
+        let sizes: Vec<usize> = (1..=max_size)
+            .filter(|size| self.count(type_info, *size, levels) > 0.0)
+            .collect();
+        if sizes.is_empty() || sizes.iter().any(|size| self.count(type_info, *size, levels).is_infinite()) {
+            return None;
+        }
+        let size = sizes[rng.random_range(0..sizes.len())];
+        Some(self.build(tree, type_info, size, levels, depth, parent_idx, variable_definitions, rng))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        &mut self,
+        tree: &mut ParseTree,
+        type_info: TypeInfo,
+        size: usize,
+        levels: usize,
+        depth: usize,
+        parent_idx: usize,
+        variable_definitions: &VariableDefinitions,
+        rng: &mut impl Rng,
+    ) -> usize {
+        let idx = tree.tree.len();
+        if size == 1 {
+            return tree.create_terminal_node(type_info, variable_definitions, depth, rng, idx, parent_idx);
+        }
+
+        // Every (rule, left size) choice, weighted by the number of trees it leads to
+        let mut choices = Vec::new();
+        for (left_type, right_type, operation) in distinct_expansions(self.grammar, type_info) {
+            if operation.arity() == 1 {
+                let weight = self.count(left_type, size - 1, levels - 1);
+                choices.push(((left_type, right_type, operation), size - 1, weight));
+            } else {
+                for left_size in 1..size - 1 {
+                    let weight = self.pair_count(left_type, left_size, right_type, size - 1 - left_size, levels - 1);
+                    choices.push(((left_type, right_type, operation), left_size, weight));
+                }
+            }
+        }
+
+        let total: f64 = choices.iter().map(|(_, _, weight)| weight).sum();
+        let mut target = rng.random::<f64>() * total;
+        let last = choices.iter().rposition(|(_, _, weight)| *weight > 0.0).expect("size with at least one tree");
+        let chosen = choices
+            .iter()
+            .position(|(_, _, weight)| {
+                target -= weight;
+                *weight > 0.0 && target < 0.0
+            })
+            .unwrap_or(last);
+        let ((left_type, right_type, operation), left_size, _) = choices[chosen];
+
+        tree.tree.push(Node {
+            idx,
+            _type: NodeType::NonTerminal(left_type, right_type, operation, type_info),
+            value: ParseTree::create_placeholder_value(type_info),
+            variable_id: None,
+            left_index: Some(idx + 1),
+            right_index: None,
+            parent_index: parent_idx,
+            depth,
+        });
+        self.build(tree, left_type, left_size, levels - 1, depth + 1, idx, variable_definitions, rng);
+        if operation.arity() == 2 {
+            tree.tree[idx].right_index = Some(tree.tree.len());
+            let right_size = size - 1 - left_size;
+            self.build(tree, right_type, right_size, levels - 1, depth + 1, idx, variable_definitions, rng);
+        }
+        idx
+    }
+}
@@ -1,5 +1,73 @@
-/// Utils for converting loss to fitness value. Can be user defined in future, write with dependency inversion in mind.
+//! Utils for converting loss to fitness value. Can be user defined in future, write with dependency inversion in mind.
+
+use std::any::Any;
+
+use crate::types::{AnyValue, DataType, Shape, TypeInfo};
 
 pub fn l1_loss_to_reciprocal_fitness(loss: f64) -> f64 {
-    return 1.0 / (1.0 + loss)
-}
\ No newline at end of file
+    1.0 / (1.0 + loss)
+}
+
+/// Weighted mean of row losses from their weighted sum, so fitness does not depend on how many rows, or how much
+/// weight, it was measured on. Rows that all weigh nothing say nothing about the tree, they count as no loss.
+pub fn weighted_mean_loss(weighted_loss: f64, total_weight: f64) -> f64 {
+    if total_weight > 0.0 { weighted_loss / total_weight } else { 0.0 }
+}
+
+/// Reads a scalar prediction out of a node value as f64, based on the data type the node declares.
+/// Integers are converted to f64 for consistent math.
+pub fn scalar_as_f64(value: &dyn Any, data_type: DataType) -> Option<f64> {
+    match data_type {
+        DataType::Float => value.downcast_ref::<f64>().copied(),
+        DataType::Integer => value.downcast_ref::<i32>().map(|v| *v as f64),
+    }
+}
+
+/// Integer held in an f64 column back as i32: the fraction is truncated and what does not fit wraps around, like
+/// the wrapping integer rules. Column implementations of integer rules may compute in f64, this keeps their output
+/// an i32 whichever way it is evaluated.
+pub fn wrapping_i32(value: f64) -> i32 {
+    value as i64 as i32
+}
+
+/// L1 loss between a prediction and a dataset target.
+/// This is hardcoded to the 2 fundamental types for now, returns None for unsupported target types.
+pub fn l1_loss(prediction: f64, target: &dyn Any) -> Option<f64> {
+    if let Some(target_f64) = target.downcast_ref::<f64>() {
+        Some((prediction - target_f64).abs())
+    } else {
+        target
+            .downcast_ref::<i32>()
+            .map(|target_i32| (prediction - (*target_i32 as f64)).abs())
+    }
+}
+
+/// Clones a type erased value of one of the built-in representations (i32/f64 scalars, vectors and matrices).
+/// Returns None when the value is not stored in the representation its TypeInfo describes.
+pub fn clone_value(value: &AnyValue, type_info: TypeInfo) -> Option<Box<AnyValue>> {
+    match (type_info.data_type, type_info.shape) {
+        (DataType::Integer, Shape::Scalar) => value.downcast_ref::<i32>().map(|v| Box::new(*v) as Box<AnyValue>),
+        (DataType::Float, Shape::Scalar) => value.downcast_ref::<f64>().map(|v| Box::new(*v) as Box<AnyValue>),
+        (DataType::Integer, Shape::Vector(_)) => value.downcast_ref::<Vec<i32>>().map(|v| Box::new(v.clone()) as Box<AnyValue>),
+        (DataType::Float, Shape::Vector(_)) => value.downcast_ref::<Vec<f64>>().map(|v| Box::new(v.clone()) as Box<AnyValue>),
+        (DataType::Integer, Shape::Matrix(_, _)) => {
+            value.downcast_ref::<Vec<Vec<i32>>>().map(|v| Box::new(v.clone()) as Box<AnyValue>)
+        }
+        (DataType::Float, Shape::Matrix(_, _)) => {
+            value.downcast_ref::<Vec<Vec<f64>>>().map(|v| Box::new(v.clone()) as Box<AnyValue>)
+        }
+    }
+}
+
+/// Elements of a value of one of the built-in representations as f64, row-major for matrices.
+pub fn flatten_as_f64(value: &dyn Any, type_info: TypeInfo) -> Option<Vec<f64>> {
+    match (type_info.data_type, type_info.shape) {
+        (_, Shape::Scalar) => scalar_as_f64(value, type_info.data_type).map(|v| vec![v]),
+        (DataType::Integer, Shape::Vector(_)) => value.downcast_ref::<Vec<i32>>().map(|v| v.iter().map(|e| *e as f64).collect()),
+        (DataType::Float, Shape::Vector(_)) => value.downcast_ref::<Vec<f64>>().cloned(),
+        (DataType::Integer, Shape::Matrix(_, _)) => value
+            .downcast_ref::<Vec<Vec<i32>>>()
+            .map(|m| m.iter().flatten().map(|e| *e as f64).collect()),
+        (DataType::Float, Shape::Matrix(_, _)) => value.downcast_ref::<Vec<Vec<f64>>>().map(|m| m.concat()),
+    }
+}
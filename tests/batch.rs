@@ -0,0 +1,91 @@
+use stsr::{
+    batch::BatchScratch,
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    tree_builder::{ParseTree, TreeOrchestrator},
+    types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+fn scalar(data_type: DataType) -> TypeInfo {
+    TypeInfo { shape: Shape::Scalar, data_type }
+}
+
+fn variables(data_type: DataType) -> VariableDefinitions {
+    VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: scalar(data_type) },
+        Variable { name: "y".to_string(), _type: scalar(data_type) },
+    ])
+}
+
+/// Rows large enough for integer products to overflow, and with zero divisors.
+fn dataset(data_type: DataType) -> Dataset {
+    let variables = variables(data_type);
+    let value = |v: i64| -> Box<AnyValue> {
+        match data_type {
+            DataType::Integer => Box::new(v as i32),
+            DataType::Float => Box::new(v as f64 / 7.0),
+        }
+    };
+    let features = (0..40i64)
+        .map(|i| DataRow::new(&variables, vec![value((i - 20) * 9973), value(i % 5 - 2)]).unwrap())
+        .collect();
+    let targets = (0..40i64).map(|i| value(i * i - 300)).collect();
+    Dataset::new(features, targets).unwrap()
+}
+
+/// Fitness of every tree of a population, evaluated row by row and column-wise.
+fn fitness_both_ways(data_type: DataType) -> (Vec<f64>, Vec<f64>) {
+    let grammar = NonTerminalGrammar::standard(data_type, &[Shape::Scalar]);
+    let mut orchestrator =
+        TreeOrchestrator::new(grammar, variables(data_type), dataset(data_type), 60, 5, scalar(data_type)).with_seed(4);
+    orchestrator.generate_trees();
+
+    orchestrator.evaluate_fitness();
+    let row_wise = orchestrator.trees.iter().map(|tree| tree.fitness).collect();
+    orchestrator.enable_batched_evaluation().unwrap();
+    orchestrator.evaluate_fitness();
+    let batched = orchestrator.trees.iter().map(|tree| tree.fitness).collect();
+    (row_wise, batched)
+}
+
+#[test]
+fn batched_fitness_matches_row_wise_fitness() {
+    for data_type in [DataType::Integer, DataType::Float] {
+        let (row_wise, batched) = fitness_both_ways(data_type);
+        assert_eq!(batched, row_wise, "{:?}", data_type);
+    }
+}
+
+const HALF: Operation = Operation::custom("Half", 1);
+
+fn half(value: &dyn std::any::Any, _: &dyn std::any::Any) -> Box<AnyValue> {
+    Box::new(value.downcast_ref::<i32>().unwrap() / 2)
+}
+
+/// Halves in f64 and keeps the fraction.
+fn half_batch(left: &[f64], _: &[f64], out: &mut [f64]) {
+    out.iter_mut().zip(left).for_each(|(out, value)| *out = value * 0.5);
+}
+
+#[test]
+fn integer_column_implementations_are_truncated_to_i32() {
+    let integer = scalar(DataType::Integer);
+    let mut grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    grammar.add_rule(NonTerminalRule::unary(integer, HALF, integer, half).with_batch(half_batch));
+    let variables = variables(DataType::Integer);
+    let dataset = dataset(DataType::Integer);
+    let tree = ParseTree::from_sexpr("(Half x)", &grammar, &variables, integer).unwrap();
+
+    let program = tree.compile(&grammar, &variables).unwrap();
+    let mut stack = program.new_stack();
+    let columns = dataset.to_columns(&variables).unwrap();
+    let outputs = program.evaluate_columns(&columns, &mut BatchScratch::new()).unwrap();
+    for (row, output) in dataset.features.iter().zip(outputs) {
+        let x = *row.values["x"].downcast_ref::<i32>().unwrap();
+        assert_eq!(output, (x / 2) as f64, "x = {}", x);
+        assert_eq!(program.evaluate_row(row, &mut stack).unwrap().as_f64(), Some(output));
+    }
+
+    let batched = program.evaluate_fitness_columns(&columns, &mut BatchScratch::new()).unwrap();
+    assert_eq!(batched, program.evaluate_fitness(&dataset).unwrap());
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "serde")]
+
+use stsr::{
+    evolution::{EvolutionConfig, RunResult},
+    nonterminal::NonTerminalGrammar,
+    serialize::Format,
+    tree_builder::TreeOrchestrator,
+    types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn grammar() -> NonTerminalGrammar {
+    NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar])
+}
+
+fn variables() -> VariableDefinitions {
+    VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: FLOAT },
+        Variable { name: "y".to_string(), _type: FLOAT },
+    ])
+}
+
+fn dataset(variables: &VariableDefinitions) -> Dataset {
+    let features = (0..20)
+        .map(|i| DataRow::new(variables, vec![Box::new(i as f64 / 4.0), Box::new((i % 5) as f64)]).unwrap())
+        .collect();
+    let targets = (0..20).map(|i| Box::new(i as f64 / 4.0 * (i % 5) as f64 + 1.0) as Box<AnyValue>).collect();
+    Dataset::new(features, targets).unwrap()
+}
+
+fn orchestrator() -> TreeOrchestrator {
+    let variables = variables();
+    let dataset = dataset(&variables);
+    let config = EvolutionConfig { point_mutation_rate: 0.2, ..EvolutionConfig::default() };
+
+    TreeOrchestrator::new(grammar(), variables, dataset, 40, 4, FLOAT)
+        .with_seed(11)
+        .with_evolution_config(config)
+}
+
+fn assert_same_run(a: &RunResult, b: &RunResult) {
+    assert_eq!(a.generations, b.generations);
+    assert_eq!(a.best_tree.to_string(), b.best_tree.to_string());
+    assert_eq!(a.best_fitness.to_bits(), b.best_fitness.to_bits());
+    assert_eq!(a.history, b.history);
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("stsr-checkpoint-test-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn a_resumed_run_continues_like_the_uninterrupted_one() {
+    let uninterrupted = orchestrator().run(6);
+
+    let mut first_half = orchestrator();
+    first_half.run(3);
+    let mut resumed =
+        TreeOrchestrator::resume(first_half.checkpoint(), grammar(), first_half.get_dataset().clone()).unwrap();
+
+    assert_same_run(&resumed.run(3), &uninterrupted);
+}
+
+#[test]
+fn checkpoint_files_round_trip() {
+    for (format, name) in [(Format::Json, "json"), (Format::Binary, "bin")] {
+        let path = temp_path(name);
+        let mut original = orchestrator();
+        original.run(2);
+        original.save_checkpoint(&path, format).unwrap();
+
+        let restored = TreeOrchestrator::resume_from_file(&path, format, grammar(), original.get_dataset().clone());
+        let _ = std::fs::remove_file(&path);
+        let restored = restored.unwrap();
+
+        let trees = |orchestrator: &TreeOrchestrator| -> Vec<String> {
+            orchestrator.trees.iter().map(|tree| tree.to_string()).collect()
+        };
+        assert_eq!(trees(&restored), trees(&original));
+        assert_eq!(restored.run_config().seed, original.run_config().seed);
+        assert_same_run(&restored.run_result(), &original.run_result());
+    }
+}
+
+#[test]
+fn run_with_checkpoints_matches_run_and_leaves_the_last_checkpoint() {
+    let path = temp_path("periodic");
+    let uninterrupted = orchestrator().run(5);
+
+    let result = orchestrator().run_with_checkpoints(5, 2, &path, Format::Json).unwrap();
+    assert_same_run(&result, &uninterrupted);
+
+    let resumed = TreeOrchestrator::resume_from_file(&path, Format::Json, grammar(), dataset(&variables()));
+    let _ = std::fs::remove_file(&path);
+    let mut resumed = resumed.unwrap();
+    assert_same_run(&resumed.run(0), &uninterrupted);
+}
+
+#[test]
+fn resuming_with_a_different_grammar_or_dataset_fails() {
+    let mut original = orchestrator();
+    original.run(1);
+
+    let integer_grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    assert!(TreeOrchestrator::resume(original.checkpoint(), integer_grammar, original.get_dataset().clone()).is_err());
+
+    let fewer_rows = original.get_dataset().subset(&[0, 1, 2]);
+    assert!(TreeOrchestrator::resume(original.checkpoint(), grammar(), fewer_rows).is_err());
+}
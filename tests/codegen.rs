@@ -0,0 +1,159 @@
+use std::process::Command;
+use stsr::{
+    codegen::CodeGenerator,
+    nonterminal::NonTerminalGrammar,
+    tree_builder::ParseTree,
+    types::{AnyValue, DataRow, DataType, Dataset, EvalInput, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+// Names that are not identifiers, keywords of a target or clash with the generated code.
+const NAMES: [&str; 4] = ["x-1", "class", "fn", "n0"];
+
+struct Case {
+    grammar: NonTerminalGrammar,
+    variables: VariableDefinitions,
+    tree: ParseTree,
+    dataset: Dataset,
+}
+
+fn case(data_type: DataType, sexpr: &str, rows: Vec<[f64; 4]>) -> Case {
+    let type_info = TypeInfo { shape: Shape::Scalar, data_type };
+    let grammar = NonTerminalGrammar::standard(data_type, &[Shape::Scalar]);
+    let variables = VariableDefinitions::new(
+        NAMES.iter().map(|name| Variable { name: name.to_string(), _type: type_info }).collect(),
+    );
+    let tree = ParseTree::from_sexpr(sexpr, &grammar, &variables, type_info).unwrap();
+
+    let value = |v: f64| -> Box<AnyValue> {
+        match data_type {
+            DataType::Integer => Box::new(v as i32),
+            DataType::Float => Box::new(v),
+        }
+    };
+    let features = rows
+        .iter()
+        .map(|row| DataRow::new(&variables, row.iter().map(|v| value(*v)).collect()).unwrap())
+        .collect();
+    let targets = rows.iter().map(|_| value(0.0)).collect();
+    let dataset = Dataset::new(features, targets).unwrap();
+
+    Case { grammar, variables, tree, dataset }
+}
+
+fn integer_case() -> Case {
+    let min = i32::MIN as f64;
+    let max = i32::MAX as f64;
+    case(
+        DataType::Integer,
+        "(Add (Multiply x-1 class) (Subtract (Divide fn n0) x-1))",
+        vec![[3.0, 4.0, 10.0, 3.0], [max, 2.0, min, -1.0], [min, -1.0, 7.0, 0.0], [-5.0, max, max, max]],
+    )
+}
+
+fn float_case() -> Case {
+    case(
+        DataType::Float,
+        "(Divide (Add x-1 class) (Subtract fn n0))",
+        vec![[1.5, 2.0, 3.0, 0.5], [-1.0, 4.0, 2.0, 2.0], [0.25, 0.125, -7.0, 1e300]],
+    )
+}
+
+fn expected_outputs(case: &Case) -> Vec<f64> {
+    let program = case.tree.compile(&case.grammar, &case.variables).unwrap();
+    let mut stack = program.new_stack();
+    case.dataset
+        .iter()
+        .map(|EvalInput::Data(row, _, _)| program.evaluate_row(row, &mut stack).unwrap().as_f64().unwrap())
+        .collect()
+}
+
+fn available(program: &str, args: &[&str]) -> bool {
+    Command::new(program).args(args).output().is_ok_and(|output| output.status.success())
+}
+
+/// Compiles the Rust output with overflow checks into a program printing the model on every row.
+fn run_rust(case: &Case, data_type: DataType) -> Vec<f64> {
+    let generator = CodeGenerator::new(&case.grammar, &case.variables);
+    let mut source = generator.rust(&case.tree).unwrap();
+    source.push_str("\nfn main() {\n");
+    for row in &case.dataset.features {
+        let args: Vec<String> = NAMES
+            .iter()
+            .map(|name| match data_type {
+                DataType::Integer => format!("{}i32", row.values[*name].downcast_ref::<i32>().unwrap()),
+                DataType::Float => format!("{:?}f64", row.values[*name].downcast_ref::<f64>().unwrap()),
+            })
+            .collect();
+        source.push_str(&format!("    println!(\"{{:?}}\", model({}));\n", args.join(", ")));
+    }
+    source.push_str("}\n");
+
+    let dir = std::env::temp_dir().join(format!("stsr-codegen-test-{}-{:?}", std::process::id(), data_type));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("model.rs");
+    let binary = dir.join("model");
+    std::fs::write(&path, &source).unwrap();
+    let compiled = Command::new("rustc")
+        .args(["--edition", "2021", "-C", "overflow-checks=on", "-o"])
+        .arg(&binary)
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(compiled.status.success(), "{}\n{}", source, String::from_utf8_lossy(&compiled.stderr));
+    let output = Command::new(&binary).output().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout).unwrap().lines().map(|line| line.parse().unwrap()).collect()
+}
+
+#[test]
+fn variable_names_become_identifiers() {
+    let case = integer_case();
+    let generator = CodeGenerator::new(&case.grammar, &case.variables);
+
+    let rust = generator.rust(&case.tree).unwrap();
+    assert!(rust.contains("x_1: i32, class: i32, fn_: i32, n0_: i32"), "{}", rust);
+    let python = generator.python(&case.tree).unwrap();
+    assert!(python.contains("(x_1, class_, fn, n0_)"), "{}", python);
+    let c = generator.c(&case.tree).unwrap();
+    assert!(c.contains("int32_t x_1, int32_t class, int32_t fn, int32_t n0_"), "{}", c);
+}
+
+#[test]
+fn rust_output_matches_evaluation() {
+    if !available("rustc", &["--version"]) {
+        eprintln!("skipped: rustc is not available");
+        return;
+    }
+
+    for (case, data_type) in [(integer_case(), DataType::Integer), (float_case(), DataType::Float)] {
+        assert_eq!(run_rust(&case, data_type), expected_outputs(&case));
+    }
+}
+
+#[test]
+fn c_output_matches_evaluation() {
+    if !available("cc", &["--version"]) {
+        eprintln!("skipped: cc is not available");
+        return;
+    }
+
+    for case in [integer_case(), float_case()] {
+        let generator = CodeGenerator::new(&case.grammar, &case.variables);
+        generator.verify_c(&case.tree, &case.dataset, "cc").unwrap();
+    }
+}
+
+#[test]
+fn python_output_matches_evaluation() {
+    if !available("python3", &["-c", "import numpy"]) {
+        eprintln!("skipped: python3 with numpy is not available");
+        return;
+    }
+
+    for case in [integer_case(), float_case()] {
+        let generator = CodeGenerator::new(&case.grammar, &case.variables);
+        generator.verify_python(&case.tree, &case.dataset, "python3").unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    tree_builder::{ParseTree, TreeOrchestrator},
+    types::{AnyValue, DataRow, DataType, Dataset, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions},
+    utils::l1_loss_to_reciprocal_fitness,
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn grammar() -> NonTerminalGrammar {
+    let shapes = [Shape::Scalar, Shape::Vector(3), Shape::Matrix(3, 3)];
+    NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes)
+}
+
+fn variables() -> VariableDefinitions {
+    VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: FLOAT },
+        Variable { name: "v".to_string(), _type: TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float } },
+        Variable { name: "m".to_string(), _type: TypeInfo { shape: Shape::Matrix(3, 3), data_type: DataType::Float } },
+    ])
+}
+
+fn dataset(targets: Vec<Box<AnyValue>>) -> Dataset {
+    let variables = variables();
+    let features = (0..targets.len())
+        .map(|i| {
+            let x = i as f64 / 4.0;
+            let v = vec![x, 1.0 - x, (i % 3) as f64];
+            let m = vec![vec![1.0, x, 0.0], vec![-x, 2.0, 0.5], vec![0.0, (i % 2) as f64, x * x]];
+            DataRow::new(&variables, vec![Box::new(x), Box::new(v), Box::new(m)]).unwrap()
+        })
+        .collect();
+    Dataset::new(features, targets).unwrap()
+}
+
+/// Fitness from the node by node tree walk, one row at a time.
+fn walked_fitness(orchestrator: &mut TreeOrchestrator) -> Vec<f64> {
+    let mut losses = vec![0.0; orchestrator.trees.len()];
+    let rows = orchestrator.get_dataset().len();
+    for idx in 0..rows {
+        let dataset = orchestrator.get_dataset().clone();
+        let row = dataset.sample_row(idx);
+        orchestrator.evaluate_trees(&row);
+        let target = dataset.targets[idx].downcast_ref::<f64>().unwrap();
+        for (loss, tree) in losses.iter_mut().zip(&orchestrator.trees) {
+            *loss += (tree.tree[0].value.downcast_ref::<f64>().unwrap() - target).abs();
+        }
+    }
+    losses.into_iter().map(|loss| l1_loss_to_reciprocal_fitness(loss / rows as f64)).collect()
+}
+
+#[test]
+fn compiled_fitness_matches_the_tree_walk() {
+    let targets = (0..30).map(|i| Box::new((i as f64 / 4.0).cos() * 2.0) as Box<AnyValue>).collect();
+    let mut orchestrator = TreeOrchestrator::new(grammar(), variables(), dataset(targets), 60, 5, FLOAT)
+        .with_seed(3)
+        .with_generation_method(GenerationMethod::Grow);
+    orchestrator.generate_trees();
+
+    let (grammar, variables) = (grammar(), variables());
+    let dataset = orchestrator.get_dataset().clone();
+    let compiled: Vec<f64> = orchestrator
+        .trees
+        .iter()
+        .map(|tree| tree.compile(&grammar, &variables).unwrap().evaluate_fitness(&dataset).unwrap())
+        .collect();
+    let walked = walked_fitness(&mut orchestrator);
+
+    // Vector and matrix variables are reached by some of the trees
+    let texts: Vec<String> = orchestrator.trees.iter().map(|tree| tree.to_string()).collect();
+    assert!(texts.iter().any(|text| text.contains(" v") || text.contains("(v")));
+    assert!(texts.iter().any(|text| text.contains(" m") || text.contains("(m")));
+    for ((compiled, walked), text) in compiled.iter().zip(&walked).zip(&texts) {
+        assert!(compiled == walked || compiled.is_nan() && walked.is_nan(), "{}: {} != {}", text, compiled, walked);
+    }
+}
+
+#[test]
+fn non_scalar_targets_are_an_error() {
+    let targets = (0..4).map(|i| Box::new(vec![i as f64; 3]) as Box<AnyValue>).collect();
+    let dataset = dataset(targets);
+    let (grammar, variables) = (grammar(), variables());
+    let tree = ParseTree::from_sexpr("(Dot v v)", &grammar, &variables, FLOAT).unwrap();
+
+    let err = tree.compile(&grammar, &variables).unwrap().evaluate_fitness(&dataset).unwrap_err();
+    assert_eq!(err, "Cannot evaluate fitness: targets must be Integer or Float scalars");
+}
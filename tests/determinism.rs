@@ -0,0 +1,73 @@
+use stsr::{
+    evolution::{EvolutionConfig, FitnessMode, RunResult},
+    nonterminal::NonTerminalGrammar,
+    tree_builder::TreeOrchestrator,
+    types::{AnyValue, DataRow, DataType, Dataset, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn orchestrator(seed: u64, generation_method: GenerationMethod) -> TreeOrchestrator {
+    let variables = VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: FLOAT },
+        Variable { name: "y".to_string(), _type: FLOAT },
+    ]);
+    let features = (0..30)
+        .map(|i| DataRow::new(&variables, vec![Box::new(i as f64 / 3.0), Box::new((i % 7) as f64)]).unwrap())
+        .collect();
+    let targets = (0..30).map(|i| Box::new((i as f64 / 3.0).powi(2) - (i % 7) as f64) as Box<AnyValue>).collect();
+    let dataset = Dataset::new(features, targets).unwrap();
+    let config = EvolutionConfig {
+        point_mutation_rate: 0.2,
+        constant_mutation_rate: 0.2,
+        ..EvolutionConfig::default()
+    };
+
+    TreeOrchestrator::new(NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]), variables, dataset, 50, 5, FLOAT)
+        .with_seed(seed)
+        .with_generation_method(generation_method)
+        .with_evolution_config(config)
+        .with_fitness_mode(FitnessMode::MiniBatch { size: 10 })
+        .unwrap()
+}
+
+fn run(seed: u64, generation_method: GenerationMethod) -> (RunResult, Vec<String>) {
+    let mut orchestrator = orchestrator(seed, generation_method);
+    let result = orchestrator.run(8);
+    let population = orchestrator.trees.iter().map(|tree| tree.to_string()).collect();
+    (result, population)
+}
+
+#[test]
+fn same_seed_gives_the_same_run() {
+    for generation_method in [GenerationMethod::Full, GenerationMethod::Grow, GenerationMethod::Uniform { max_size: 15 }] {
+        let (first, first_population) = run(42, generation_method);
+        let (second, second_population) = run(42, generation_method);
+
+        assert_eq!(first.seed, 42);
+        assert_eq!(first.best_tree.to_string(), second.best_tree.to_string());
+        assert_eq!(first.best_fitness.to_bits(), second.best_fitness.to_bits());
+        assert_eq!(first.history, second.history);
+        assert_eq!(first_population, second_population);
+    }
+}
+
+#[test]
+fn different_seeds_give_different_runs() {
+    let (first, first_population) = run(1, GenerationMethod::Grow);
+    let (second, second_population) = run(2, GenerationMethod::Grow);
+    assert!(first_population != second_population || first.history != second.history);
+}
+
+#[test]
+fn a_run_can_be_replayed_from_its_config() {
+    let mut original = orchestrator(7, GenerationMethod::Grow);
+    let result = original.run(5);
+
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let mut replay = TreeOrchestrator::from_run_config(original.run_config(), grammar, original.get_dataset().clone()).unwrap();
+    let replayed = replay.run(5);
+
+    assert_eq!(result.best_tree.to_string(), replayed.best_tree.to_string());
+    assert_eq!(result.history, replayed.history);
+}
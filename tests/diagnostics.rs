@@ -0,0 +1,86 @@
+use stsr::{
+    diagnostics::GrammarIssue,
+    linalg,
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    standard,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const VECTOR: TypeInfo = TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float };
+const INTEGER: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Integer };
+
+fn variables(types: &[(&str, TypeInfo)]) -> VariableDefinitions {
+    VariableDefinitions::new(types.iter().map(|(name, type_info)| Variable { name: name.to_string(), _type: *type_info }).collect())
+}
+
+fn add() -> NonTerminalRule {
+    NonTerminalRule::new(FLOAT, FLOAT, Operation::Add, FLOAT, standard::add)
+}
+
+fn norm() -> NonTerminalRule {
+    NonTerminalRule::unary(VECTOR, Operation::Norm, FLOAT, linalg::norm)
+}
+
+fn grammar(rules: Vec<NonTerminalRule>) -> NonTerminalGrammar {
+    let mut grammar = NonTerminalGrammar::new();
+    for rule in rules {
+        grammar.add_rule(rule);
+    }
+    grammar
+}
+
+#[test]
+fn a_complete_grammar_has_no_issues() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    assert!(grammar.diagnose(&variables(&[("x", FLOAT)]), FLOAT, 3).is_empty());
+}
+
+#[test]
+fn types_no_variable_ends_only_get_constants() {
+    let shapes = [Shape::Scalar, Shape::Vector(3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let issues = grammar.diagnose(&variables(&[("v", VECTOR)]), FLOAT, 4);
+
+    assert_eq!(issues, vec![GrammarIssue::NoProducer(INTEGER)]);
+    assert_eq!(
+        issues[0].to_string(),
+        "No variable can end Integer/Scalar, directly or through rules, so its nodes are always random constants"
+    );
+}
+
+#[test]
+fn rules_whose_inputs_cannot_be_completed_are_unused() {
+    // A float below the root needs a Norm and a vector under it, three levels in all.
+    let grammar = grammar(vec![add(), norm()]);
+    let issues = grammar.diagnose(&variables(&[("v", VECTOR)]), FLOAT, 2);
+
+    assert_eq!(issues, vec![GrammarIssue::UnusedRule(add().signature())]);
+    assert!(grammar.diagnose(&variables(&[("v", VECTOR)]), FLOAT, 3).is_empty());
+}
+
+#[test]
+fn types_beyond_the_maximum_depth_are_unreachable() {
+    let grammar = grammar(vec![add(), norm()]);
+    let issues = grammar.diagnose(&variables(&[("x", FLOAT), ("v", VECTOR), ("n", INTEGER)]), FLOAT, 1);
+
+    assert_eq!(
+        issues,
+        vec![
+            GrammarIssue::UnusedRule(add().signature()),
+            GrammarIssue::UnusedRule(norm().signature()),
+            GrammarIssue::UnreachableType { type_info: VECTOR, min_depth: Some(1) },
+            GrammarIssue::UnreachableType { type_info: INTEGER, min_depth: None },
+        ]
+    );
+    assert_eq!(issues[3].to_string(), "Integer/Scalar is never reachable from the target type");
+}
+
+#[test]
+fn duplicate_rules_are_reported_once() {
+    let grammar = grammar(vec![add(), add(), add()]);
+    let issues = grammar.diagnose(&variables(&[("x", FLOAT)]), FLOAT, 3);
+
+    assert_eq!(issues, vec![GrammarIssue::DuplicateRule(add().signature())]);
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn variables(names: &[&str], type_info: TypeInfo) -> VariableDefinitions {
+    VariableDefinitions::new(names.iter().map(|name| Variable { name: name.to_string(), _type: type_info }).collect())
+}
+
+/// Enumerates every tree, checking each is valid and different from the others, and returns how many there were.
+fn enumerate_all(grammar: &NonTerminalGrammar, variables: &VariableDefinitions, max_depth: usize) -> u128 {
+    let mut seen = HashSet::new();
+    for tree in grammar.enumerate_trees(variables, FLOAT, max_depth) {
+        tree.validate(grammar, variables, FLOAT).unwrap();
+        assert!(tree.tree.iter().all(|node| node.depth < max_depth), "{} is too deep", tree);
+        assert!(seen.insert(tree.to_string()), "{} is enumerated twice", tree);
+    }
+    seen.len() as u128
+}
+
+#[test]
+fn counts_match_the_enumerated_trees() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = variables(&["x", "y"], FLOAT);
+
+    // A leaf, or one of 4 operations over two smaller trees.
+    for (max_depth, expected) in [(1, 2), (2, 2 + 4 * 2 * 2), (3, 2 + 4 * 18 * 18)] {
+        assert_eq!(grammar.count_trees(&variables, FLOAT, max_depth), expected);
+        assert_eq!(enumerate_all(&grammar, &variables, max_depth), expected);
+    }
+}
+
+#[test]
+fn counts_match_with_constant_leaves_and_shape_changes() {
+    let shapes = [Shape::Scalar, Shape::Vector(2)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let variables = variables(&["v", "w"], TypeInfo { shape: Shape::Vector(2), data_type: DataType::Float });
+
+    for max_depth in 1..=3 {
+        assert_eq!(enumerate_all(&grammar, &variables, max_depth), grammar.count_trees(&variables, FLOAT, max_depth));
+    }
+    assert_eq!(grammar.count_trees(&variables, FLOAT, 1), 0);
+}
+
+#[test]
+fn trees_can_be_reached_by_index() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = variables(&["x", "y"], FLOAT);
+    let all: Vec<String> = grammar.enumerate_trees(&variables, FLOAT, 3).map(|tree| tree.to_string()).collect();
+
+    let mut enumerator = grammar.enumerate_trees(&variables, FLOAT, 3);
+    assert_eq!(enumerator.total(), all.len() as u128);
+    assert_eq!(enumerator.size_hint(), (all.len(), Some(all.len())));
+    for index in [0, 1, 17, 500, all.len() - 1] {
+        assert_eq!(enumerator.tree(index as u128).unwrap().to_string(), all[index]);
+    }
+    assert!(enumerator.tree(all.len() as u128).is_none());
+
+    assert_eq!(enumerator.nth(500).unwrap().to_string(), all[500]);
+    assert_eq!(enumerator.next().unwrap().to_string(), all[501]);
+    assert_eq!(enumerator.count(), all.len() - 502);
+}
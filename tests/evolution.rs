@@ -0,0 +1,119 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use stsr::{
+    evolution::EvolutionConfig,
+    nonterminal::NonTerminalGrammar,
+    tree_builder::TreeOrchestrator,
+    types::{AnyValue, DataRow, DataType, Dataset, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn orchestrator(max_trees: usize, config: EvolutionConfig) -> TreeOrchestrator {
+    let variables = VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: FLOAT },
+        Variable { name: "y".to_string(), _type: FLOAT },
+    ]);
+    let features = (0..20)
+        .map(|i| DataRow::new(&variables, vec![Box::new(i as f64 / 2.0), Box::new((i % 3) as f64)]).unwrap())
+        .collect();
+    let targets = (0..20).map(|i| Box::new(i as f64 / 2.0 * (i % 3) as f64 - 1.0) as Box<AnyValue>).collect();
+    let dataset = Dataset::new(features, targets).unwrap();
+
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    TreeOrchestrator::new(grammar, variables, dataset, max_trees, 4, FLOAT)
+        .with_seed(5)
+        .with_generation_method(GenerationMethod::Grow)
+        .with_evolution_config(config)
+}
+
+fn population(orchestrator: &TreeOrchestrator) -> Vec<String> {
+    orchestrator.trees.iter().map(|tree| tree.to_string()).collect()
+}
+
+fn best_score(orchestrator: &TreeOrchestrator) -> f64 {
+    orchestrator.population_snapshot().scores.into_iter().fold(f64::NEG_INFINITY, f64::max)
+}
+
+#[test]
+fn elites_are_carried_over_unchanged() {
+    let config = EvolutionConfig { elitism: 3, mutation_rate: 0.5, ..EvolutionConfig::default() };
+    let mut orchestrator = orchestrator(20, config);
+    orchestrator.run(0);
+
+    for _ in 0..5 {
+        let snapshot = orchestrator.population_snapshot();
+        let mut ranked: Vec<usize> = (0..snapshot.trees.len()).collect();
+        ranked.sort_by(|&a, &b| snapshot.scores[b].total_cmp(&snapshot.scores[a]));
+        let elites: Vec<String> = ranked[..3].iter().map(|&idx| snapshot.trees[idx].to_string()).collect();
+
+        orchestrator.evolve_generation();
+        assert_eq!(population(&orchestrator)[..3], elites[..]);
+    }
+}
+
+#[test]
+fn best_fitness_never_drops_with_elitism() {
+    let config = EvolutionConfig { mutation_rate: 0.5, point_mutation_rate: 0.3, ..EvolutionConfig::default() };
+    let mut orchestrator = orchestrator(30, config);
+    let result = orchestrator.run(10);
+
+    assert_eq!(result.history.len(), 11);
+    for pair in result.history.windows(2) {
+        assert!(pair[1].best_fitness >= pair[0].best_fitness, "{:?}", pair);
+    }
+    assert_eq!(result.best_fitness, result.history.last().unwrap().best_fitness);
+}
+
+#[test]
+fn population_size_is_kept() {
+    // Crossover makes two children at a time, which must not overflow an odd population
+    for (max_trees, elitism) in [(15, 0), (15, 2), (8, 8), (3, 20)] {
+        let config = EvolutionConfig { elitism, crossover_rate: 1.0, ..EvolutionConfig::default() };
+        let mut orchestrator = orchestrator(max_trees, config);
+        orchestrator.run(3);
+        assert_eq!(orchestrator.trees.len(), max_trees);
+        assert_eq!(orchestrator.population_snapshot().scores.len(), max_trees);
+        let ids: Vec<usize> = orchestrator.trees.iter().map(|tree| tree.id).collect();
+        assert_eq!(ids, (0..max_trees).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn tournaments_pick_the_fittest_contender() {
+    let mut rng = ChaCha8Rng::seed_from_u64(6);
+
+    // Missing the best of 10 trees in 400 draws is practically impossible
+    let config = EvolutionConfig { tournament_size: 400, ..EvolutionConfig::default() };
+    let mut large = orchestrator(10, config);
+    large.run(0);
+    let best = best_score(&large);
+    let scores = large.population_snapshot().scores;
+    for _ in 0..50 {
+        assert_eq!(scores[large.tournament_select(&mut rng)], best);
+    }
+
+    // Bigger tournaments select fitter trees on average
+    let mean_selected = |tournament_size: usize, rng: &mut ChaCha8Rng| -> f64 {
+        let config = EvolutionConfig { tournament_size, ..EvolutionConfig::default() };
+        let mut orchestrator = orchestrator(40, config);
+        orchestrator.run(0);
+        let scores = orchestrator.population_snapshot().scores;
+        (0..2000).map(|_| scores[orchestrator.tournament_select(rng)]).sum::<f64>() / 2000.0
+    };
+    let (one, two, four) = (mean_selected(1, &mut rng), mean_selected(2, &mut rng), mean_selected(4, &mut rng));
+    assert!(one < two && two < four, "{} {} {}", one, two, four);
+}
+
+#[test]
+fn without_variation_offspring_are_copies_of_parents() {
+    let config = EvolutionConfig { elitism: 0, crossover_rate: 0.0, mutation_rate: 0.0, ..EvolutionConfig::default() };
+    let mut orchestrator = orchestrator(25, config);
+    orchestrator.run(0);
+    let parents = population(&orchestrator);
+
+    orchestrator.evolve_generation();
+    for child in population(&orchestrator) {
+        assert!(parents.contains(&child), "{} has no parent", child);
+    }
+}
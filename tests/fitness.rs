@@ -0,0 +1,65 @@
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    tree_builder::{ParseTree, TreeOrchestrator},
+    types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn grammar() -> NonTerminalGrammar {
+    NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar])
+}
+
+fn variables() -> VariableDefinitions {
+    VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: FLOAT },
+        Variable { name: "y".to_string(), _type: FLOAT },
+    ])
+}
+
+fn orchestrator(max_trees: usize) -> TreeOrchestrator {
+    let variables = variables();
+    let features = (0..25)
+        .map(|i| DataRow::new(&variables, vec![Box::new(i as f64 / 5.0), Box::new((i % 4) as f64)]).unwrap())
+        .collect();
+    let targets = (0..25).map(|i| Box::new((i as f64 / 5.0).sin() * 3.0) as Box<AnyValue>).collect();
+    let dataset = Dataset::new(features, targets).unwrap();
+
+    let mut orchestrator = TreeOrchestrator::new(grammar(), variables, dataset, max_trees, 4, FLOAT).with_seed(8);
+    orchestrator.generate_trees();
+    orchestrator
+}
+
+/// Each tree scored on its own, one after the other.
+fn one_by_one(orchestrator: &TreeOrchestrator) -> Vec<f64> {
+    let (grammar, variables) = (grammar(), variables());
+    orchestrator
+        .trees
+        .iter()
+        .map(|tree| tree.clone().evaluate_fitness(orchestrator.get_dataset(), &grammar, &variables))
+        .collect()
+}
+
+/// With or without the `parallel` feature, every tree gets its own score, even when the population no longer has
+/// the size it was created with.
+#[test]
+fn every_tree_is_scored_like_on_its_own() {
+    for batched in [false, true] {
+        for population in [30, 45, 12] {
+            let mut orchestrator = orchestrator(30);
+            if batched {
+                orchestrator.enable_batched_evaluation().unwrap();
+            }
+            let extra: Vec<ParseTree> = orchestrator.trees.iter().take(population.max(30) - 30).cloned().collect();
+            orchestrator.trees.truncate(population);
+            orchestrator.trees.extend(extra);
+
+            orchestrator.evaluate_fitness();
+            let scores = orchestrator.population_snapshot().scores;
+            assert_eq!(scores.len(), population);
+            assert_eq!(scores, one_by_one(&orchestrator), "{} trees, batched {}", population, batched);
+            let fitness: Vec<f64> = orchestrator.trees.iter().map(|tree| tree.fitness).collect();
+            assert_eq!(fitness, scores);
+        }
+    }
+}
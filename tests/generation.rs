@@ -0,0 +1,65 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    possibilities_tables::PossibilityTable,
+    tree_builder::ParseTree,
+    types::{DataType, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const VECTOR: TypeInfo = TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float };
+
+fn generate(grammar: &NonTerminalGrammar, variables: &VariableDefinitions, method: GenerationMethod, max_depth: usize) -> Vec<ParseTree> {
+    let table = PossibilityTable::new(grammar, variables, FLOAT, max_depth);
+    let mut rng = ChaCha8Rng::seed_from_u64(max_depth as u64);
+    (0..200)
+        .map(|id| ParseTree::generate_random(id, max_depth, FLOAT, grammar, variables, method, &table, &mut rng))
+        .collect()
+}
+
+fn leaf_depths(tree: &ParseTree) -> Vec<usize> {
+    tree.tree.iter().filter(|node| node.left_index.is_none()).map(|node| node.depth).collect()
+}
+
+#[test]
+fn full_trees_reach_max_depth_on_every_branch() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = VariableDefinitions::new(vec![Variable { name: "x".to_string(), _type: FLOAT }]);
+
+    for max_depth in 1..=5 {
+        for tree in generate(&grammar, &variables, GenerationMethod::Full, max_depth) {
+            tree.validate(&grammar, &variables, FLOAT).unwrap();
+            assert!(leaf_depths(&tree).iter().all(|depth| *depth == max_depth - 1), "{}", tree);
+        }
+    }
+}
+
+// Scalars need a vector under them and Element an integer index only constants end, so some branches have to
+// stop early. Generation panics if the table ever offered a type it cannot complete.
+#[test]
+fn generation_completes_every_type_the_table_offers() {
+    let shapes = [Shape::Scalar, Shape::Vector(3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let variables = VariableDefinitions::new(vec![Variable { name: "v".to_string(), _type: VECTOR }]);
+
+    for max_depth in 2..=6 {
+        for method in [GenerationMethod::Full, GenerationMethod::Grow, GenerationMethod::Uniform { max_size: 9 }] {
+            for tree in generate(&grammar, &variables, method, max_depth) {
+                tree.validate(&grammar, &variables, FLOAT).unwrap();
+                assert!(leaf_depths(&tree).iter().all(|depth| *depth < max_depth), "{}", tree);
+                // A scalar is never a leaf: no variable has that type and rules can always build one in time.
+                assert!(tree.tree.iter().all(|node| node.left_index.is_some() || node._type.output_type() != FLOAT), "{}", tree);
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "cannot be generated at depth 0 within max depth 1")]
+fn types_that_do_not_fit_fail_loudly() {
+    let shapes = [Shape::Scalar, Shape::Vector(3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let variables = VariableDefinitions::new(vec![Variable { name: "v".to_string(), _type: VECTOR }]);
+    generate(&grammar, &variables, GenerationMethod::Full, 1);
+}
@@ -0,0 +1,95 @@
+use stsr::{
+    grammar_file::GrammarFile,
+    nonterminal::RuleId,
+    ops::{CustomOperation, Operation},
+    registry::FunctionRegistry,
+    types::TypeInfo,
+};
+
+const HEADER: &str = "type real = Float/Scalar\nvariable x : real\noutput real\n";
+
+fn parse(rules: &str) -> Result<GrammarFile, String> {
+    GrammarFile::parse(&format!("{}{}", HEADER, rules), &FunctionRegistry::with_builtins())
+}
+
+fn error(rules: &str) -> String {
+    parse(rules).expect_err("the grammar file should not parse")
+}
+
+#[test]
+fn parses_declared_custom_operations() {
+    let file = parse("operation Scale : 2\nrule Add(real, real) -> real = add_f64\nrule Scale(real, real) -> real = mul_f64\n")
+        .unwrap();
+    let real: TypeInfo = "Float/Scalar".parse().unwrap();
+
+    assert_eq!(file.grammar.rules.len(), 2);
+    assert_eq!(file.operations["Scale"], CustomOperation::new("Scale", 2));
+    let id = RuleId { operation: Operation::custom("Scale", 2), input_one_type: real, input_two_type: real, output: real };
+    assert_eq!(file.grammar.find_rule(id).map(|rule| rule.signature()), Some("Scale(Float/Scalar, Float/Scalar) -> Float/Scalar".to_string()));
+}
+
+#[test]
+fn undeclared_operations_are_errors() {
+    let err = error("rule Ad(real, real) -> real = add_f64\n");
+    assert!(err.starts_with("line 4: Unknown operation 'Ad'"), "{}", err);
+}
+
+#[test]
+fn operation_declarations_are_checked() {
+    assert!(error("operation Add : 2\n").contains("'Add' is a built-in operation"));
+    assert!(error("operation Scale : 2\noperation Scale : 1\n").contains("Operation 'Scale' is already declared"));
+    assert!(error("operation Scale : 3\n").contains("operations take one or two inputs"));
+    assert!(error("operation Scale : two\n").contains("'two' is not a number of inputs"));
+    assert!(error("operation ThisNameIsFarTooLongForAnOperation : 1\n").contains("longer than 23 bytes"));
+}
+
+#[test]
+fn rule_errors_name_their_line() {
+    assert!(error("rule Add(real) -> real = add_f64\n").starts_with("line 4: Add takes 2 inputs, got 1"));
+    assert!(error("rule Add(real, complex) -> real = add_f64\n").contains("Unknown type 'complex'"));
+    assert!(error("rule Add(real, real) -> real = plus\n").contains("Unknown function 'plus'"));
+    assert!(error("rule Add(real, real) -> real = add_i32\n").contains("Function 'add_i32' cannot implement"));
+    assert!(error("rule Add(real, real) -> real\n").contains("Expected 'rule"));
+    assert!(error("rule Add(real, real) -> real = add_f64\n\nrule Add(real, real) -> real = add_f64\n")
+        .starts_with("line 6: Rule Add(Float/Scalar, Float/Scalar) -> Float/Scalar is already declared"));
+    assert!(error("terminal pi : real\n").contains("Unknown declaration 'terminal'"));
+}
+
+#[test]
+fn grammars_need_rules_for_their_output() {
+    assert_eq!(GrammarFile::parse(HEADER, &FunctionRegistry::with_builtins()).unwrap_err(), "The grammar declares no rules");
+    assert!(error("rule Add(Integer/Scalar, Integer/Scalar) -> Integer/Scalar = add_i32\n")
+        .contains("No rule produces the output type Float/Scalar"));
+}
+
+#[test]
+fn functions_have_to_compute_the_rule_operation() {
+    assert!(error("rule Divide(real, real) -> real = add_f64\n")
+        .starts_with("line 4: Function 'add_f64' computes Add, not Divide"));
+    assert!(error("rule Norm(Float/Vector(3)) -> real = dot\n").contains("Function 'dot' takes 2 inputs, Norm takes 1"));
+}
+
+#[test]
+fn custom_operations_only_bind_functions_of_their_arity() {
+    assert!(error("operation neg : 1\nrule neg(real) -> real = add_f64\n")
+        .starts_with("line 5: Function 'add_f64' takes 2 inputs, neg takes 1"));
+    assert!(error("operation neg : 1\nrule neg(real) -> real = add\n").contains("Function 'add' takes 2 inputs"));
+
+    let norm = parse(
+        "type point = Float/Vector(3)\nvariable p : point\noperation length : 1\nrule length(point) -> real = norm\nrule Add(real, real) -> real = add_f64\n",
+    );
+    assert!(norm.is_ok(), "{:?}", norm.err());
+}
+
+#[test]
+fn grammars_are_diagnosed_on_load() {
+    let unused = error("rule Add(real, real) -> real = add_f64\nrule Add(Integer/Scalar, Integer/Scalar) -> Integer/Scalar = add_i32\n");
+    assert!(unused.starts_with("The grammar has problems: "), "{}", unused);
+    assert!(unused.contains("Rule Add(Integer/Scalar, Integer/Scalar) -> Integer/Scalar can never appear"), "{}", unused);
+    assert!(unused.contains("Integer/Scalar is never reachable from the target type"), "{}", unused);
+
+    let element = "type point = Float/Vector(3)\nvariable p : point\nrule Element(point, Integer/Scalar) -> real = element\n";
+    assert!(error(element).contains("No variable can end Integer/Scalar"));
+    assert!(parse(&format!("constant Integer/Scalar\n{}", element)).is_ok());
+    assert!(error("constant real\nconstant Float/Scalar\n").starts_with("line 5: Float/Scalar is already declared constant"));
+}
@@ -0,0 +1,114 @@
+use stsr::{
+    linalg::{self, linear_algebra_rules},
+    node::compatible_outputs,
+    ops::Operation,
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+
+fn float(shape: Shape) -> TypeInfo {
+    TypeInfo { shape, data_type: DataType::Float }
+}
+
+fn integer(shape: Shape) -> TypeInfo {
+    TypeInfo { shape, data_type: DataType::Integer }
+}
+
+fn get<T: Clone + 'static>(value: Box<AnyValue>) -> T {
+    value.downcast_ref::<T>().unwrap().clone()
+}
+
+#[test]
+fn operations_compute_their_values() {
+    let (u, v) = (vec![1.0, 2.0, 3.0], vec![4.0, -5.0, 0.5]);
+    let m = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+
+    assert_eq!(get::<f64>(linalg::dot(&u, &v)), 4.0 - 10.0 + 1.5);
+    assert_eq!(
+        get::<Vec<Vec<f64>>>(linalg::outer(&vec![1.0, 2.0], &v)),
+        vec![vec![4.0, -5.0, 0.5], vec![8.0, -10.0, 1.0]]
+    );
+    assert_eq!(get::<Vec<Vec<f64>>>(linalg::transpose(&m, &())), vec![vec![1.0, 4.0], vec![2.0, 5.0], vec![3.0, 6.0]]);
+    assert_eq!(get::<Vec<f64>>(linalg::matvec(&m, &u)), vec![14.0, 32.0]);
+    assert_eq!(get::<f64>(linalg::norm(&vec![3.0, 4.0], &())), 5.0);
+    assert_eq!(get::<f64>(linalg::norm(&vec![3, -4], &())), 5.0);
+}
+
+#[test]
+fn integer_operations_wrap() {
+    assert_eq!(get::<i32>(linalg::dot(&vec![i32::MAX, 1], &vec![1, 1])), i32::MIN);
+    assert_eq!(get::<i32>(linalg::dot(&vec![2, 3], &vec![-4, 5])), 7);
+    assert_eq!(get::<Vec<Vec<i32>>>(linalg::outer(&vec![65536], &vec![65536, 2])), vec![vec![0, 131072]]);
+    assert_eq!(get::<Vec<i32>>(linalg::matvec(&vec![vec![1, 2], vec![-3, 4]], &vec![5, 6])), vec![17, 9]);
+}
+
+#[test]
+fn element_indices_wrap_into_range() {
+    let v = vec![10.0, 20.0, 30.0];
+    for (index, expected) in [(0, 10.0), (2, 30.0), (3, 10.0), (-1, 30.0), (i32::MIN, 20.0)] {
+        assert_eq!(get::<f64>(linalg::element(&v, &index)), expected, "index {}", index);
+    }
+
+    let m = vec![vec![1, 2], vec![3, 4], vec![5, 6]];
+    assert_eq!(get::<Vec<i32>>(linalg::element(&m, &1)), vec![3, 4]);
+    assert_eq!(get::<Vec<i32>>(linalg::element(&m, &-4)), vec![5, 6]);
+}
+
+#[test]
+fn output_types_follow_the_shapes() {
+    let (scalar, vector, matrix) = (float(Shape::Scalar), float(Shape::Vector(3)), float(Shape::Matrix(2, 3)));
+
+    assert_eq!(compatible_outputs(vector, vector, Operation::Dot), vec![scalar]);
+    assert_eq!(compatible_outputs(float(Shape::Vector(2)), vector, Operation::Outer), vec![matrix]);
+    assert_eq!(compatible_outputs(matrix, matrix, Operation::Transpose), vec![float(Shape::Matrix(3, 2))]);
+    assert_eq!(compatible_outputs(matrix, vector, Operation::MatVec), vec![float(Shape::Vector(2))]);
+    assert_eq!(compatible_outputs(integer(Shape::Vector(3)), integer(Shape::Vector(3)), Operation::Norm), vec![scalar]);
+    assert_eq!(compatible_outputs(vector, integer(Shape::Scalar), Operation::Element), vec![scalar]);
+    assert_eq!(compatible_outputs(matrix, integer(Shape::Scalar), Operation::Element), vec![vector]);
+
+    // Mismatched sizes, data types or index types
+    assert!(compatible_outputs(vector, float(Shape::Vector(2)), Operation::Dot).is_empty());
+    assert!(compatible_outputs(vector, integer(Shape::Vector(3)), Operation::Dot).is_empty());
+    assert!(compatible_outputs(matrix, float(Shape::Vector(2)), Operation::MatVec).is_empty());
+    assert!(compatible_outputs(vector, scalar, Operation::Element).is_empty());
+    assert!(compatible_outputs(scalar, scalar, Operation::Norm).is_empty());
+}
+
+#[test]
+fn arithmetic_broadcasts_scalars() {
+    let (scalar, vector, matrix) = (float(Shape::Scalar), float(Shape::Vector(3)), float(Shape::Matrix(2, 3)));
+
+    for operation in [Operation::Add, Operation::Subtract, Operation::Multiply, Operation::Divide] {
+        assert_eq!(compatible_outputs(scalar, scalar, operation), vec![scalar]);
+        assert_eq!(compatible_outputs(vector, scalar, operation), vec![vector]);
+        assert_eq!(compatible_outputs(scalar, vector, operation), vec![vector]);
+        assert_eq!(compatible_outputs(vector, vector, operation), vec![vector]);
+        assert_eq!(compatible_outputs(matrix, scalar, operation), vec![matrix]);
+        assert_eq!(compatible_outputs(scalar, matrix, operation), vec![matrix]);
+        assert!(compatible_outputs(vector, float(Shape::Vector(2)), operation).is_empty());
+        assert!(compatible_outputs(scalar, integer(Shape::Scalar), operation).is_empty());
+    }
+
+    // Matrices add element-wise and multiply as matrices
+    assert_eq!(compatible_outputs(matrix, matrix, Operation::Add), vec![matrix]);
+    assert!(compatible_outputs(matrix, matrix, Operation::Multiply).is_empty());
+    assert_eq!(
+        compatible_outputs(matrix, float(Shape::Matrix(3, 4)), Operation::Multiply),
+        vec![float(Shape::Matrix(2, 4))]
+    );
+    assert!(compatible_outputs(matrix, matrix, Operation::Divide).is_empty());
+}
+
+#[test]
+fn shapes_with_a_zero_dimension_get_no_rules() {
+    let shapes = [Shape::Scalar, Shape::Vector(0), Shape::Matrix(0, 2), Shape::Matrix(2, 0)];
+    assert!(linear_algebra_rules(DataType::Float, &shapes).is_empty());
+
+    let shapes = [Shape::Scalar, Shape::Vector(0), Shape::Vector(2)];
+    let rules = linear_algebra_rules(DataType::Float, &shapes);
+    assert!(!rules.is_empty());
+    for rule in rules {
+        for type_info in [rule.input_one_type, rule.input_two_type, rule.output] {
+            assert_ne!(type_info.shape, Shape::Vector(0), "{:?}", rule.operation);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use stsr::{
+    loader::CsvLoader,
+    types::{AnyValue, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const INTEGER: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Integer };
+
+fn variables(types: &[(&str, TypeInfo)]) -> VariableDefinitions {
+    VariableDefinitions::new(types.iter().map(|(name, type_info)| Variable { name: name.to_string(), _type: *type_info }).collect())
+}
+
+fn value<T: Clone + 'static>(value: &AnyValue) -> T {
+    value.downcast_ref::<T>().expect("value of another type").clone()
+}
+
+fn feature<T: Clone + 'static>(dataset: &Dataset, row: usize, name: &str) -> T {
+    value(dataset.features[row].values[name].as_ref())
+}
+
+#[test]
+fn loads_scalars_and_a_target() {
+    let variables = variables(&[("x", FLOAT), ("n", INTEGER)]);
+    let csv = "n, x, y\n1, 0.5, 2.5\n-3, 1e3, -1\n";
+    let dataset = CsvLoader::new(&variables).target("y", FLOAT).load_reader(csv.as_bytes()).unwrap();
+
+    assert_eq!(dataset.len(), 2);
+    assert_eq!(feature::<f64>(&dataset, 0, "x"), 0.5);
+    assert_eq!(feature::<i32>(&dataset, 1, "n"), -3);
+    assert_eq!(feature::<f64>(&dataset, 1, "x"), 1000.0);
+    assert_eq!(value::<f64>(dataset.targets[0].as_ref()), 2.5);
+    assert!(dataset.weights.is_none());
+}
+
+#[test]
+fn packs_vector_and_matrix_columns() {
+    let vector = TypeInfo { shape: Shape::Vector(2), data_type: DataType::Float };
+    let matrix = TypeInfo { shape: Shape::Matrix(2, 2), data_type: DataType::Integer };
+    let variables = variables(&[("v", vector), ("m", matrix), ("w", vector)]);
+    let csv = "v_0,v_1,m_0_0,m_0_1,m_1_0,m_1_1,a,b,t_0,t_1\n1,2,3,4,5,6,7,8,9,10\n";
+
+    let dataset = CsvLoader::new(&variables)
+        .pack("w", &["b", "a"])
+        .targets(&["t_0", "t_1"], TypeInfo { shape: Shape::Vector(2), data_type: DataType::Integer })
+        .load_reader(csv.as_bytes())
+        .unwrap();
+
+    assert_eq!(feature::<Vec<f64>>(&dataset, 0, "v"), vec![1.0, 2.0]);
+    assert_eq!(feature::<Vec<Vec<i32>>>(&dataset, 0, "m"), vec![vec![3, 4], vec![5, 6]]);
+    assert_eq!(feature::<Vec<f64>>(&dataset, 0, "w"), vec![8.0, 7.0]);
+    assert_eq!(value::<Vec<i32>>(dataset.targets[0].as_ref()), vec![9, 10]);
+}
+
+#[test]
+fn reads_weights_and_custom_delimiters() {
+    let variables = variables(&[("x", FLOAT)]);
+    let csv = "x;y;w\n1;2;0.5\n3;4;0\n";
+    let dataset = CsvLoader::new(&variables)
+        .target("y", FLOAT)
+        .weight_column("w")
+        .delimiter(b';')
+        .load_reader(csv.as_bytes())
+        .unwrap();
+
+    assert_eq!(dataset.weights, Some(vec![0.5, 0.0]));
+    assert_eq!(feature::<f64>(&dataset, 1, "x"), 3.0);
+
+    let negative = CsvLoader::new(&variables).target("y", FLOAT).weight_column("w").load_reader("x,y,w\n1,2,-1\n".as_bytes());
+    assert_eq!(negative.unwrap_err(), "line 2: weight -1 must be finite and non-negative");
+}
+
+#[test]
+fn errors_name_the_line_and_column() {
+    let variables = variables(&[("x", FLOAT), ("n", INTEGER)]);
+    let load = |csv: &str| CsvLoader::new(&variables).target("y", FLOAT).load_reader(csv.as_bytes()).unwrap_err();
+
+    assert_eq!(load("x,y\n1,2\n"), "line 1: missing column 'n' for 'n'");
+    assert_eq!(load("x,n,y,x\n1,2,3,4\n"), "line 1: duplicate column 'x'");
+    assert_eq!(load("x,n,y\n1,2,3\n1,2.5,3\n"), "line 3: cannot parse '2.5' as i32 for 'n'");
+    assert!(load("x,n,y\n1,2,3\n1,2\n").starts_with("line 3: "));
+
+    let no_target = CsvLoader::new(&variables).load_reader("x,n\n1,2\n".as_bytes());
+    assert_eq!(no_target.unwrap_err(), "No target column set on CsvLoader");
+}
+
+#[test]
+fn shapes_without_values_are_rejected() {
+    let empty = TypeInfo { shape: Shape::Matrix(2, 0), data_type: DataType::Float };
+    let variables = variables(&[("m", empty)]);
+    let result = CsvLoader::new(&variables).pack("m", &[]).target("y", FLOAT).load_reader("y\n1\n".as_bytes());
+    assert_eq!(result.unwrap_err(), "'m' has shape Matrix(2, 0), which holds no values");
+}
@@ -0,0 +1,160 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    tree_builder::ParseTree,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const INTEGER: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Integer };
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn variables(names: &[&str]) -> VariableDefinitions {
+    VariableDefinitions::new(names.iter().map(|name| Variable { name: name.to_string(), _type: INTEGER }).collect())
+}
+
+#[test]
+fn point_mutation_of_an_empty_tree_does_nothing() {
+    let grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    let mut tree = ParseTree::empty(0);
+    assert!(!tree.point_mutate(&grammar, &variables(&["x"]), &mut ChaCha8Rng::seed_from_u64(0)));
+    assert!(tree.tree.is_empty());
+}
+
+#[test]
+fn point_mutation_always_changes_a_terminal() {
+    let grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+    for (text, names) in [("x", &["x"][..]), ("x", &["x", "y"][..]), ("3", &[][..]), ("3", &["x"][..])] {
+        let variables = variables(names);
+        let tree = ParseTree::from_sexpr(text, &grammar, &variables, INTEGER).unwrap();
+        for _ in 0..200 {
+            let mut mutated = tree.clone();
+            assert!(mutated.point_mutate(&grammar, &variables, &mut rng));
+            assert_ne!(mutated.to_string(), text);
+        }
+    }
+}
+
+#[test]
+fn point_mutation_keeps_the_shape() {
+    let grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    let variables = variables(&["x", "y"]);
+    let tree = ParseTree::from_sexpr("(Add (Multiply x 2) (Subtract y x))", &grammar, &variables, INTEGER).unwrap();
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+
+    for _ in 0..100 {
+        let mut mutated = tree.clone();
+        assert!(mutated.point_mutate(&grammar, &variables, &mut rng));
+        assert_ne!(mutated.to_string(), tree.to_string());
+        mutated.validate(&grammar, &variables, INTEGER).unwrap();
+        let shape = |tree: &ParseTree| tree.tree.iter().map(|node| (node.left_index, node.right_index)).collect::<Vec<_>>();
+        assert_eq!(shape(&mutated), shape(&tree));
+    }
+}
+
+fn float_variables(names: &[&str]) -> VariableDefinitions {
+    VariableDefinitions::new(names.iter().map(|name| Variable { name: name.to_string(), _type: FLOAT }).collect())
+}
+
+/// Every node as printed on its own, except the constants.
+fn without_constants(tree: &ParseTree) -> Vec<String> {
+    tree.tree
+        .iter()
+        .map(|node| match (node.left_index, &node.variable_id) {
+            (None, None) => "constant".to_string(),
+            (None, Some(name)) => name.clone(),
+            _ => format!("{:?}", node._type),
+        })
+        .collect()
+}
+
+fn constants<T: Copy + 'static>(tree: &ParseTree) -> Vec<T> {
+    tree.tree
+        .iter()
+        .filter(|node| node.left_index.is_none() && node.variable_id.is_none())
+        .filter_map(|node| node.value.downcast_ref::<T>().copied())
+        .collect()
+}
+
+#[test]
+fn perturbation_only_changes_constants() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = float_variables(&["x", "y"]);
+    let tree = ParseTree::from_sexpr("(Add (Multiply x 2.5) (Subtract y -1.0))", &grammar, &variables, FLOAT).unwrap();
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+    for _ in 0..50 {
+        let mut perturbed = tree.clone();
+        perturbed.perturb_constants(0.5, &mut rng);
+        perturbed.validate(&grammar, &variables, FLOAT).unwrap();
+        assert_eq!(without_constants(&perturbed), without_constants(&tree));
+        let (before, after) = (constants::<f64>(&tree), constants::<f64>(&perturbed));
+        assert_eq!(after.len(), 2);
+        assert!(before.iter().zip(&after).all(|(before, after)| before != after), "{}", perturbed);
+    }
+}
+
+#[test]
+fn integers_move_by_at_most_the_scale() {
+    let grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    let variables = variables(&["x"]);
+    let tree = ParseTree::from_sexpr("(Add x 10)", &grammar, &variables, INTEGER).unwrap();
+    let mut rng = ChaCha8Rng::seed_from_u64(8);
+
+    for (scale, k) in [(3.0, 3), (2.6, 3), (0.2, 1)] {
+        let mut steps = std::collections::BTreeSet::new();
+        for _ in 0..500 {
+            let mut perturbed = tree.clone();
+            perturbed.perturb_constants(scale, &mut rng);
+            steps.insert(constants::<i32>(&perturbed)[0] - 10);
+        }
+        assert_eq!(steps, (-k..=k).collect(), "scale {}", scale);
+    }
+
+    let mut wrapping = ParseTree::from_sexpr("(Add x 2147483647)", &grammar, &variables, INTEGER).unwrap();
+    for _ in 0..20 {
+        wrapping.perturb_constants(1.0, &mut rng);
+    }
+    wrapping.validate(&grammar, &variables, INTEGER).unwrap();
+}
+
+#[test]
+fn vectors_and_matrices_keep_their_shape() {
+    let shapes = [Shape::Scalar, Shape::Vector(3), Shape::Matrix(3, 3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let vector = TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float };
+    let variables = VariableDefinitions::new(vec![Variable { name: "v".to_string(), _type: vector }]);
+    let text = "(Add (Dot v [1.0 2.0 3.0]) (Norm (MatVec [[1.0 0.0 0.0] [0.0 1.0 0.0] [0.0 0.0 1.0]] v)))";
+    let tree = ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap();
+    let mut rng = ChaCha8Rng::seed_from_u64(9);
+
+    for _ in 0..20 {
+        let mut perturbed = tree.clone();
+        perturbed.perturb_constants(1.0, &mut rng);
+        perturbed.validate(&grammar, &variables, FLOAT).unwrap();
+        assert_ne!(perturbed.to_string(), tree.to_string());
+
+        let vector = perturbed.tree.iter().find_map(|node| node.value.downcast_ref::<Vec<f64>>()).unwrap();
+        assert_eq!(vector.len(), 3);
+        let matrix = perturbed.tree.iter().find_map(|node| node.value.downcast_ref::<Vec<Vec<f64>>>()).unwrap();
+        assert_eq!(matrix.iter().map(|row| row.len()).collect::<Vec<_>>(), vec![3, 3, 3]);
+    }
+}
+
+#[test]
+fn a_zero_scale_changes_nothing() {
+    let integers = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    let floats = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut tree = ParseTree::from_sexpr("(Add x (Multiply 3 -7))", &integers, &variables(&["x"]), INTEGER).unwrap();
+    let mut float_tree = ParseTree::from_sexpr("(Add x 0.25)", &floats, &float_variables(&["x"]), FLOAT).unwrap();
+    for _ in 0..20 {
+        tree.perturb_constants(0.0, &mut rng);
+        float_tree.perturb_constants(0.0, &mut rng);
+    }
+    assert_eq!(tree.to_string(), "(Add x (Multiply 3 -7))");
+    assert_eq!(float_tree.to_string(), "(Add x 0.25)");
+}
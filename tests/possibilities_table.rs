@@ -0,0 +1,67 @@
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    ops::Operation,
+    possibilities_tables::PossibilityTable,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const VECTOR: TypeInfo = TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float };
+const INDEX: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Integer };
+
+// Scalars only come out of the linear algebra rules, since the only variable is a vector. Element's integer
+// index is neither a variable nor produced by any rule.
+fn table(max_depth: usize) -> (NonTerminalGrammar, PossibilityTable) {
+    let shapes = [Shape::Scalar, Shape::Vector(3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let variables = VariableDefinitions::new(vec![Variable { name: "v".to_string(), _type: VECTOR }]);
+    let table = PossibilityTable::new(&grammar, &variables, FLOAT, max_depth);
+    (grammar, table)
+}
+
+#[test]
+fn min_heights_count_the_levels_down_to_a_terminal() {
+    let (_, table) = table(4);
+
+    assert_eq!(table.min_height(VECTOR), Some(1));
+    assert_eq!(table.min_height(FLOAT), Some(2));
+    assert!(table.is_terminal(VECTOR));
+    assert!(!table.is_terminal(FLOAT));
+
+    // Types no variable ends are filled with constants.
+    assert_eq!(table.min_height(INDEX), Some(1));
+    assert!(table.is_terminal(INDEX));
+
+    let matrix = TypeInfo { shape: Shape::Matrix(3, 3), data_type: DataType::Float };
+    assert_eq!(table.min_height(matrix), None);
+}
+
+#[test]
+fn rows_only_hold_types_that_fit_in_the_depth_left() {
+    let (_, too_shallow) = table(1);
+    assert!(!too_shallow.is_valid_for_generation());
+
+    let (_, shallow) = table(2);
+    assert!(shallow.is_valid_for_generation());
+    assert!(shallow.can_produce_type_at_depth(0, FLOAT));
+    assert!(shallow.can_produce_type_at_depth(1, VECTOR));
+    assert!(shallow.can_produce_type_at_depth(1, INDEX));
+    assert!(!shallow.can_produce_type_at_depth(1, FLOAT));
+
+    let (_, deeper) = table(3);
+    assert!(deeper.can_produce_type_at_depth(1, FLOAT));
+    assert!(!deeper.can_produce_type_at_depth(2, FLOAT));
+}
+
+#[test]
+fn expansions_skip_rules_whose_inputs_cannot_be_completed() {
+    let (grammar, table) = table(2);
+
+    let mut operations: Vec<Operation> =
+        table.expansions(&grammar, 0, FLOAT).into_iter().map(|(_, _, operation)| operation).collect();
+    operations.sort_by_key(|operation| operation.to_string());
+    operations.dedup();
+    assert_eq!(operations, vec![Operation::Dot, Operation::Element, Operation::Norm]);
+
+    assert!(table.expansions(&grammar, 1, FLOAT).is_empty());
+}
@@ -0,0 +1,63 @@
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    tree_builder::ParseTree,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn infix(text: &str) -> String {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = VariableDefinitions::new(
+        ["a", "b", "c"].iter().map(|name| Variable { name: name.to_string(), _type: FLOAT }).collect(),
+    );
+    ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap().to_infix()
+}
+
+#[test]
+fn right_operands_of_equal_precedence_keep_their_parentheses() {
+    assert_eq!(infix("(Multiply a (Divide b c))"), "a * (b / c)");
+    assert_eq!(infix("(Divide a (Multiply b c))"), "a / (b * c)");
+    assert_eq!(infix("(Divide a (Divide b c))"), "a / (b / c)");
+    assert_eq!(infix("(Add a (Subtract b c))"), "a + (b - c)");
+    assert_eq!(infix("(Subtract a (Add b c))"), "a - (b + c)");
+    assert_eq!(infix("(Subtract a (Subtract b c))"), "a - (b - c)");
+}
+
+#[test]
+fn associative_and_left_operands_drop_their_parentheses() {
+    assert_eq!(infix("(Add a (Add b c))"), "a + b + c");
+    assert_eq!(infix("(Multiply a (Multiply b c))"), "a * b * c");
+    assert_eq!(infix("(Divide (Multiply a b) c)"), "a * b / c");
+    assert_eq!(infix("(Subtract (Add a b) c)"), "a + b - c");
+    assert_eq!(infix("(Add a (Multiply b c))"), "a + b * c");
+    assert_eq!(infix("(Multiply a (Add b c))"), "a * (b + c)");
+}
+
+#[test]
+fn dot_export_escapes_labels_and_links_children() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = VariableDefinitions::new(
+        ["say\"hi", "back\\slash"].iter().map(|name| Variable { name: name.to_string(), _type: FLOAT }).collect(),
+    );
+    let tree = ParseTree::from_sexpr("(Add say\"hi (Multiply back\\slash 2.5))", &grammar, &variables, FLOAT).unwrap();
+
+    assert_eq!(
+        tree.to_dot(),
+        concat!(
+            "digraph tree_0 {\n",
+            "    graph [ordering=out];\n",
+            "    node [fontname=\"Helvetica\"];\n",
+            "    n0 [label=\"Add : Float/Scalar\", shape=ellipse];\n",
+            "    n1 [label=\"say\\\"hi : Float/Scalar\", shape=box];\n",
+            "    n2 [label=\"Multiply : Float/Scalar\", shape=ellipse];\n",
+            "    n3 [label=\"back\\\\slash : Float/Scalar\", shape=box];\n",
+            "    n4 [label=\"2.5 : Float/Scalar\", shape=plaintext];\n",
+            "    n0 -> n1;\n",
+            "    n0 -> n2;\n",
+            "    n2 -> n3;\n",
+            "    n2 -> n4;\n",
+            "}\n",
+        )
+    );
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "serde")]
+
+use stsr::serialize::{from_bytes, to_bytes, Format, Value};
+
+// Values whose shortest decimal form is hard to parse back exactly, and the ones JSON writes as strings.
+const FLOATS: [f64; 9] = [
+    97.71999492940387,
+    -9.544517375509983,
+    0.1 + 0.2,
+    5e-324,
+    f64::MAX,
+    -0.0,
+    f64::NAN,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+];
+
+fn round_trip(value: &Value, format: Format) -> Value {
+    from_bytes(&to_bytes(value, format).unwrap(), format).unwrap()
+}
+
+#[test]
+fn floats_round_trip_bit_for_bit() {
+    for format in [Format::Json, Format::Binary] {
+        for float in FLOATS {
+            match round_trip(&Value::Float(float), format) {
+                Value::Float(read) => assert_eq!(read.to_bits(), float.to_bits(), "{:?} in {:?}", float, format),
+                other => panic!("{:?} read back as {:?}", float, other),
+            }
+        }
+
+        let matrix = Value::FloatMatrix(vec![FLOATS[..3].to_vec(), FLOATS[3..6].to_vec()]);
+        match round_trip(&matrix, format) {
+            Value::FloatMatrix(read) => {
+                let bits: Vec<u64> = read.iter().flatten().map(|v| v.to_bits()).collect();
+                assert_eq!(bits, FLOATS[..6].iter().map(|v| v.to_bits()).collect::<Vec<u64>>());
+            }
+            other => panic!("{:?} read back as {:?}", matrix, other),
+        }
+    }
+}
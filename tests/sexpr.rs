@@ -0,0 +1,134 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use stsr::{
+    nonterminal::{NonTerminalGrammar, NonTerminalRule},
+    ops::Operation,
+    possibilities_tables::PossibilityTable,
+    tree_builder::ParseTree,
+    types::{AnyValue, DataType, GenerationMethod, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const INTEGER: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Integer };
+const VECTOR: TypeInfo = TypeInfo { shape: Shape::Vector(3), data_type: DataType::Float };
+
+fn linear_algebra() -> (NonTerminalGrammar, VariableDefinitions) {
+    let shapes = [Shape::Scalar, Shape::Vector(3), Shape::Matrix(2, 3)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    let variables = VariableDefinitions::new(vec![
+        Variable { name: "v".to_string(), _type: VECTOR },
+        Variable { name: "x".to_string(), _type: FLOAT },
+    ]);
+    (grammar, variables)
+}
+
+fn scalar_variables(data_type: DataType) -> VariableDefinitions {
+    let type_info = TypeInfo { shape: Shape::Scalar, data_type };
+    VariableDefinitions::new(vec![
+        Variable { name: "x".to_string(), _type: type_info },
+        Variable { name: "y".to_string(), _type: type_info },
+    ])
+}
+
+/// Parses both printed forms of every tree back and checks they print the same.
+fn assert_round_trips(grammar: &NonTerminalGrammar, variables: &VariableDefinitions, output_type: TypeInfo, seed: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let table = PossibilityTable::new(grammar, variables, output_type, 5);
+    for method in [GenerationMethod::Full, GenerationMethod::Grow] {
+        for id in 0..100 {
+            let tree = ParseTree::generate_random(id, 5, output_type, grammar, variables, method, &table, &mut rng);
+            for text in [tree.to_string(), format!("{:#}", tree)] {
+                let parsed = ParseTree::from_sexpr(&text, grammar, variables, output_type)
+                    .unwrap_or_else(|err| panic!("{}: {}", text, err));
+                parsed.validate(grammar, variables, output_type).unwrap();
+                assert_eq!(parsed.to_string(), tree.to_string());
+                assert_eq!(format!("{:#}", parsed), format!("{:#}", tree));
+            }
+        }
+    }
+}
+
+#[test]
+fn printed_trees_parse_back() {
+    for data_type in [DataType::Float, DataType::Integer] {
+        let grammar = NonTerminalGrammar::standard(data_type, &[Shape::Scalar]);
+        let output_type = TypeInfo { shape: Shape::Scalar, data_type };
+        assert_round_trips(&grammar, &scalar_variables(data_type), output_type, 1);
+    }
+    let (grammar, variables) = linear_algebra();
+    assert_round_trips(&grammar, &variables, FLOAT, 2);
+}
+
+#[test]
+fn annotated_and_literal_forms() {
+    let (grammar, variables) = linear_algebra();
+    let text = "(Add:Float/Scalar (Dot:Float/Scalar v [1.5 -2.0 3e-7]) x:Float/Scalar)";
+    let tree = ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap();
+    assert_eq!(tree.to_string(), "(Add (Dot v [1.5 -2.0 3e-7]) x)");
+    assert_eq!(
+        format!("{:#}", tree),
+        "(Add:Float/Scalar (Dot:Float/Scalar v:Float/Vector(3) [1.5 -2.0 3e-7]:Float/Vector(3)) x:Float/Scalar)"
+    );
+
+    let matrix = ParseTree::from_sexpr("[[1.0 2.0 3.0] [4.0 5.0 6.0]]", &grammar, &variables, TypeInfo {
+        shape: Shape::Matrix(2, 3),
+        data_type: DataType::Float,
+    })
+    .unwrap();
+    assert_eq!(matrix.to_string(), "[[1.0 2.0 3.0] [4.0 5.0 6.0]]");
+}
+
+#[test]
+fn ill_typed_input_is_rejected() {
+    let (grammar, variables) = linear_algebra();
+    let parse = |text: &str| ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap_err();
+
+    assert_eq!(parse("(Add x v)"), "Variable 'v' is Float/Vector(3), but Float/Scalar is expected here");
+    assert_eq!(parse("x:Integer/Scalar"), "Annotated as Integer/Scalar, but Float/Scalar is expected here");
+    assert_eq!(parse("[1.0 2.0 3.0]"), "Found an array literal, but Float/Scalar is expected here");
+    assert_eq!(parse("(Dot v [1.0 2.0])"), "Expected 3 elements for Float/Vector(3), got 2");
+    assert_eq!(parse("(Power x x)"), "Unknown operation 'Power'");
+    assert_eq!(parse("(Add x x) y"), "Unexpected 'y' after the end of the expression");
+    assert_eq!(parse("(Add x x"), "Missing ')' for '(Add'");
+}
+
+#[test]
+fn unknown_variables_are_rejected() {
+    let (grammar, variables) = linear_algebra();
+    let parse = |text: &str| ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap_err();
+
+    assert_eq!(parse("(Add x z)"), "'z' is not a variable or a Float/Scalar constant");
+    assert_eq!(parse("(Dot v w)"), "'w' is not a variable or a Float/Vector(3) constant");
+}
+
+#[test]
+fn operations_take_their_number_of_arguments() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = scalar_variables(DataType::Float);
+    let parse = |text: &str| ParseTree::from_sexpr(text, &grammar, &variables, FLOAT).unwrap_err();
+
+    assert_eq!(parse("(Add x)"), "'Add' takes 2 arguments, got 1");
+    assert_eq!(parse("(Multiply x y x)"), "'Multiply' takes 2 arguments, got 3");
+}
+
+fn negate(value: &dyn std::any::Any, _: &dyn std::any::Any) -> Box<AnyValue> {
+    Box::new(-value.downcast_ref::<i32>().unwrap())
+}
+
+fn difference(left: &dyn std::any::Any, right: &dyn std::any::Any) -> Box<AnyValue> {
+    Box::new(left.downcast_ref::<i32>().unwrap() - right.downcast_ref::<i32>().unwrap())
+}
+
+#[test]
+fn operations_sharing_a_name_are_told_apart_by_arity() {
+    let mut grammar = NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar]);
+    grammar.add_rule(NonTerminalRule::unary(INTEGER, Operation::custom("Neg", 1), INTEGER, negate));
+    grammar.add_rule(NonTerminalRule::new(INTEGER, INTEGER, Operation::custom("Neg", 2), INTEGER, difference));
+    let variables = scalar_variables(DataType::Integer);
+    let parse = |text: &str| ParseTree::from_sexpr(text, &grammar, &variables, INTEGER);
+
+    for text in ["(Neg x)", "(Neg x y)", "(Neg (Neg x) (Neg y 3))"] {
+        assert_eq!(parse(text).unwrap().to_string(), text);
+    }
+    assert_eq!(parse("(Neg x y x)").unwrap_err(), "'Neg' takes 1 or 2 arguments, got 3");
+}
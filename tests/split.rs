@@ -0,0 +1,168 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    split::DatasetSplit,
+    tree_builder::TreeOrchestrator,
+    types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn variables() -> VariableDefinitions {
+    VariableDefinitions::new(vec![Variable { name: "row".to_string(), _type: FLOAT }])
+}
+
+/// Rows whose feature is their index, with the given targets.
+fn dataset(targets: Vec<Box<AnyValue>>) -> Dataset {
+    let variables = variables();
+    let features = (0..targets.len())
+        .map(|i| DataRow::new(&variables, vec![Box::new(i as f64)]).unwrap())
+        .collect();
+    Dataset::new(features, targets).unwrap()
+}
+
+fn float_dataset(len: usize) -> Dataset {
+    dataset((0..len).map(|i| Box::new(i as f64 * 0.5) as Box<AnyValue>).collect())
+}
+
+fn rows(dataset: &Dataset) -> Vec<usize> {
+    dataset.features.iter().map(|row| *row.values["row"].downcast_ref::<f64>().unwrap() as usize).collect()
+}
+
+fn sizes(split: &DatasetSplit) -> (usize, usize, usize) {
+    (split.train.len(), split.validation.len(), split.test.len())
+}
+
+/// Every row is in exactly one part.
+fn assert_partition(split: &DatasetSplit, len: usize) {
+    let mut all: Vec<usize> = [&split.train, &split.validation, &split.test].into_iter().flat_map(rows).collect();
+    all.sort();
+    assert_eq!(all, (0..len).collect::<Vec<_>>());
+}
+
+#[test]
+fn random_splits_round_part_sizes_and_cover_every_row() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    // 2.5 rounds up, 1.0 exactly, 0.45 of 11 rounds to 5
+    for (len, validation, test, expected) in [
+        (10, 0.25, 0.25, (4, 3, 3)),
+        (10, 0.1, 0.0, (9, 1, 0)),
+        (11, 0.45, 0.45, (1, 5, 5)),
+        (3, 0.0, 0.0, (3, 0, 0)),
+    ] {
+        let dataset = float_dataset(len);
+        let split = dataset.split_random(validation, test, &mut rng).unwrap();
+        assert_eq!(sizes(&split), expected, "{} rows, {} and {}", len, validation, test);
+        assert_partition(&split, len);
+    }
+}
+
+#[test]
+fn random_splits_shuffle_and_keep_targets_with_their_rows() {
+    let dataset = float_dataset(50);
+    let split = dataset.split_random(0.2, 0.2, &mut ChaCha8Rng::seed_from_u64(1)).unwrap();
+
+    assert_ne!(rows(&split.train), (0..30).collect::<Vec<_>>());
+    for part in [&split.train, &split.validation, &split.test] {
+        for (row, target) in rows(part).iter().zip(&part.targets) {
+            assert_eq!(*target.downcast_ref::<f64>().unwrap(), *row as f64 * 0.5);
+        }
+    }
+}
+
+#[test]
+fn stratified_splits_keep_class_proportions() {
+    // 60 rows of class 0, 30 of class 1, 10 of class 2
+    let classes = |i: usize| if i < 60 { 0 } else if i < 90 { 1 } else { 2 };
+    let dataset = dataset((0..100).map(|i| Box::new(classes(i)) as Box<AnyValue>).collect());
+    let split = dataset.split_stratified(0.2, 0.1, &mut ChaCha8Rng::seed_from_u64(2)).unwrap();
+
+    assert_eq!(sizes(&split), (70, 20, 10));
+    assert_partition(&split, 100);
+    let count = |part: &Dataset, class: i32| part.targets.iter().filter(|target| *target.downcast_ref::<i32>().unwrap() == class).count();
+    for (class, rows) in [(0, 60.0), (1, 30.0), (2, 10.0)] {
+        for (part, fraction) in [(&split.train, 0.7), (&split.validation, 0.2), (&split.test, 0.1)] {
+            let expected: f64 = rows * fraction;
+            assert!((count(part, class) as f64 - expected).abs() <= 1.0, "class {}: {} of {}", class, count(part, class), expected);
+        }
+    }
+}
+
+#[test]
+fn stratified_splits_spread_continuous_targets() {
+    let dataset = float_dataset(40);
+    let split = dataset.split_stratified(0.25, 0.25, &mut ChaCha8Rng::seed_from_u64(3)).unwrap();
+
+    assert_eq!(sizes(&split), (20, 10, 10));
+    assert_partition(&split, 40);
+    // Each quarter of the targets sends rows to validation and test
+    for part in [&split.validation, &split.test] {
+        let mut quarters: Vec<usize> = rows(part).iter().map(|row| row / 10).collect();
+        quarters.sort();
+        quarters.dedup();
+        assert_eq!(quarters, vec![0, 1, 2, 3]);
+    }
+}
+
+#[test]
+fn time_ordered_splits_keep_the_row_order() {
+    let dataset = float_dataset(10);
+    let split = dataset.split_time_ordered(0.25, 0.15).unwrap();
+
+    assert_eq!(sizes(&split), (5, 3, 2));
+    assert_eq!(rows(&split.train), vec![0, 1, 2, 3, 4]);
+    assert_eq!(rows(&split.validation), vec![5, 6, 7]);
+    assert_eq!(rows(&split.test), vec![8, 9]);
+}
+
+#[test]
+fn fractions_that_leave_no_training_rows_are_rejected() {
+    let dataset = float_dataset(10);
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+
+    for (validation, test) in [(-0.1, 0.2), (0.2, -0.1), (0.5, 0.5), (0.7, 0.6), (f64::NAN, 0.2), (0.2, f64::NAN), (f64::INFINITY, 0.0)] {
+        assert!(dataset.split_random(validation, test, &mut rng).is_err(), "{} and {}", validation, test);
+        assert!(dataset.split_stratified(validation, test, &mut rng).is_err(), "{} and {}", validation, test);
+        assert!(dataset.split_time_ordered(validation, test).is_err(), "{} and {}", validation, test);
+    }
+}
+
+#[test]
+fn every_row_is_held_out_by_exactly_one_fold() {
+    let dataset = float_dataset(23);
+    let folds = dataset.k_folds(5, &mut ChaCha8Rng::seed_from_u64(5)).unwrap();
+    assert_eq!(folds.len(), 5);
+
+    let mut held_out_rows = Vec::new();
+    for (train, held_out) in &folds {
+        assert!(held_out.len() == 4 || held_out.len() == 5, "{}", held_out.len());
+        assert_eq!(train.len() + held_out.len(), 23);
+        let mut fold_rows: Vec<usize> = rows(train).into_iter().chain(rows(held_out)).collect();
+        fold_rows.sort();
+        assert_eq!(fold_rows, (0..23).collect::<Vec<_>>());
+        held_out_rows.extend(rows(held_out));
+    }
+    held_out_rows.sort();
+    assert_eq!(held_out_rows, (0..23).collect::<Vec<_>>());
+
+    assert!(dataset.k_folds(1, &mut ChaCha8Rng::seed_from_u64(5)).is_err());
+    assert!(dataset.k_folds(24, &mut ChaCha8Rng::seed_from_u64(5)).is_err());
+}
+
+#[test]
+fn cross_validation_reports_every_fold() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let orchestrator = TreeOrchestrator::new(grammar, variables(), float_dataset(20), 20, 3, FLOAT).with_seed(6);
+
+    let result = orchestrator.cross_validate(4, 2).unwrap();
+    assert_eq!(result.fold_fitness.len(), 4);
+    assert!(result.fold_fitness.iter().all(|fitness| fitness.is_finite()));
+    let mean = result.fold_fitness.iter().sum::<f64>() / 4.0;
+    assert!((result.mean - mean).abs() < 1e-12);
+    assert!(result.std_dev >= 0.0);
+
+    // Seeded, so it repeats
+    assert_eq!(orchestrator.cross_validate(4, 2).unwrap(), result);
+    assert!(orchestrator.cross_validate(21, 2).is_err());
+}
@@ -0,0 +1,90 @@
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    ops::Operation,
+    standard::{self, standard_rules},
+    types::{AnyValue, DataType, Shape, TypeInfo},
+};
+
+fn get<T: Clone + 'static>(value: Box<AnyValue>) -> T {
+    value.downcast_ref::<T>().unwrap().clone()
+}
+
+/// Runs the column implementation of the standard scalar rule for `operation`.
+fn batch(data_type: DataType, operation: Operation, left: f64, right: f64) -> f64 {
+    let rule = standard_rules(data_type, &[Shape::Scalar]).into_iter().find(|rule| rule.operation == operation).unwrap();
+    let mut out = [0.0];
+    rule.batch_func.unwrap()(&[left], &[right], &mut out);
+    out[0]
+}
+
+#[test]
+fn division_by_zero_gives_one() {
+    assert_eq!(get::<f64>(standard::divide(&3.5, &0.0)), 1.0);
+    assert_eq!(get::<f64>(standard::divide(&-0.0, &-0.0)), 1.0);
+    assert_eq!(get::<f64>(standard::divide(&3.0, &2.0)), 1.5);
+    assert_eq!(get::<i32>(standard::divide(&7, &0)), 1);
+    assert_eq!(get::<i32>(standard::divide(&-7, &2)), -3);
+    assert_eq!(get::<Vec<f64>>(standard::divide(&vec![2.0, 4.0], &vec![0.0, 2.0])), vec![1.0, 2.0]);
+    assert_eq!(get::<Vec<Vec<i32>>>(standard::divide(&vec![vec![5, 6]], &0)), vec![vec![1, 1]]);
+
+    for data_type in [DataType::Float, DataType::Integer] {
+        assert_eq!(batch(data_type, Operation::Divide, 9.0, 0.0), 1.0, "{:?}", data_type);
+    }
+    assert_eq!(batch(DataType::Integer, Operation::Divide, -7.0, 2.0), -3.0);
+}
+
+#[test]
+fn integer_arithmetic_wraps() {
+    assert_eq!(get::<i32>(standard::add(&i32::MAX, &1)), i32::MIN);
+    assert_eq!(get::<i32>(standard::subtract(&i32::MIN, &1)), i32::MAX);
+    assert_eq!(get::<i32>(standard::multiply(&65536, &65536)), 0);
+    assert_eq!(get::<i32>(standard::divide(&i32::MIN, &-1)), i32::MIN);
+    assert_eq!(get::<Vec<i32>>(standard::add(&vec![i32::MAX, 1], &1)), vec![i32::MIN, 2]);
+
+    let max = i32::MAX as f64;
+    assert_eq!(batch(DataType::Integer, Operation::Add, max, 1.0), i32::MIN as f64);
+    assert_eq!(batch(DataType::Integer, Operation::Multiply, 65536.0, 65536.0), 0.0);
+    assert_eq!(batch(DataType::Integer, Operation::Divide, i32::MIN as f64, -1.0), i32::MIN as f64);
+}
+
+#[test]
+fn matrices_multiply_as_matrices_and_the_rest_element_wise() {
+    let a = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+    let b = vec![vec![5.0, 6.0], vec![7.0, 8.0]];
+
+    assert_eq!(get::<Vec<Vec<f64>>>(standard::multiply(&a, &b)), vec![vec![19.0, 22.0], vec![43.0, 50.0]]);
+    assert_eq!(get::<Vec<Vec<f64>>>(standard::add(&a, &b)), vec![vec![6.0, 8.0], vec![10.0, 12.0]]);
+    assert_eq!(get::<Vec<Vec<f64>>>(standard::multiply(&a, &2.0)), vec![vec![2.0, 4.0], vec![6.0, 8.0]]);
+    assert_eq!(get::<Vec<Vec<f64>>>(standard::subtract(&10.0, &a)), vec![vec![9.0, 8.0], vec![7.0, 6.0]]);
+    assert_eq!(get::<Vec<f64>>(standard::multiply(&vec![1.0, 2.0], &vec![3.0, 4.0])), vec![3.0, 8.0]);
+
+    // A 2x3 by 3x1 product
+    let product = standard::multiply(&vec![vec![1, 2, 3], vec![4, 5, 6]], &vec![vec![1], vec![0], vec![-1]]);
+    assert_eq!(get::<Vec<Vec<i32>>>(product), vec![vec![-2], vec![-2]]);
+}
+
+#[test]
+fn grammars_have_a_rule_per_compatible_pair_of_shapes() {
+    let count = |shapes: &[Shape]| NonTerminalGrammar::standard(DataType::Float, shapes).rules.len();
+
+    assert_eq!(count(&[Shape::Scalar]), 4);
+    // Element-wise only, without scalars to broadcast
+    assert_eq!(count(&[Shape::Vector(3)]), 4);
+    // Scalar and vector in every combination, for every operation
+    assert_eq!(count(&[Shape::Scalar, Shape::Vector(3)]), 16);
+    // Square matrices: no element-wise division of two matrices
+    assert_eq!(count(&[Shape::Scalar, Shape::Matrix(2, 2)]), 15);
+    // Only Add and Subtract, 2x3 matrices do not multiply
+    assert_eq!(count(&[Shape::Matrix(2, 3)]), 2);
+    // Products of 2x3 and 3x2 matrices are square, which are left out
+    assert_eq!(count(&[Shape::Matrix(2, 3), Shape::Matrix(3, 2)]), 4);
+    // With them, 8 pairs multiply as matrices, and each of the 4 shapes adds and subtracts
+    assert_eq!(count(&[Shape::Matrix(2, 3), Shape::Matrix(3, 2), Shape::Matrix(2, 2), Shape::Matrix(3, 3)]), 16);
+
+    for rule in NonTerminalGrammar::standard(DataType::Integer, &[Shape::Scalar, Shape::Vector(2)]).rules {
+        for type_info in [rule.input_one_type, rule.input_two_type, rule.output] {
+            assert_eq!(type_info.data_type, DataType::Integer);
+            assert!(matches!(type_info, TypeInfo { shape: Shape::Scalar | Shape::Vector(2), .. }));
+        }
+    }
+}
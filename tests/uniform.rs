@@ -0,0 +1,97 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+use stsr::{
+    nonterminal::NonTerminalGrammar,
+    possibilities_tables::PossibilityTable,
+    tree_builder::ParseTree,
+    types::{DataType, Shape, TypeInfo, Variable, VariableDefinitions},
+    uniform::{UniformCounts, UniformSampler},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+const VECTOR: TypeInfo = TypeInfo { shape: Shape::Vector(2), data_type: DataType::Float };
+
+/// The node types in preorder. Leaves only keep their type, the sampler draws trees up to which variable or
+/// constant ends them.
+fn shape(tree: &ParseTree) -> String {
+    fn visit(tree: &ParseTree, idx: usize, out: &mut String) {
+        let node = &tree.tree[idx];
+        out.push_str(&format!("{:?} ", node._type));
+        for child in [node.left_index, node.right_index].into_iter().flatten() {
+            visit(tree, child, out);
+        }
+    }
+    let mut out = String::new();
+    visit(tree, 0, &mut out);
+    out
+}
+
+/// Upper critical value of the chi-square distribution with `df` degrees of freedom at p = 0.001, by the
+/// Wilson-Hilferty approximation.
+fn chi_square_critical(df: f64) -> f64 {
+    let z = 3.09;
+    df * (1.0 - 2.0 / (9.0 * df) + z * (2.0 / (9.0 * df)).sqrt()).powi(3)
+}
+
+#[test]
+fn trees_of_a_size_are_sampled_uniformly() {
+    let shapes = [Shape::Scalar, Shape::Vector(2)];
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &shapes).with_linear_algebra(DataType::Float, &shapes);
+    // One variable per leaf type, so enumerated trees and shapes are the same thing
+    let variables = VariableDefinitions::new(vec![Variable { name: "v".to_string(), _type: VECTOR }]);
+    let max_depth = 3;
+    let table = PossibilityTable::new(&grammar, &variables, FLOAT, max_depth);
+    let mut counts = UniformCounts::default();
+    let mut sampler = UniformSampler::new(&grammar, &table, &mut counts);
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+
+    let mut tested = 0;
+    for size in 2..=7 {
+        let mut expected: HashMap<String, usize> = grammar
+            .enumerate_trees(&variables, FLOAT, max_depth)
+            .filter(|tree| tree.tree.len() == size)
+            .map(|tree| (shape(&tree), 0))
+            .collect();
+        assert_eq!(sampler.count(FLOAT, size, max_depth), expected.len() as f64, "size {}", size);
+        if expected.len() < 2 {
+            continue;
+        }
+
+        let samples = 100 * expected.len();
+        for id in 0..samples {
+            let tree = sampler.sample(id, FLOAT, size, &variables, &mut rng).unwrap();
+            tree.validate(&grammar, &variables, FLOAT).unwrap();
+            *expected.get_mut(&shape(&tree)).unwrap_or_else(|| panic!("{} is not enumerated", tree)) += 1;
+        }
+
+        let mean = samples as f64 / expected.len() as f64;
+        let chi_square: f64 = expected.values().map(|seen| (*seen as f64 - mean).powi(2) / mean).sum();
+        let critical = chi_square_critical((expected.len() - 1) as f64);
+        assert!(chi_square < critical, "size {}: chi-square {} over {}", size, chi_square, critical);
+        tested += 1;
+    }
+    assert_eq!(tested, 5);
+}
+
+#[test]
+fn sizes_with_too_many_trees_are_not_sampled() {
+    let grammar = NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar]);
+    let variables = VariableDefinitions::new(vec![Variable { name: "x".to_string(), _type: FLOAT }]);
+    let table = PossibilityTable::new(&grammar, &variables, FLOAT, 10);
+    let mut counts = UniformCounts::default();
+    let mut sampler = UniformSampler::new(&grammar, &table, &mut counts);
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+    // 4^400 operations times the shapes of 400 nodes overflow
+    assert!(sampler.count(FLOAT, 801, 10).is_infinite());
+    assert!(sampler.sample(0, FLOAT, 801, &variables, &mut rng).is_none());
+    assert!(sampler.sample_up_to(0, FLOAT, 801, &variables, &mut rng).is_none());
+
+    // No count turns into NaN, and smaller sizes are still sampled
+    for size in 1..=801 {
+        assert!(!sampler.count(FLOAT, size, 10).is_nan(), "size {}", size);
+    }
+    let tree = sampler.sample_up_to(0, FLOAT, 101, &variables, &mut rng).unwrap();
+    tree.validate(&grammar, &variables, FLOAT).unwrap();
+}
@@ -0,0 +1,105 @@
+use stsr::{
+    batch::BatchScratch,
+    nonterminal::NonTerminalGrammar,
+    tree_builder::ParseTree,
+    types::{AnyValue, DataRow, DataType, Dataset, Shape, TypeInfo, Variable, VariableDefinitions},
+};
+
+const FLOAT: TypeInfo = TypeInfo { shape: Shape::Scalar, data_type: DataType::Float };
+
+fn grammar() -> NonTerminalGrammar {
+    NonTerminalGrammar::standard(DataType::Float, &[Shape::Scalar])
+}
+
+fn variables() -> VariableDefinitions {
+    VariableDefinitions::new(vec![Variable { name: "x".to_string(), _type: FLOAT }])
+}
+
+/// Rows (x, target) for the model x + x.
+fn dataset(rows: &[(f64, f64)]) -> Dataset {
+    let variables = variables();
+    let features = rows.iter().map(|(x, _)| DataRow::new(&variables, vec![Box::new(*x)]).unwrap()).collect();
+    let targets = rows.iter().map(|(_, target)| Box::new(*target) as Box<AnyValue>).collect();
+    Dataset::new(features, targets).unwrap()
+}
+
+fn tree() -> ParseTree {
+    ParseTree::from_sexpr("(Add x x)", &grammar(), &variables(), FLOAT).unwrap()
+}
+
+fn compiled_fitness(dataset: &Dataset) -> f64 {
+    let (grammar, variables, tree) = (grammar(), variables(), tree());
+    tree.compile(&grammar, &variables).unwrap().evaluate_fitness(dataset).unwrap()
+}
+
+fn batched_fitness(dataset: &Dataset) -> f64 {
+    let (grammar, variables, tree) = (grammar(), variables(), tree());
+    let columns = dataset.to_columns(&variables).unwrap();
+    let mut scratch = BatchScratch::new();
+    tree.compile(&grammar, &variables).unwrap().evaluate_fitness_columns(&columns, &mut scratch).unwrap()
+}
+
+// Losses 0, 1, 4 and 1
+const ROWS: [(f64, f64); 4] = [(1.0, 2.0), (2.0, 3.0), (3.0, 2.0), (0.5, 2.0)];
+
+#[test]
+fn weights_change_fitness() {
+    let unweighted = compiled_fitness(&dataset(&ROWS));
+    assert_eq!(unweighted, 1.0 / (1.0 + 6.0 / 4.0));
+
+    let on_the_exact_row = dataset(&ROWS).with_weights(vec![5.0, 1.0, 1.0, 1.0]).unwrap();
+    assert_eq!(compiled_fitness(&on_the_exact_row), 1.0 / (1.0 + 6.0 / 8.0));
+    let on_the_worst_row = dataset(&ROWS).with_weights(vec![1.0, 1.0, 5.0, 1.0]).unwrap();
+    assert_eq!(compiled_fitness(&on_the_worst_row), 1.0 / (1.0 + 22.0 / 8.0));
+
+    // Only the ratios between weights matter
+    let scaled = dataset(&ROWS).with_weights(vec![10.0, 2.0, 2.0, 2.0]).unwrap();
+    assert_eq!(compiled_fitness(&scaled), compiled_fitness(&on_the_exact_row));
+}
+
+#[test]
+fn zero_weight_rows_are_ignored() {
+    let mut rows = ROWS.to_vec();
+    rows.extend([(4.0, -100.0), (7.0, 1e6)]);
+    let padded = dataset(&rows).with_weights(vec![1.0, 1.0, 1.0, 1.0, 0.0, 0.0]).unwrap();
+
+    assert_eq!(compiled_fitness(&padded), compiled_fitness(&dataset(&ROWS)));
+    assert_eq!(batched_fitness(&padded), compiled_fitness(&dataset(&ROWS)));
+}
+
+#[test]
+fn compiled_batched_and_mini_batch_fitness_agree() {
+    let weighted = dataset(&ROWS).with_weights(vec![0.5, 2.0, 1.5, 0.0]).unwrap();
+    let (grammar, variables, tree) = (grammar(), variables(), tree());
+    let program = tree.compile(&grammar, &variables).unwrap();
+    let columns = weighted.to_columns(&variables).unwrap();
+
+    assert_eq!(batched_fitness(&weighted), compiled_fitness(&weighted));
+    for rows in [vec![0, 1, 2, 3], vec![2, 0], vec![1], vec![3, 3, 1]] {
+        let row_wise = program.evaluate_fitness_rows(&weighted, &rows).unwrap();
+        assert_eq!(row_wise, compiled_fitness(&weighted.subset(&rows)), "{:?}", rows);
+        let selected = columns.select(&rows);
+        let batched = program.evaluate_fitness_columns(&selected, &mut BatchScratch::new()).unwrap();
+        assert_eq!(batched, row_wise, "{:?}", rows);
+    }
+}
+
+#[test]
+fn fitness_does_not_depend_on_the_number_of_rows() {
+    let (grammar, variables, tree) = (grammar(), variables(), tree());
+    let program = tree.compile(&grammar, &variables).unwrap();
+    let full = dataset(&ROWS);
+
+    // The same rows twice, or a batch of rows as good as the whole set, give the same fitness
+    assert_eq!(program.evaluate_fitness_rows(&full, &[0, 1, 2, 3, 0, 1, 2, 3]).unwrap(), compiled_fitness(&full));
+    assert_eq!(program.evaluate_fitness_rows(&full, &[1, 3]).unwrap(), 1.0 / 2.0);
+    assert_eq!(program.evaluate_fitness_rows(&full, &[1]).unwrap(), 1.0 / 2.0);
+}
+
+#[test]
+fn weights_have_to_be_valid() {
+    assert!(dataset(&ROWS).with_weights(vec![1.0, 1.0, 1.0]).is_err());
+    assert!(dataset(&ROWS).with_weights(vec![1.0, -1.0, 1.0, 1.0]).is_err());
+    assert!(dataset(&ROWS).with_weights(vec![1.0, f64::NAN, 1.0, 1.0]).is_err());
+    assert!(dataset(&ROWS).with_weights(vec![0.0; 4]).is_err());
+}